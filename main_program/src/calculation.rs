@@ -1,26 +1,73 @@
+use rand::{rngs::SmallRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use timeseries::{SaveItems, Timeseries};
 
 use crate::{
+    checkpoint::{Checkpoint, CheckpointError},
     composite_system::{
         MultipleDistinctFeedbackSystems, MultipleIdenticalFeedbackSystems, SingleFeedbackSystem,
     },
+    coupling_schedule::{self, CouplingSchedule, EdgeParameter},
+    csr_history::CsrHistory,
+    delay_buffer::Compact,
+    dynamical_system::{CombinedWeight, DynamicalSystem, Feedback},
+    edge_selection::EdgeSelection,
     fitzhugh_nagumo,
     hindmarsh_rose,
-    integration_methods::IntegrationMethods,
+    history::SinglePrecisionHistory,
+    integration_methods::{IntegrationMethods, RungeKuttaDelay},
     lang_kobayashi,
     lorenz,
+    lyapunov::{self, LyapunovExponents},
     mackey_glass,
     // mdre,
-    network::Network,
+    network::{Network, SimplificationReport},
+    network_validation::NetworkError,
+    parameter_sweep::{ParameterSweep, PointRecorder, SweepPoints},
+    plasticity::Plasticity,
+    regime_map::RegimeMap,
     roessler,
     stuart_landau,
+    synchronisation::{SynchronisationAnalysis, SynchronisationFiles},
 };
 
 #[allow(dead_code)]
+#[derive(Deserialize)]
 pub enum Tasks {
-    IntegrateUntilTimeNoSave { time: f64 },
-    IntegrateSegmentsAndSave { segments: usize, epsilon: f64 },
+    IntegrateUntilTimeNoSave {
+        time: f64,
+    },
+    IntegrateSegmentsAndSave {
+        segments: usize,
+        epsilon: f64,
+    },
     PrintTechnicalDetails,
+    // e.g. one coupling strength per step of a hysteresis sweep
+    SetEdgeParameter {
+        selection: EdgeSelection,
+        parameter: EdgeParameter,
+        value: f64,
+    },
+    // modulates the edges during the following tasks, an empty schedule ends the modulation
+    ScheduleCoupling(CouplingSchedule),
+    // learning rules for the following tasks, an empty list freezes the strengths again
+    SetPlasticity(Vec<Plasticity>),
+    // other initial conditions, e.g. for the members of an ensemble
+    PerturbStates {
+        amount: f64,
+        seed: u64,
+    },
+    // a bifurcation diagram, not interrupted by checkpoints
+    ParameterSweep(ParameterSweep),
+    // regimes over two parameters, the grid points in parallel. it leaves the state as it was.
+    RegimeMap(RegimeMap),
+    // integrates on while the exponents are computed, not interrupted by checkpoints
+    LyapunovExponents(LyapunovExponents),
+    // like `IntegrateSegmentsAndSave`, the synchrony of the nodes is measured in every segment
+    IntegrateSegmentsAndAnalyse(SynchronisationAnalysis),
     // FindExtrema
     // OtherStuff
 }
@@ -32,10 +79,20 @@ pub struct Calculation<'a, 'b> {
     pub total_steps: u64,
     pub segment_length: usize,
     network: &'a Network,
+    node_setup: NodeSetup,
+    system_type: SystemType,
+    coupling_layout: CouplingLayout,
     pub system: Box<dyn IntegrationMethods>,
     pub timeseries: Timeseries,
     task_sequence: &'b Vec<Tasks>,
     save_items: SaveItems,
+    data_directory: String,
+    // position in `task_sequence`: the next task and its segments (or steps) already done
+    task: usize,
+    task_progress: usize,
+    // (file, every that many segments)
+    checkpoint: Option<(PathBuf, usize)>,
+    segments_since_checkpoint: usize,
 }
 
 #[allow(dead_code)]
@@ -72,62 +129,335 @@ impl<'a, 'b> Calculation<'a, 'b> {
         self.total_steps += self.segment_length as u64;
     }
 
-    pub fn perform_tasks(&mut self) {
-        for task in self.task_sequence {
+    // one segment, saved as `save_items` asks
+    fn integrate_segment_save(&mut self, epsilon: &f64) {
+        match self.save_items.clone() {
+            SaveItems::Timeseries => {
+                self.integrate_segment_save_timeseries(epsilon);
+            }
+            SaveItems::ParametricCurve2d { variable_pairs } => {
+                self.integrate_segment_save_parametric_curves_2d(&variable_pairs, epsilon)
+            }
+            SaveItems::TimeseriesAndParametricCurve2d { variable_pairs } => {
+                self.system.integrate_and_keep_segment(&mut self.timeseries);
+                self.timeseries.save_simplified_timeseries(epsilon);
+                self.timeseries
+                    .save_simplified_parametric_curves(&variable_pairs, epsilon);
+                self.total_steps += self.segment_length as u64;
+            }
+        }
+    }
+
+    // continues where the tasks stopped, e.g. after `resume` from a checkpoint
+    pub fn perform_tasks(&mut self) -> Result<(), String> {
+        let task_sequence = self.task_sequence;
+        while let Some(task) = task_sequence.get(self.task) {
             match task {
                 Tasks::IntegrateUntilTimeNoSave { time } => {
                     let time_in_steps = (time / self.dt) as usize;
-                    self.n_steps_rk4(time_in_steps);
+                    // in pieces of a segment, checkpoints can be saved in between
+                    while self.task_progress < time_in_steps {
+                        let steps = self.segment_length.min(time_in_steps - self.task_progress);
+                        self.n_steps_rk4(steps);
+                        self.task_progress += steps;
+                        self.segment_done()?;
+                    }
                 }
                 Tasks::IntegrateSegmentsAndSave { segments, epsilon } => {
-                    match self.save_items.clone() {
-                        SaveItems::Timeseries => {
-                            for _ in 0..*segments {
-                                self.integrate_segment_save_timeseries(&epsilon);
-                            }
-                        }
-                        SaveItems::ParametricCurve2d { variable_pairs } => {
-                            for _ in 0..*segments {
-                                self.integrate_segment_save_parametric_curves_2d(
-                                    &variable_pairs,
-                                    epsilon,
-                                )
-                            }
-                        }
-                        SaveItems::TimeseriesAndParametricCurve2d { variable_pairs } => {
-                            for _ in 0..*segments {
-                                self.system.integrate_and_keep_segment(&mut self.timeseries);
-                                self.timeseries.save_simplified_timeseries(epsilon);
-                                self.timeseries
-                                    .save_simplified_parametric_curves(&variable_pairs, epsilon)
-                            }
-                        }
+                    while self.task_progress < *segments {
+                        self.integrate_segment_save(epsilon);
+                        self.task_progress += 1;
+                        self.segment_done()?;
                     }
                 }
                 Tasks::PrintTechnicalDetails => {
                     self.timeseries.display_simplification_ratio();
+                    print!("{}", self.system.memory_report());
+                }
+                Tasks::SetEdgeParameter {
+                    selection,
+                    parameter,
+                    value,
+                } => self.set_edge_parameter(selection, *parameter, *value)?,
+                Tasks::ScheduleCoupling(schedule) => {
+                    self.system
+                        .set_coupling_schedule(
+                            schedule
+                                .clone()
+                                .resolve_group_names(&self.network.edge_groups),
+                        )
+                        .map_err(|e| e.to_string())?;
+                }
+                Tasks::SetPlasticity(rules) => {
+                    self.system
+                        .set_plasticity(
+                            rules
+                                .iter()
+                                .map(|p| p.resolve_group_names(&self.network.edge_groups))
+                                .collect(),
+                        )
+                        .map_err(|e| e.to_string())?;
+                }
+                Tasks::PerturbStates { amount, seed } => {
+                    self.system
+                        .perturb_states(*amount, &mut SmallRng::seed_from_u64(*seed));
                 }
-                _ => {
-                    todo!();
+                Tasks::ParameterSweep(sweep) => self.parameter_sweep(sweep)?,
+                Tasks::RegimeMap(map) => self.regime_map(map)?,
+                Tasks::LyapunovExponents(task) => self.lyapunov_exponents(task)?,
+                Tasks::IntegrateSegmentsAndAnalyse(analysis) => {
+                    self.integrate_segments_and_analyse(analysis)?
                 }
             }
+            self.task += 1;
+            self.task_progress = 0;
         }
+        Ok(())
+    }
+
+    fn set_edge_parameter(
+        &mut self,
+        selection: &EdgeSelection,
+        parameter: EdgeParameter,
+        value: f64,
+    ) -> Result<(), String> {
+        // starts from the current edges, so learned strengths are kept
+        let mut edges = self.system.current_edges();
+        for e in edges
+            .iter_mut()
+            .filter(|e| selection.matches(e, &self.network.edge_groups))
+        {
+            *coupling_schedule::parameter_of(e, parameter) = value;
+        }
+        self.system.update_edges(&edges).map_err(|e| e.to_string())
+    }
+
+    // the whole sweep is one piece of the task sequence: a run resumed from a checkpoint
+    // repeats it and writes its file again
+    fn parameter_sweep(&mut self, sweep: &ParameterSweep) -> Result<(), String> {
+        let variables = self.system.timeseries_curve_names();
+        let section = match sweep.points {
+            SweepPoints::Poincare { section, .. } => section,
+            _ => sweep.variable,
+        };
+        if sweep.variable.max(section) >= variables.len() {
+            return Err(format!(
+                "the sweep records variable {} of only {} ({:?})",
+                sweep.variable.max(section),
+                variables.len(),
+                variables
+            ));
+        }
+        let path = Path::new(&self.data_directory).join(&sweep.file);
+        let mut file =
+            BufWriter::new(File::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?);
+        let write_error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+        writeln!(
+            file,
+            "# {}\tnode\t{} ({:?})",
+            sweep.parameter_name(),
+            variables[sweep.variable],
+            sweep.points
+        )
+        .map_err(write_error)?;
+
+        let start = match sweep.continuation {
+            true => None,
+            false => Some(self.system.save_state()?),
+        };
+        let nodes = self.network.get_nodes();
+        let parameter = sweep
+            .parameter
+            .resolve(&self.system.current_edges(), &self.network.edge_groups);
+        for value in sweep.parameter_values() {
+            if let Some(start) = &start {
+                self.system.restore_state(start)?;
+            }
+            parameter.set(self.system.as_mut(), value)?;
+            self.n_steps_rk4((sweep.transient / self.dt) as usize);
+
+            let mut recorder =
+                PointRecorder::new(sweep.points, sweep.variable, nodes, variables.len());
+            for _ in 0..(sweep.record / self.dt) as usize {
+                self.single_step_rk4_count();
+                recorder.push(&self.system.keep_state());
+            }
+            for (node, point) in recorder.found {
+                writeln!(file, "{}\t{}\t{}", value, node, point).map_err(write_error)?;
+            }
+        }
+        file.flush().map_err(write_error)
+    }
+
+    // every grid point gets its own copy of the system, started from the current state
+    fn regime_map(&mut self, map: &RegimeMap) -> Result<(), String> {
+        let variables = self.system.timeseries_curve_names();
+        if map.variable >= variables.len() {
+            return Err(format!(
+                "the regime map records variable {} of only {} ({:?})",
+                map.variable,
+                variables.len(),
+                variables
+            ));
+        }
+        let edges = self.system.current_edges();
+        let columns = map
+            .columns
+            .parameter
+            .resolve(&edges, &self.network.edge_groups);
+        let rows = map
+            .rows
+            .parameter
+            .resolve(&edges, &self.network.edge_groups);
+        let start = self.system.save_state()?;
+        let (network, dt, node_setup, system_type, coupling_layout) = (
+            self.network,
+            self.dt,
+            self.node_setup,
+            self.system_type,
+            self.coupling_layout,
+        );
+        let build =
+            || new_composite_system_of_type(network, dt, node_setup, system_type, coupling_layout);
+        let points = map.scan(build, &start, &columns, &rows, dt)?;
+        map.write(&points, &self.data_directory)
+    }
+
+    fn lyapunov_exponents(&mut self, task: &LyapunovExponents) -> Result<(), String> {
+        let steps = ((task.interval / self.dt) as usize).max(1);
+        let settle = (task.settle / self.dt) as usize / steps;
+        let intervals = ((task.time / self.dt) as usize / steps).max(1);
+        let exponents = lyapunov::lyapunov_spectrum(
+            self.system.as_mut(),
+            self.dt,
+            steps,
+            settle,
+            intervals,
+            task.exponents,
+            |_| {},
+        )?;
+        self.total_steps += ((settle + intervals) * steps) as u64;
+
+        println!("lyapunov exponents: {:?}", exponents);
+        if let Some(dimension) = lyapunov::kaplan_yorke_dimension(&exponents) {
+            println!("kaplan-yorke dimension: {}", dimension);
+        }
+        let path = Path::new(&self.data_directory).join(&task.file);
+        let write_error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+        let mut file = BufWriter::new(File::create(&path).map_err(write_error)?);
+        for exponent in exponents {
+            writeln!(file, "{}", exponent).map_err(write_error)?;
+        }
+        file.flush().map_err(write_error)
+    }
+
+    // the result files are continued after the segments already done, e.g. after `resume`
+    fn integrate_segments_and_analyse(
+        &mut self,
+        analysis: &SynchronisationAnalysis,
+    ) -> Result<(), String> {
+        let nodes = self.network.get_nodes();
+        analysis.check(nodes, self.system.timeseries_curve_names())?;
+        let mut files =
+            SynchronisationFiles::open(&self.data_directory, nodes, self.task_progress)?;
+        while self.task_progress < analysis.segments {
+            self.integrate_segment_save(&analysis.epsilon);
+            let synchrony = analysis.analyse(self.timeseries.segment(), nodes, self.dt);
+            files.write(
+                self.task_progress,
+                self.timeseries.segment_start_time(),
+                &synchrony,
+            )?;
+            self.task_progress += 1;
+            self.segment_done()?;
+        }
+        Ok(())
+    }
+
+    // while performing the tasks, a checkpoint is saved to `path` every `segments` segments.
+    // `IntegrateUntilTimeNoSave` counts in pieces of `segment_length` steps.
+    pub fn checkpoint_every(&mut self, path: impl Into<PathBuf>, segments: usize) {
+        self.checkpoint = Some((path.into(), segments.max(1)));
+        self.segments_since_checkpoint = 0;
+    }
+
+    fn segment_done(&mut self) -> Result<(), String> {
+        let Some((path, every)) = &self.checkpoint else {
+            return Ok(());
+        };
+        self.segments_since_checkpoint += 1;
+        if self.segments_since_checkpoint >= *every {
+            self.segments_since_checkpoint = 0;
+            let path = path.clone();
+            self.save_checkpoint(path).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    // the output files are flushed first, the checkpoint never refers to unwritten lines
+    pub fn save_checkpoint(&mut self, path: impl AsRef<Path>) -> Result<(), CheckpointError> {
+        let checkpoint = Checkpoint {
+            dt: self.dt,
+            nodes: self.network.get_nodes(),
+            segment_length: self.segment_length,
+            node_setup: self.node_setup,
+            system_type: self.system_type,
+            coupling_layout: self.coupling_layout,
+            save_items: self.save_items.clone(),
+            total_steps: self.total_steps,
+            task: self.task,
+            task_progress: self.task_progress,
+            timeseries: self.timeseries.progress()?,
+            system: self.system.save_state().map_err(CheckpointError::System)?,
+        };
+        checkpoint.save(path)
     }
     // +++++++++++++++++++++++++
     // +++++++++++++++++++++++++
     // +++++++++++++++++++++++++
+    // the network is validated for `dt` first. non fatal problems are printed as warnings,
+    // fatal ones are returned (all of them) instead of building the systems.
+    #[allow(clippy::too_many_arguments)]
     pub fn examples(
         dt: f64,
         network: &'a Network,
         segment_length: usize,
         node_setup: NodeSetup,
         system_type: SystemType,
+        coupling_layout: CouplingLayout,
         task_sequence: &'b Vec<Tasks>,
         save_items: SaveItems,
-    ) -> Self {
-        let system = new_composite_system_of_type(&network, dt, node_setup, system_type);
+    ) -> Result<Self, Vec<NetworkError>> {
+        Self::examples_in_directory(
+            "./data",
+            dt,
+            network,
+            segment_length,
+            node_setup,
+            system_type,
+            coupling_layout,
+            task_sequence,
+            save_items,
+        )
+    }
 
-        let timeseries = Timeseries::new(
+    // like `examples`, the curves are saved in `data_directory`
+    #[allow(clippy::too_many_arguments)]
+    pub fn examples_in_directory(
+        data_directory: &str,
+        dt: f64,
+        network: &'a Network,
+        segment_length: usize,
+        node_setup: NodeSetup,
+        system_type: SystemType,
+        coupling_layout: CouplingLayout,
+        task_sequence: &'b Vec<Tasks>,
+        save_items: SaveItems,
+    ) -> Result<Self, Vec<NetworkError>> {
+        let system = Self::validated_system(network, dt, node_setup, system_type, coupling_layout)?;
+
+        let timeseries = Timeseries::new_in_directory(
+            data_directory,
             dt,
             network.get_nodes(),
             system.timeseries_row_len(),
@@ -136,28 +466,198 @@ impl<'a, 'b> Calculation<'a, 'b> {
             &save_items,
         );
 
-        Calculation {
+        Ok(Calculation {
             dt,
             time: 0.0,
             total_steps: 0,
             segment_length,
             network,
+            node_setup,
+            system_type,
+            coupling_layout,
             system,
             timeseries,
             task_sequence,
             save_items,
+            data_directory: data_directory.to_string(),
+            task: 0,
+            task_progress: 0,
+            checkpoint: None,
+            segments_since_checkpoint: 0,
+        })
+    }
+
+    // like `resume_in_directory`, the curves are continued in "./data"
+    pub fn resume(
+        checkpoint: impl AsRef<Path>,
+        network: &'a Network,
+        task_sequence: &'b Vec<Tasks>,
+    ) -> Result<Self, CheckpointError> {
+        Self::resume_in_directory(checkpoint, "./data", network, task_sequence)
+    }
+
+    // continues a calculation from its checkpoint, with the network and the task sequence it
+    // was started with. the saved curves are cut back to the checkpoint and continued.
+    pub fn resume_in_directory(
+        checkpoint: impl AsRef<Path>,
+        data_directory: &str,
+        network: &'a Network,
+        task_sequence: &'b Vec<Tasks>,
+    ) -> Result<Self, CheckpointError> {
+        let checkpoint = Checkpoint::load(checkpoint)?;
+        if checkpoint.nodes != network.get_nodes() {
+            return Err(CheckpointError::Mismatch(format!(
+                "{} nodes instead of {}",
+                checkpoint.nodes,
+                network.get_nodes()
+            )));
+        }
+        if checkpoint.task > task_sequence.len() {
+            return Err(CheckpointError::Mismatch(format!(
+                "task {} of only {}",
+                checkpoint.task,
+                task_sequence.len()
+            )));
+        }
+        let mut system = Self::validated_system(
+            network,
+            checkpoint.dt,
+            checkpoint.node_setup,
+            checkpoint.system_type,
+            checkpoint.coupling_layout,
+        )?;
+        system
+            .restore_state(&checkpoint.system)
+            .map_err(CheckpointError::System)?;
+
+        let timeseries = Timeseries::resume_in_directory(
+            data_directory,
+            checkpoint.dt,
+            network.get_nodes(),
+            system.timeseries_row_len(),
+            checkpoint.segment_length,
+            system.timeseries_curve_names(),
+            &checkpoint.save_items,
+            &checkpoint.timeseries,
+        )?;
+
+        Ok(Calculation {
+            dt: checkpoint.dt,
+            time: 0.0,
+            total_steps: checkpoint.total_steps,
+            segment_length: checkpoint.segment_length,
+            network,
+            node_setup: checkpoint.node_setup,
+            system_type: checkpoint.system_type,
+            coupling_layout: checkpoint.coupling_layout,
+            system,
+            timeseries,
+            task_sequence,
+            save_items: checkpoint.save_items,
+            data_directory: data_directory.to_string(),
+            task: checkpoint.task,
+            task_progress: checkpoint.task_progress,
+            checkpoint: None,
+            segments_since_checkpoint: 0,
+        })
+    }
+
+    fn validated_system(
+        network: &Network,
+        dt: f64,
+        node_setup: NodeSetup,
+        system_type: SystemType,
+        coupling_layout: CouplingLayout,
+    ) -> Result<Box<dyn IntegrationMethods>, Vec<NetworkError>> {
+        if let Err(errors) = network.validate_for_dt(dt) {
+            for warning in errors.iter().filter(|e| !e.is_fatal()) {
+                println!("warning: {}", warning);
+            }
+            let fatal: Vec<NetworkError> = errors.into_iter().filter(|e| e.is_fatal()).collect();
+            if !fatal.is_empty() {
+                return Err(fatal);
+            }
         }
+        let system =
+            new_composite_system_of_type(network, dt, node_setup, system_type, coupling_layout);
+        network.validate_layers(system.coupling_names().len())?;
+        Ok(system)
     }
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum NodeSetup {
     Single,
+    #[default]
     Identical,
     Distinct,
 }
 
+// how the past values are stored for the delayed coupling, see `DelayCoupling`
 #[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum CouplingLayout {
+    #[default]
+    RingBuffers, // `History`: a ring buffer per node
+    Csr, // `CsrHistory`: sparse rows and one array for all nodes, for large networks
+    SinglePrecisionRingBuffers, // `SinglePrecisionHistory`: ring buffers in f32, half the memory
+}
+
+fn multiple_identical<S>(
+    network: &Network,
+    dt: f64,
+    coupling_layout: CouplingLayout,
+) -> Box<dyn IntegrationMethods>
+where
+    S: Feedback + 'static,
+    S::FeedbackT: Compact,
+{
+    match coupling_layout {
+        CouplingLayout::RingBuffers => {
+            Box::new(MultipleIdenticalFeedbackSystems::<S>::new(network, dt))
+        }
+        CouplingLayout::Csr => Box::new(MultipleIdenticalFeedbackSystems::<
+            S,
+            CsrHistory<S, RungeKuttaDelay<S::FeedbackT>>,
+        >::with_coupling(network, dt)),
+        CouplingLayout::SinglePrecisionRingBuffers => {
+            Box::new(MultipleIdenticalFeedbackSystems::<
+                S,
+                SinglePrecisionHistory<S, RungeKuttaDelay<S::FeedbackT>>,
+            >::with_coupling(network, dt))
+        }
+    }
+}
+
+fn multiple_distinct<S>(
+    network: &Network,
+    dt: f64,
+    coupling_layout: CouplingLayout,
+) -> Box<dyn IntegrationMethods>
+where
+    S: Feedback + 'static,
+    S::FeedbackT: Compact,
+{
+    match coupling_layout {
+        CouplingLayout::RingBuffers => {
+            Box::new(MultipleDistinctFeedbackSystems::<S>::new(network, dt))
+        }
+        CouplingLayout::Csr => Box::new(MultipleDistinctFeedbackSystems::<
+            S,
+            CsrHistory<S, RungeKuttaDelay<S::FeedbackT>>,
+        >::with_coupling(network, dt)),
+        CouplingLayout::SinglePrecisionRingBuffers => {
+            Box::new(MultipleDistinctFeedbackSystems::<
+                S,
+                SinglePrecisionHistory<S, RungeKuttaDelay<S::FeedbackT>>,
+            >::with_coupling(network, dt))
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum SystemType {
     LangKobayashi,
     Lorenz,
@@ -169,76 +669,108 @@ pub enum SystemType {
     Roessler,
 }
 
+impl SystemType {
+    // parallel edges are combined with the weights the system actually uses:
+    // phasors for the complex-field lasers and oscillators, plain strengths otherwise
+    pub fn simplify_network(&self, network: &mut Network) -> SimplificationReport {
+        match self {
+            SystemType::LangKobayashi => simplify_for::<lang_kobayashi::System>(network),
+            SystemType::MackeyGlass => simplify_for::<mackey_glass::System>(network),
+            SystemType::Lorenz => simplify_for::<lorenz::System>(network),
+            SystemType::HindmarshRose => simplify_for::<hindmarsh_rose::System>(network),
+            SystemType::StuartLandau => simplify_for::<stuart_landau::System>(network),
+            SystemType::FitzHughNagumo => simplify_for::<fitzhugh_nagumo::System>(network),
+            SystemType::Roessler => simplify_for::<roessler::System>(network),
+        }
+    }
+
+    // the variables of the saved curves, in the order of their indices
+    pub fn variable_names(&self) -> &'static [&'static str] {
+        match self {
+            SystemType::LangKobayashi => lang_kobayashi::System::keep_state_names(),
+            SystemType::MackeyGlass => mackey_glass::System::keep_state_names(),
+            SystemType::Lorenz => lorenz::System::keep_state_names(),
+            SystemType::HindmarshRose => hindmarsh_rose::System::keep_state_names(),
+            SystemType::StuartLandau => stuart_landau::System::keep_state_names(),
+            SystemType::FitzHughNagumo => fitzhugh_nagumo::System::keep_state_names(),
+            SystemType::Roessler => roessler::System::keep_state_names(),
+        }
+    }
+}
+
+fn simplify_for<S: Feedback>(network: &mut Network) -> SimplificationReport
+where
+    S::WeightT: CombinedWeight,
+{
+    network.simplify_network::<S::WeightT>()
+}
+
 pub fn new_composite_system_of_type(
     network: &Network,
     dt: f64,
     node_setup: NodeSetup,
     system_type: SystemType,
+    coupling_layout: CouplingLayout,
 ) -> Box<dyn IntegrationMethods> {
     match (network.get_nodes(), node_setup) {
-        (1, _) => {
+        // a single system only has the ring buffers, other layouts run it as a network of one
+        (1, _) if coupling_layout == CouplingLayout::RingBuffers => {
             print!("### single system of type: ");
             match system_type {
                 SystemType::LangKobayashi => {
                     println!("Lang-Kobayashi");
                     Box::new(SingleFeedbackSystem::<lang_kobayashi::System>::new(
-                        &network, dt,
+                        network, dt,
                     ))
                 }
                 SystemType::MackeyGlass => {
                     println!("Mackey-Glass");
                     Box::new(SingleFeedbackSystem::<mackey_glass::System>::new(
-                        &network, dt,
+                        network, dt,
                     ))
                 }
                 // SystemType::MDRE => {
                 //     println!("Microscopically-Derived-Rate-Equations");
-                //     Box::new(SingleFeedbackSystem::<mdre::System>::new(&network, dt))
+                //     Box::new(SingleFeedbackSystem::<mdre::System>::new(network, dt))
                 // }
                 SystemType::Lorenz => {
                     println!("Lorenz");
-                    Box::new(SingleFeedbackSystem::<lorenz::System>::new(&network, dt))
+                    Box::new(SingleFeedbackSystem::<lorenz::System>::new(network, dt))
                 }
                 SystemType::HindmarshRose => {
                     println!("Hindmarsh-Rose");
                     Box::new(SingleFeedbackSystem::<hindmarsh_rose::System>::new(
-                        &network, dt,
+                        network, dt,
                     ))
                 }
                 SystemType::StuartLandau => {
                     println!("Stuart-Landau");
                     Box::new(SingleFeedbackSystem::<stuart_landau::System>::new(
-                        &network, dt,
+                        network, dt,
                     ))
                 }
                 SystemType::FitzHughNagumo => {
                     println!("FitzHugh-Nagumo");
                     Box::new(SingleFeedbackSystem::<fitzhugh_nagumo::System>::new(
-                        &network, dt,
+                        network, dt,
                     ))
                 }
                 SystemType::Roessler => {
                     println!("Roessler");
-                    Box::new(SingleFeedbackSystem::<roessler::System>::new(&network, dt))
+                    Box::new(SingleFeedbackSystem::<roessler::System>::new(network, dt))
                 }
             }
         }
-        (2.., NodeSetup::Identical) => {
+        (1, _) | (2.., NodeSetup::Identical) => {
             print!("### multiple identical systems of tyle: ");
             match system_type {
                 SystemType::LangKobayashi => {
                     println!("Lang-Kobayashi");
-                    Box::new(
-                        MultipleIdenticalFeedbackSystems::<lang_kobayashi::System>::new(
-                            &network, dt,
-                        ),
-                    )
+                    multiple_identical::<lang_kobayashi::System>(network, dt, coupling_layout)
                 }
                 SystemType::MackeyGlass => {
                     println!("Mackey-Glass");
-                    Box::new(
-                        MultipleIdenticalFeedbackSystems::<mackey_glass::System>::new(&network, dt),
-                    )
+                    multiple_identical::<mackey_glass::System>(network, dt, coupling_layout)
                 }
                 // SystemType::MDRE => {
                 //     println!("Microscopically-Derived-Rate-Equations");
@@ -248,39 +780,23 @@ pub fn new_composite_system_of_type(
                 // }
                 SystemType::Lorenz => {
                     println!("Lorenz");
-                    Box::new(MultipleIdenticalFeedbackSystems::<lorenz::System>::new(
-                        &network, dt,
-                    ))
+                    multiple_identical::<lorenz::System>(network, dt, coupling_layout)
                 }
                 SystemType::HindmarshRose => {
                     println!("Hindmarsh-Rose");
-                    Box::new(
-                        MultipleIdenticalFeedbackSystems::<hindmarsh_rose::System>::new(
-                            &network, dt,
-                        ),
-                    )
+                    multiple_identical::<hindmarsh_rose::System>(network, dt, coupling_layout)
                 }
                 SystemType::StuartLandau => {
                     println!("Stuart-Landau");
-                    Box::new(
-                        MultipleIdenticalFeedbackSystems::<stuart_landau::System>::new(
-                            &network, dt,
-                        ),
-                    )
+                    multiple_identical::<stuart_landau::System>(network, dt, coupling_layout)
                 }
                 SystemType::FitzHughNagumo => {
                     println!("FitzHugh-Nagumo");
-                    Box::new(
-                        MultipleIdenticalFeedbackSystems::<fitzhugh_nagumo::System>::new(
-                            &network, dt,
-                        ),
-                    )
+                    multiple_identical::<fitzhugh_nagumo::System>(network, dt, coupling_layout)
                 }
                 SystemType::Roessler => {
                     println!("Roessler");
-                    Box::new(MultipleIdenticalFeedbackSystems::<roessler::System>::new(
-                        &network, dt,
-                    ))
+                    multiple_identical::<roessler::System>(network, dt, coupling_layout)
                 }
             }
         }
@@ -290,17 +806,11 @@ pub fn new_composite_system_of_type(
             match system_type {
                 SystemType::LangKobayashi => {
                     println!("Lang-Kobayashi");
-                    Box::new(
-                        MultipleDistinctFeedbackSystems::<lang_kobayashi::System>::new(
-                            &network, dt,
-                        ),
-                    )
+                    multiple_distinct::<lang_kobayashi::System>(network, dt, coupling_layout)
                 }
                 SystemType::MackeyGlass => {
                     println!("Mackey-Glass");
-                    Box::new(
-                        MultipleDistinctFeedbackSystems::<mackey_glass::System>::new(&network, dt),
-                    )
+                    multiple_distinct::<mackey_glass::System>(network, dt, coupling_layout)
                 }
                 // SystemType::MDRE => {
                 //     println!("Microscopically-Derived-Rate-Equations");
@@ -310,37 +820,23 @@ pub fn new_composite_system_of_type(
                 // }
                 SystemType::Lorenz => {
                     println!("Lorenz");
-                    Box::new(MultipleDistinctFeedbackSystems::<lorenz::System>::new(
-                        &network, dt,
-                    ))
+                    multiple_distinct::<lorenz::System>(network, dt, coupling_layout)
                 }
                 SystemType::HindmarshRose => {
                     println!("Hindmarsh-Rose");
-                    Box::new(
-                        MultipleDistinctFeedbackSystems::<hindmarsh_rose::System>::new(
-                            &network, dt,
-                        ),
-                    )
+                    multiple_distinct::<hindmarsh_rose::System>(network, dt, coupling_layout)
                 }
                 SystemType::StuartLandau => {
                     println!("Stuart-Landau");
-                    Box::new(
-                        MultipleDistinctFeedbackSystems::<stuart_landau::System>::new(&network, dt),
-                    )
+                    multiple_distinct::<stuart_landau::System>(network, dt, coupling_layout)
                 }
                 SystemType::FitzHughNagumo => {
                     println!("FitzHugh-Nagumo");
-                    Box::new(
-                        MultipleDistinctFeedbackSystems::<fitzhugh_nagumo::System>::new(
-                            &network, dt,
-                        ),
-                    )
+                    multiple_distinct::<fitzhugh_nagumo::System>(network, dt, coupling_layout)
                 }
                 SystemType::Roessler => {
                     println!("Roessler");
-                    Box::new(MultipleDistinctFeedbackSystems::<roessler::System>::new(
-                        &network, dt,
-                    ))
+                    multiple_distinct::<roessler::System>(network, dt, coupling_layout)
                 }
             }
         }
@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use timeseries::{SaveItems, TimeseriesProgress};
+
+use crate::calculation::{CouplingLayout, NodeSetup, SystemType};
+use crate::network_validation::NetworkError;
+
+const CHECKPOINT_VERSION: u32 = 1;
+
+// everything needed to continue a `Calculation` bit-identically. the network and the task
+// sequence are not stored, a run is resumed with the same ones. tasks draw their random
+// numbers from their own seed, there is no generator state to keep between tasks.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub dt: f64,
+    pub nodes: usize,
+    pub segment_length: usize,
+    pub node_setup: NodeSetup,
+    pub system_type: SystemType,
+    pub coupling_layout: CouplingLayout,
+    pub save_items: SaveItems,
+    pub total_steps: u64,
+    pub task: usize,          // the task of the sequence that is performed next
+    pub task_progress: usize, // its segments (or steps of `IntegrateUntilTimeNoSave`) already done
+    pub timeseries: TimeseriesProgress,
+    pub system: Vec<u8>, // `IntegrationMethods::save_state`
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Encoding(bincode::Error),
+    Network(Vec<NetworkError>),
+    System(String),
+    Mismatch(String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckpointError::Io(error) => write!(f, "could not access checkpoint: {}", error),
+            CheckpointError::Encoding(error) => write!(f, "invalid checkpoint: {}", error),
+            CheckpointError::Network(errors) => {
+                write!(f, "the network cannot be integrated:")?;
+                for error in errors {
+                    write!(f, " {};", error)?;
+                }
+                Ok(())
+            }
+            CheckpointError::System(message) => {
+                write!(f, "could not save or restore the system: {}", message)
+            }
+            CheckpointError::Mismatch(message) => {
+                write!(
+                    f,
+                    "the checkpoint belongs to another calculation: {}",
+                    message
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(error: io::Error) -> Self {
+        CheckpointError::Io(error)
+    }
+}
+
+impl From<bincode::Error> for CheckpointError {
+    fn from(error: bincode::Error) -> Self {
+        CheckpointError::Encoding(error)
+    }
+}
+
+impl From<Vec<NetworkError>> for CheckpointError {
+    fn from(errors: Vec<NetworkError>) -> Self {
+        CheckpointError::Network(errors)
+    }
+}
+
+#[allow(dead_code)]
+impl Checkpoint {
+    // written to a temporary file that replaces `path` when it is complete, a job that is
+    // killed while saving keeps its previous checkpoint
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CheckpointError> {
+        let path = path.as_ref();
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, bincode::serialize(&(CHECKPOINT_VERSION, self))?)?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CheckpointError> {
+        let bytes = fs::read(path)?;
+        // the version comes first, older layouts are reported instead of misread
+        let version: u32 = bincode::deserialize(&bytes)?;
+        if version != CHECKPOINT_VERSION {
+            return Err(CheckpointError::Mismatch(format!(
+                "version {}, this program reads version {}",
+                version, CHECKPOINT_VERSION
+            )));
+        }
+        let (_, checkpoint): (u32, Checkpoint) = bincode::deserialize(&bytes)?;
+        Ok(checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculation::{Calculation, Tasks};
+    use crate::edge_selection::EdgeSelection;
+    use crate::network::Network;
+    use crate::plasticity::{Plasticity, PlasticityRule};
+    use std::path::PathBuf;
+
+    fn files_in(directory: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut files: Vec<(PathBuf, Vec<u8>)> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|e| e == "txt"))
+            .map(|path| {
+                (
+                    PathBuf::from(path.file_name().unwrap()),
+                    fs::read(&path).unwrap(),
+                )
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn resumed_run_is_bit_identical() {
+        let dt = 1.0 / 64.0;
+        let mut network = Network::new(3, 0.1, 0.0, 1.0, 0, dt);
+        network.put_bi_ring(0.2, 0.0, 2.0);
+        let tasks = vec![
+            Tasks::PerturbStates {
+                amount: 0.2,
+                seed: 1,
+            },
+            Tasks::SetPlasticity(vec![Plasticity {
+                rule: PlasticityRule::Hebbian {
+                    rate: 0.5,
+                    decay: 1.0,
+                },
+                selection: EdgeSelection::All,
+                min_strength: 0.0,
+                max_strength: 1.0,
+            }]),
+            // two pieces of a segment
+            Tasks::IntegrateUntilTimeNoSave { time: 2.0 },
+            Tasks::IntegrateSegmentsAndSave {
+                segments: 5,
+                epsilon: 0.001,
+            },
+        ];
+        let base = std::env::temp_dir().join("checkpoint_resumed_run_is_bit_identical");
+        let directory = |name: &str| base.join(name).to_string_lossy().to_string();
+        let run = |data_directory: &str| {
+            Calculation::examples_in_directory(
+                data_directory,
+                dt,
+                &network,
+                64,
+                NodeSetup::Distinct,
+                SystemType::MackeyGlass,
+                CouplingLayout::RingBuffers,
+                &tasks,
+                SaveItems::Timeseries,
+            )
+            .unwrap()
+        };
+
+        let mut uninterrupted = run(&directory("uninterrupted"));
+        uninterrupted.perform_tasks().unwrap();
+        uninterrupted.timeseries.progress().unwrap();
+
+        // the last checkpoint is saved after 6 of 7 pieces, the last segment is written again
+        let checkpoint = base.join("run.checkpoint");
+        let mut interrupted = run(&directory("interrupted"));
+        interrupted.checkpoint_every(&checkpoint, 3);
+        interrupted.perform_tasks().unwrap();
+        interrupted.timeseries.progress().unwrap();
+        drop(interrupted);
+
+        let mut resumed = Calculation::resume_in_directory(
+            &checkpoint,
+            &directory("interrupted"),
+            &network,
+            &tasks,
+        )
+        .unwrap();
+        assert_eq!(resumed.total_steps, 6 * 64);
+        resumed.perform_tasks().unwrap();
+        resumed.timeseries.progress().unwrap();
+
+        assert_eq!(resumed.total_steps, uninterrupted.total_steps);
+        assert_eq!(
+            resumed.system.keep_state(),
+            uninterrupted.system.keep_state()
+        );
+        assert_eq!(
+            resumed.system.current_edges(),
+            uninterrupted.system.current_edges()
+        );
+        let files = files_in(&base.join("uninterrupted"));
+        assert_eq!(files.len(), 3);
+        assert_eq!(files_in(&base.join("interrupted")), files);
+
+        let mut other_network = Network::new(4, 0.1, 0.0, 1.0, 0, dt);
+        other_network.put_bi_ring(0.2, 0.0, 2.0);
+        assert!(matches!(
+            Calculation::resume_in_directory(
+                &checkpoint,
+                &directory("other"),
+                &other_network,
+                &tasks
+            ),
+            Err(CheckpointError::Mismatch(_))
+        ));
+        fs::remove_dir_all(base).unwrap();
+    }
+}
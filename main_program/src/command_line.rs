@@ -0,0 +1,463 @@
+use timeseries::SaveItems;
+
+use crate::calculation::{CouplingLayout, NodeSetup, SystemType, Tasks};
+use crate::global_parameter_map::GlobalParameterMap;
+use crate::network::Network;
+use crate::parameter::ArgumentError;
+use crate::var::Var;
+
+// everything the binary can be told on the command line, `-help` lists it
+pub fn parameter_map<'a>() -> GlobalParameterMap<'a> {
+    let mut map = GlobalParameterMap::new();
+
+    map.insert(
+        "integration",
+        "inverse_dt",
+        vec![Var::UFloat(512.0)],
+        vec!["idt"],
+        "integration steps per time unit, dt = 1 / idt",
+        true,
+    );
+    map.insert(
+        "integration",
+        "segment_length",
+        vec![Var::UInt(1024)],
+        vec!["segl"],
+        "steps per segment, the curves are simplified and written per segment",
+        true,
+    );
+    map.insert(
+        "integration",
+        "csr",
+        vec![Var::Switch(false)],
+        vec!["csr"],
+        "sparse rows and one history array for all nodes (`CsrHistory`), for large networks",
+        true,
+    );
+    map.insert(
+        "integration",
+        "f32",
+        vec![Var::Switch(false)],
+        vec!["f32"],
+        "history ring buffers in single precision, half the memory",
+        true,
+    );
+
+    map.insert(
+        "system",
+        "system",
+        vec![Var::Word("lorenz")],
+        vec!["system"],
+        "lang_kobayashi, lorenz, mackey_glass, hindmarsh_rose, stuart_landau, fitzhugh_nagumo or roessler",
+        true,
+    );
+    map.insert(
+        "system",
+        "node_setup",
+        vec![Var::Word("identical")],
+        vec!["setup"],
+        "identical: one model for all nodes, distinct: a model per node",
+        true,
+    );
+    map.insert(
+        "system",
+        "parameter",
+        vec![Var::Word(""), Var::Float(0.0)],
+        vec!["param", "p"],
+        "sets a model parameter of every node, e.g. `-param rho 24.5`",
+        false,
+    );
+
+    map.insert(
+        "network",
+        "nodes",
+        vec![Var::UInt(1)],
+        vec!["nodes", "n"],
+        "number of nodes",
+        true,
+    );
+    map.insert(
+        "network",
+        "seed",
+        vec![Var::UInt(0)],
+        vec!["seed"],
+        "seed of the network's random numbers",
+        true,
+    );
+    map.insert(
+        "network",
+        "tau",
+        vec![Var::UFloat(10.0)],
+        vec!["tau"],
+        "delay of the feedback edge of node 0 that is used without -edge and -ring",
+        true,
+    );
+    map.insert(
+        "network",
+        "edge",
+        vec![
+            Var::Int(0),
+            Var::Int(0),
+            Var::Float(0.1),
+            Var::Turn(0.0),
+            Var::UFloat(10.0),
+        ],
+        vec!["edge"],
+        "an edge: into, from, strength, turn, delay",
+        false,
+    );
+    map.insert(
+        "network",
+        "ring",
+        vec![Var::Float(0.1), Var::Turn(0.0), Var::UFloat(10.0)],
+        vec!["ring"],
+        "a unidirectional ring through all nodes: strength, turn, delay",
+        true,
+    );
+    map.insert(
+        "network",
+        "simplify",
+        vec![Var::Switch(false)],
+        vec!["simplify"],
+        "combine parallel edges with equal delay into one edge (by the weights of -system)",
+        true,
+    );
+
+    map.insert(
+        "output",
+        "epsilon",
+        vec![Var::UFloat(0.005)],
+        vec!["epsilon"],
+        "tolerance of the curve simplification",
+        true,
+    );
+    map.insert(
+        "output",
+        "save",
+        vec![Var::Word("curve")],
+        vec!["save"],
+        "timeseries, curve (the parametric curve of -pair) or both",
+        true,
+    );
+    map.insert(
+        "output",
+        "pair",
+        vec![Var::UInt(0), Var::UInt(2)],
+        vec!["pair"],
+        "the two variables of the parametric curve",
+        true,
+    );
+    map.insert(
+        "output",
+        "data",
+        vec![Var::Word("./data")],
+        vec!["data"],
+        "directory of the curves",
+        true,
+    );
+    map.insert(
+        "output",
+        "checkpoint",
+        vec![Var::Word("")],
+        vec!["checkpoint"],
+        "file the whole integration state is saved to, see -checkpoint_every",
+        true,
+    );
+    map.insert(
+        "output",
+        "checkpoint_every",
+        vec![Var::UInt(10)],
+        vec!["checkpoint_every"],
+        "segments between two checkpoints",
+        true,
+    );
+    map.insert(
+        "output",
+        "resume",
+        vec![Var::Word("")],
+        vec!["resume"],
+        "checkpoint to continue from, with the same network and tasks",
+        true,
+    );
+
+    map.insert(
+        "tasks",
+        "buffer_time",
+        vec![Var::UFloat(0.0)],
+        vec!["buft"],
+        "time integrated without saving, to let transients decay",
+        true,
+    );
+    map.insert(
+        "tasks",
+        "segments",
+        vec![Var::UInt(25)],
+        vec!["segs"],
+        "segments integrated and saved after the buffer time",
+        true,
+    );
+    map.insert(
+        "tasks",
+        "perturb",
+        vec![Var::UFloat(0.0), Var::UInt(0)],
+        vec!["perturb"],
+        "scales the initial states by random factors in [1 - amount, 1 + amount): amount, seed",
+        true,
+    );
+    map.insert(
+        "tasks",
+        "ensemble",
+        vec![Var::UInt(0)],
+        vec!["ensemble"],
+        "runs from perturbed initial conditions, in one thread each, saved in <data>/ensemble",
+        true,
+    );
+    map.insert(
+        "tasks",
+        "config",
+        vec![Var::Word("")],
+        vec!["config"],
+        "a json file that describes the whole run, the other options except -checkpoint, \
+         -checkpoint_every and -resume are ignored",
+        true,
+    );
+
+    map
+}
+
+pub fn dt(parameters: &GlobalParameterMap) -> f64 {
+    1.0 / parameters.value::<f64>("integration", "inverse_dt")
+}
+
+pub fn segment_length(parameters: &GlobalParameterMap) -> Result<usize, ArgumentError> {
+    Ok(parameters.uint_at_least("integration", "segment_length", 1)? as usize)
+}
+
+pub fn coupling_layout(parameters: &GlobalParameterMap) -> Result<CouplingLayout, ArgumentError> {
+    match (
+        parameters.value::<bool>("integration", "csr"),
+        parameters.value::<bool>("integration", "f32"),
+    ) {
+        (false, false) => Ok(CouplingLayout::RingBuffers),
+        (true, false) => Ok(CouplingLayout::Csr),
+        (false, true) => Ok(CouplingLayout::SinglePrecisionRingBuffers),
+        (true, true) => Err(ArgumentError::ConflictingOptions(
+            "-csr".to_string(),
+            "-f32".to_string(),
+        )),
+    }
+}
+
+pub fn system_type(parameters: &GlobalParameterMap) -> Result<SystemType, ArgumentError> {
+    parameters.choice(
+        "system",
+        "system",
+        &[
+            ("lang_kobayashi", SystemType::LangKobayashi),
+            ("lorenz", SystemType::Lorenz),
+            ("mackey_glass", SystemType::MackeyGlass),
+            ("hindmarsh_rose", SystemType::HindmarshRose),
+            ("stuart_landau", SystemType::StuartLandau),
+            ("fitzhugh_nagumo", SystemType::FitzHughNagumo),
+            ("roessler", SystemType::Roessler),
+        ],
+    )
+}
+
+pub fn node_setup(parameters: &GlobalParameterMap) -> Result<NodeSetup, ArgumentError> {
+    parameters.choice(
+        "system",
+        "node_setup",
+        &[
+            ("identical", NodeSetup::Identical),
+            ("distinct", NodeSetup::Distinct),
+        ],
+    )
+}
+
+// `{"name": value, ...}` of every -param, for `IntegrationMethods::set_model_parameters`
+pub fn model_parameters(parameters: &GlobalParameterMap) -> serde_json::Value {
+    let mut model = serde_json::Map::new();
+    for set in parameters.sets("system", "parameter") {
+        if let [Var::Word(name), Var::Float(value)] = set[..] {
+            model.insert(name.to_string(), value.into());
+        }
+    }
+    serde_json::Value::Object(model)
+}
+
+pub fn network(parameters: &GlobalParameterMap) -> Result<Network, ArgumentError> {
+    let mut network = Network::new(
+        parameters.uint_at_least("network", "nodes", 1)? as usize,
+        0.1,
+        0.1,
+        100.0,
+        parameters.value::<u32>("network", "seed") as u64,
+        dt(parameters),
+    );
+    let edges = parameters.sets("network", "edge");
+    for edge in edges {
+        if let [Var::Int(into), Var::Int(from), Var::Float(strength), Var::Turn(turn), Var::UFloat(delay)] =
+            edge[..]
+        {
+            network.put_edge(into as isize, from as isize, strength, turn, delay);
+        }
+    }
+    let ring_given = parameters.was_set("network", "ring");
+    if let [Var::Float(strength), Var::Turn(turn), Var::UFloat(delay)] =
+        parameters.get("network", "ring").parameter[0][..]
+    {
+        if ring_given {
+            network.put_ring(strength, turn, delay);
+        }
+    }
+    if edges.is_empty() && !ring_given {
+        network.put_edge(0, 0, 0.0, 0.5, parameters.value::<f64>("network", "tau"));
+    }
+    if parameters.value::<bool>("network", "simplify") {
+        print!(
+            "{}",
+            system_type(parameters)?.simplify_network(&mut network)
+        );
+    }
+    Ok(network)
+}
+
+pub fn save_items(parameters: &GlobalParameterMap) -> Result<SaveItems, ArgumentError> {
+    let variable_pairs = match parameters.get("output", "pair").parameter[0][..] {
+        [Var::UInt(a), Var::UInt(b)] => vec![[a as usize, b as usize]],
+        _ => unreachable!("-pair has two unsigned ints"),
+    };
+    let save_items = parameters.choice(
+        "output",
+        "save",
+        &[
+            ("timeseries", SaveItems::Timeseries),
+            (
+                "curve",
+                SaveItems::ParametricCurve2d {
+                    variable_pairs: variable_pairs.clone(),
+                },
+            ),
+            (
+                "both",
+                SaveItems::TimeseriesAndParametricCurve2d {
+                    variable_pairs: variable_pairs.clone(),
+                },
+            ),
+        ],
+    )?;
+    // -pair only matters for the curves
+    if !matches!(save_items, SaveItems::Timeseries) {
+        let names = system_type(parameters)?.variable_names();
+        if variable_pairs.iter().flatten().any(|&v| v >= names.len()) {
+            return Err(ArgumentError::OutOfRange {
+                option: parameters.option_name("output", "pair"),
+                value: format!("{} {}", variable_pairs[0][0], variable_pairs[0][1]),
+                expected: format!("indices below {} ({})", names.len(), names.join(", ")),
+            });
+        }
+    }
+    Ok(save_items)
+}
+
+// an optional file, "" if not given
+pub fn file<'a>(parameters: &GlobalParameterMap<'a>, domain: &str, name: &str) -> Option<&'a str> {
+    Some(parameters.word(domain, name)).filter(|file| !file.is_empty())
+}
+
+pub fn tasks(parameters: &GlobalParameterMap) -> Vec<Tasks> {
+    let mut tasks = Vec::new();
+    if let [Var::UFloat(amount), Var::UInt(seed)] =
+        parameters.get("tasks", "perturb").parameter[0][..]
+    {
+        if amount > 0.0 {
+            tasks.push(Tasks::PerturbStates {
+                amount,
+                seed: seed as u64,
+            });
+        }
+    }
+    tasks.push(Tasks::IntegrateUntilTimeNoSave {
+        time: parameters.value::<f64>("tasks", "buffer_time"),
+    });
+    tasks.push(Tasks::IntegrateSegmentsAndSave {
+        segments: parameters.value::<u32>("tasks", "segments") as usize,
+        epsilon: parameters.value::<f64>("output", "epsilon"),
+    });
+    tasks.push(Tasks::PrintTechnicalDetails);
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_line_builds_the_run() {
+        let arguments: Vec<String> = "-idt 64 -system mackey_glass -p beta_0 0.3 -n 3 \
+                                      -edge 0 1 0.1 0 2 -edge 1 2 0.1 0 2 -save timeseries"
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        let mut parameters = parameter_map();
+        parameters.set_from_args(&arguments).unwrap();
+        assert_eq!(dt(&parameters), 1.0 / 64.0);
+        assert_eq!(system_type(&parameters), Ok(SystemType::MackeyGlass));
+        assert_eq!(
+            model_parameters(&parameters),
+            serde_json::json!({"beta_0": 0.3})
+        );
+        let network = network(&parameters).unwrap();
+        assert_eq!(network.nodes, 3);
+        assert_eq!(network.edges.len(), 2);
+        assert!(matches!(save_items(&parameters), Ok(SaveItems::Timeseries)));
+        assert_eq!(file(&parameters, "output", "checkpoint"), None);
+
+        // without edges: the feedback edge of node 0
+        let network = super::network(&parameter_map()).unwrap();
+        assert_eq!(network.edges.len(), 1);
+        assert_eq!(network.edges[0].delay, 10.0);
+
+        let arguments = vec!["-system".to_string(), "lorentz".to_string()];
+        let mut parameters = parameter_map();
+        parameters.set_from_args(&arguments).unwrap();
+        assert!(matches!(
+            system_type(&parameters),
+            Err(ArgumentError::InvalidChoice { .. })
+        ));
+    }
+    #[test]
+    fn values_that_cannot_run_are_rejected() {
+        let parsed = |line: &'static str| {
+            let arguments: Vec<String> = line.split_whitespace().map(String::from).collect();
+            let mut parameters = parameter_map();
+            parameters.set_from_args(&arguments).unwrap();
+            (
+                network(&parameters).map(|_| ()),
+                segment_length(&parameters),
+                save_items(&parameters).map(|_| ()),
+            )
+        };
+        let (network, _, _) = parsed("-n 0");
+        assert_eq!(
+            network.unwrap_err().to_string(),
+            "`-nodes 0` is out of range, expected at least 1"
+        );
+        let (_, segment_length, _) = parsed("-segl 0");
+        assert!(matches!(
+            segment_length,
+            Err(ArgumentError::OutOfRange { .. })
+        ));
+        let (_, _, save_items) = parsed("-pair 0 9");
+        assert_eq!(
+            save_items.unwrap_err().to_string(),
+            "`-pair 0 9` is out of range, expected indices below 3 (x, y, z)"
+        );
+        // mackey_glass saves only x, the pair is not used for timeseries
+        let (_, _, save_items) = parsed("-system mackey_glass -save timeseries");
+        assert!(save_items.is_ok());
+        let (_, _, save_items) = parsed("-system mackey_glass -save curve");
+        assert!(save_items.is_err());
+    }
+}
@@ -1,13 +1,74 @@
+use rand::{rngs::SmallRng, Rng};
 use timeseries::Timeseries;
 
+#[cfg(feature = "parallel")]
+use crate::integration_methods::PARALLEL_MIN_LEN;
 use crate::{
-    dynamical_system::Feedback,
-    history::History,
-    integration_methods::{self, IntegrationMethods, RungeKuttaDelay},
-    network::Network,
+    coupling_schedule::CouplingSchedule,
+    delay_coupling::DelayCoupling,
+    dynamical_system::{Components, Feedback},
+    history::{History, MemoryReport},
+    integration_methods::{self, IntegrationMethods, PlasticInputOf, RungeKuttaDelay},
+    layered_history::LayeredHistory,
+    network::{Edge, Network},
+    network_validation::NetworkError,
+    plasticity::Plasticity,
 };
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
-const EQUAL_RINGBUFFERS: bool = false; // make each ringbuffer as long as the longest one needed
+const EQUAL_RINGBUFFERS: bool = false; // true: every node's buffer as long as the longest delay
+const RESTORED_OTHER_NETWORK: &str = "the saved state belongs to a network of another size";
+
+// what `save_state` writes: time, states, models and the history
+type SavedState<S, States, Models, H = History<S, RungeKuttaDelay<<S as Feedback>::FeedbackT>>> = (
+    f64,
+    States,
+    Models,
+    LayeredHistory<S, RungeKuttaDelay<<S as Feedback>::FeedbackT>, H>,
+);
+
+// `IntegrationMethods::phase_space`: the node states, then the history
+fn phase_space_of<S, H>(
+    states: &[S::StateT],
+    history: &LayeredHistory<S, RungeKuttaDelay<S::FeedbackT>, H>,
+) -> Vec<f64>
+where
+    S: Feedback,
+    H: DelayCoupling<S, RungeKuttaDelay<S::FeedbackT>>,
+{
+    let past = history.past();
+    let (state_len, past_len) = (S::StateT::LEN, RungeKuttaDelay::<S::FeedbackT>::LEN);
+    let mut components = vec![0.0; states.len() * state_len + past.len() * past_len];
+    let (state_part, past_part) = components.split_at_mut(states.len() * state_len);
+    for (s, into) in states.iter().zip(state_part.chunks_exact_mut(state_len)) {
+        s.write_components(into);
+    }
+    for (value, into) in past.iter().zip(past_part.chunks_exact_mut(past_len)) {
+        value.write_components(into);
+    }
+    components
+}
+
+fn set_phase_space_of<S, H>(
+    states: &mut [S::StateT],
+    history: &mut LayeredHistory<S, RungeKuttaDelay<S::FeedbackT>, H>,
+    components: &[f64],
+) where
+    S: Feedback,
+    H: DelayCoupling<S, RungeKuttaDelay<S::FeedbackT>>,
+{
+    let (state_len, past_len) = (S::StateT::LEN, RungeKuttaDelay::<S::FeedbackT>::LEN);
+    let (state_part, past_part) = components.split_at(states.len() * state_len);
+    for (s, from) in states.iter_mut().zip(state_part.chunks_exact(state_len)) {
+        *s = S::StateT::from_components(from);
+    }
+    let past: Vec<RungeKuttaDelay<S::FeedbackT>> = past_part
+        .chunks_exact(past_len)
+        .map(RungeKuttaDelay::from_components)
+        .collect();
+    history.set_past(&past);
+}
 
 // put this function into `Calculation`
 // pub fn new_composite_system<'a, DynSystemT>(
@@ -50,7 +111,11 @@ where
     pub time: f64,
     state: DynSystemT::StateT,
     model: DynSystemT::ModelT,
-    feedback_history: History<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>,
+    feedback_history: LayeredHistory<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>,
+    // scratch reused every step, `[node * layers + layer]`
+    feedback: Vec<[RungeKuttaDelay<DynSystemT::FeedbackT>; 2]>,
+    keep_for_feedback: Vec<RungeKuttaDelay<DynSystemT::FeedbackT>>,
+    plastic: Vec<Vec<PlasticInputOf<DynSystemT>>>, // `[node]`, edges with smooth learning rules
 }
 
 #[allow(dead_code)]
@@ -64,11 +129,16 @@ where
             time: 0.0,
             state: DynSystemT::StateT::default(),
             model: DynSystemT::ModelT::default(),
-            feedback_history: History::<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>::new(
-                dt,
-                &network,
-                EQUAL_RINGBUFFERS,
-            ),
+            feedback_history: LayeredHistory::new(dt, network, EQUAL_RINGBUFFERS),
+            feedback: vec![
+                [RungeKuttaDelay::default(); 2];
+                network.get_nodes() * network.number_of_layers()
+            ],
+            keep_for_feedback: vec![
+                RungeKuttaDelay::default();
+                network.get_nodes() * network.number_of_layers()
+            ],
+            plastic: vec![Vec::new(); network.get_nodes()],
         }
     }
 }
@@ -79,17 +149,21 @@ where
     DynSystemT: Feedback,
 {
     fn single_step_rk4(&mut self) {
-        let mut keep_for_feedback = RungeKuttaDelay::<DynSystemT::FeedbackT>::default();
-        integration_methods::rk4_delay::<DynSystemT>(
+        self.feedback_history
+            .get_all_feedback_rk4_into(&mut self.feedback);
+        self.feedback_history.plastic_inputs_into(&mut self.plastic);
+        integration_methods::rk4_delay_layers::<DynSystemT>(
             &mut self.state,
             &self.model,
-            &mut keep_for_feedback,
-            &self.feedback_history.get_single_node_feedback_rk4(),
+            &mut self.keep_for_feedback,
+            &self.feedback,
+            &mut self.plastic[0],
             &self.dt,
-            DynSystemT::f,
-            DynSystemT::get_feedback,
         );
-        self.feedback_history.push_node_state(0, keep_for_feedback);
+        self.feedback_history
+            .push_node_states_from(&self.keep_for_feedback);
+        self.feedback_history.set_plastic_strengths(&self.plastic);
+        self.feedback_history.advance();
         self.time += self.dt;
     }
 
@@ -105,33 +179,92 @@ where
         timeseries.update_time(&self.time);
         timeseries.segment().iter_mut().for_each(|row| {
             self.single_step_rk4();
-            row.iter_mut()
-                .zip(DynSystemT::keep_state(&self.state))
-                .for_each(|(el, keep_state)| *el = keep_state)
+            DynSystemT::keep_state_into(&self.state, row)
         });
     }
+    fn update_edges(&mut self, edges: &[Edge]) -> Result<(), NetworkError> {
+        self.feedback_history.update_edges(edges)
+    }
+    fn set_coupling_schedule(&mut self, schedule: CouplingSchedule) -> Result<(), NetworkError> {
+        self.feedback_history.set_schedule(schedule)
+    }
+    fn set_plasticity(&mut self, rules: Vec<Plasticity>) -> Result<(), NetworkError> {
+        self.feedback_history.set_plasticity(rules)
+    }
+    fn current_edges(&self) -> Vec<Edge> {
+        self.feedback_history.edges()
+    }
+    fn perturb_states(&mut self, amount: f64, rng: &mut SmallRng) {
+        self.state = self.state * (1.0 + amount * rng.gen_range(-1.0..1.0));
+    }
+    fn coupling_names(&self) -> &'static [&'static str] {
+        DynSystemT::coupling_names()
+    }
+    fn memory_report(&self) -> MemoryReport {
+        self.feedback_history.memory_report()
+    }
     fn timeseries_row_len(&self) -> usize {
         DynSystemT::keep_state(&DynSystemT::StateT::default()).len()
     }
     fn timeseries_curve_names(&self) -> &'static [&'static str] {
         DynSystemT::keep_state_names()
     }
+    fn save_state(&self) -> Result<Vec<u8>, String> {
+        bincode::serialize(&(self.time, &self.state, &self.model, &self.feedback_history))
+            .map_err(|e| e.to_string())
+    }
+    fn restore_state(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let (time, state, model, feedback_history): SavedState<
+            DynSystemT,
+            DynSystemT::StateT,
+            DynSystemT::ModelT,
+        > = bincode::deserialize(bytes).map_err(|e| e.to_string())?;
+        if feedback_history.shape() != self.feedback_history.shape() {
+            return Err(RESTORED_OTHER_NETWORK.to_string());
+        }
+        self.time = time;
+        self.state = state;
+        self.model = model;
+        self.feedback_history = feedback_history;
+        Ok(())
+    }
+    fn set_model_parameters(&mut self, parameters: &serde_json::Value) -> Result<(), String> {
+        self.model = integration_methods::with_parameters(&self.model, parameters)?;
+        Ok(())
+    }
+    fn phase_space(&self) -> Vec<f64> {
+        phase_space_of(std::slice::from_ref(&self.state), &self.feedback_history)
+    }
+    fn set_phase_space(&mut self, components: &[f64]) {
+        set_phase_space_of(
+            std::slice::from_mut(&mut self.state),
+            &mut self.feedback_history,
+            components,
+        )
+    }
 }
 
 // // ++++++++++++++++++++++++++++++++
 
 #[derive(Default)]
 #[allow(dead_code)]
-pub struct MultipleIdenticalFeedbackSystems<DynSystemT>
-where
+pub struct MultipleIdenticalFeedbackSystems<
+    DynSystemT,
+    H = History<DynSystemT, RungeKuttaDelay<<DynSystemT as Feedback>::FeedbackT>>,
+> where
     DynSystemT: Feedback + 'static,
+    H: DelayCoupling<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>,
 {
     dt: f64,
     pub time: f64,
     nodes: usize,
     states: Vec<DynSystemT::StateT>,
     model: DynSystemT::ModelT,
-    feedback_history: History<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>,
+    feedback_history: LayeredHistory<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>, H>,
+    // scratch reused every step, `[node * layers + layer]`
+    feedback: Vec<[RungeKuttaDelay<DynSystemT::FeedbackT>; 2]>,
+    keep_for_feedback: Vec<RungeKuttaDelay<DynSystemT::FeedbackT>>,
+    plastic: Vec<Vec<PlasticInputOf<DynSystemT>>>, // `[node]`, edges with smooth learning rules
 }
 
 #[allow(dead_code)]
@@ -140,46 +273,71 @@ where
     DynSystemT: Feedback,
 {
     pub fn new(network: &Network, dt: f64) -> Self {
+        Self::with_coupling(network, dt)
+    }
+}
+
+#[allow(dead_code)]
+impl<DynSystemT, H> MultipleIdenticalFeedbackSystems<DynSystemT, H>
+where
+    DynSystemT: Feedback,
+    H: DelayCoupling<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>,
+{
+    // with another way to store the past than `History`, e.g. `CsrHistory` for large networks
+    pub fn with_coupling(network: &Network, dt: f64) -> Self {
         MultipleIdenticalFeedbackSystems {
             dt,
             time: 0.0,
             nodes: network.get_nodes(),
             states: vec![DynSystemT::StateT::default(); network.get_nodes()],
             model: DynSystemT::ModelT::default(),
-            feedback_history: History::<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>::new(
-                dt,
-                &network,
-                EQUAL_RINGBUFFERS,
-            ),
+            feedback_history: LayeredHistory::new(dt, network, EQUAL_RINGBUFFERS),
+            feedback: vec![
+                [RungeKuttaDelay::default(); 2];
+                network.get_nodes() * network.number_of_layers()
+            ],
+            keep_for_feedback: vec![
+                RungeKuttaDelay::default();
+                network.get_nodes() * network.number_of_layers()
+            ],
+            plastic: vec![Vec::new(); network.get_nodes()],
         }
     }
 }
 
 #[allow(dead_code)]
-impl<DynSystemT> IntegrationMethods for MultipleIdenticalFeedbackSystems<DynSystemT>
+impl<DynSystemT, H> IntegrationMethods for MultipleIdenticalFeedbackSystems<DynSystemT, H>
 where
     DynSystemT: Feedback,
+    H: DelayCoupling<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>,
 {
     fn single_step_rk4(&mut self) {
-        let mut keep_for_feedback =
-            vec![RungeKuttaDelay::<DynSystemT::FeedbackT>::default(); self.nodes];
-        for ((mut s, mut k), f) in &mut self
-            .states
+        let layers = self.feedback_history.number_of_layers();
+        self.feedback_history
+            .get_all_feedback_rk4_into(&mut self.feedback);
+        self.feedback_history.plastic_inputs_into(&mut self.plastic);
+        let step = |(((s, k), f), p): (((_, _), _), &mut Vec<_>)| {
+            integration_methods::rk4_delay_layers::<DynSystemT>(s, &self.model, k, f, p, &self.dt)
+        };
+        #[cfg(not(feature = "parallel"))]
+        self.states
             .iter_mut()
-            .zip(&mut keep_for_feedback)
-            .zip(&self.feedback_history.get_all_feedback_rk4())
-        {
-            integration_methods::rk4_delay::<DynSystemT>(
-                &mut s,
-                &self.model,
-                &mut k,
-                f,
-                &self.dt,
-                DynSystemT::f,
-                DynSystemT::get_feedback,
-            );
-        }
-        self.feedback_history.push_node_states(keep_for_feedback);
+            .zip(self.keep_for_feedback.chunks_exact_mut(layers))
+            .zip(self.feedback.chunks_exact(layers))
+            .zip(self.plastic.iter_mut())
+            .for_each(step);
+        #[cfg(feature = "parallel")]
+        self.states
+            .par_iter_mut()
+            .zip(self.keep_for_feedback.par_chunks_exact_mut(layers))
+            .zip(self.feedback.par_chunks_exact(layers))
+            .zip(self.plastic.par_iter_mut())
+            .with_min_len(PARALLEL_MIN_LEN)
+            .for_each(step);
+        self.feedback_history
+            .push_node_states_from(&self.keep_for_feedback);
+        self.feedback_history.set_plastic_strengths(&self.plastic);
+        self.feedback_history.advance();
         self.time += self.dt;
     }
 
@@ -205,19 +363,42 @@ where
     fn keep_state(&self) -> Vec<f64> {
         self.states
             .iter()
-            .map(|s| DynSystemT::keep_state(&s))
-            .flatten()
+            .flat_map(|s| DynSystemT::keep_state(s))
             .collect::<Vec<f64>>()
     }
     fn integrate_and_keep_segment(&mut self, timeseries: &mut Timeseries) {
         timeseries.update_time(&self.time);
+        let row_len = self.timeseries_row_len();
         timeseries.segment().iter_mut().for_each(|row| {
             self.single_step_rk4();
-            row.iter_mut()
-                .zip(self.keep_state())
-                .for_each(|(el, keep_state)| *el = keep_state)
+            for (values, s) in row.chunks_exact_mut(row_len).zip(&self.states) {
+                DynSystemT::keep_state_into(s, values)
+            }
         });
     }
+    fn update_edges(&mut self, edges: &[Edge]) -> Result<(), NetworkError> {
+        self.feedback_history.update_edges(edges)
+    }
+    fn set_coupling_schedule(&mut self, schedule: CouplingSchedule) -> Result<(), NetworkError> {
+        self.feedback_history.set_schedule(schedule)
+    }
+    fn set_plasticity(&mut self, rules: Vec<Plasticity>) -> Result<(), NetworkError> {
+        self.feedback_history.set_plasticity(rules)
+    }
+    fn current_edges(&self) -> Vec<Edge> {
+        self.feedback_history.edges()
+    }
+    fn perturb_states(&mut self, amount: f64, rng: &mut SmallRng) {
+        for s in &mut self.states {
+            *s = *s * (1.0 + amount * rng.gen_range(-1.0..1.0));
+        }
+    }
+    fn coupling_names(&self) -> &'static [&'static str] {
+        DynSystemT::coupling_names()
+    }
+    fn memory_report(&self) -> MemoryReport {
+        self.feedback_history.memory_report()
+    }
     fn timeseries_row_len(&self) -> usize {
         DynSystemT::keep_state(&DynSystemT::StateT::default()).len()
     }
@@ -226,22 +407,59 @@ where
         println!("{:?}", names);
         names
     }
+    fn save_state(&self) -> Result<Vec<u8>, String> {
+        bincode::serialize(&(self.time, &self.states, &self.model, &self.feedback_history))
+            .map_err(|e| e.to_string())
+    }
+    fn restore_state(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let (time, states, model, feedback_history): SavedState<
+            DynSystemT,
+            Vec<DynSystemT::StateT>,
+            DynSystemT::ModelT,
+            H,
+        > = bincode::deserialize(bytes).map_err(|e| e.to_string())?;
+        if states.len() != self.nodes || feedback_history.shape() != self.feedback_history.shape() {
+            return Err(RESTORED_OTHER_NETWORK.to_string());
+        }
+        self.time = time;
+        self.states = states;
+        self.model = model;
+        self.feedback_history = feedback_history;
+        Ok(())
+    }
+    fn set_model_parameters(&mut self, parameters: &serde_json::Value) -> Result<(), String> {
+        self.model = integration_methods::with_parameters(&self.model, parameters)?;
+        Ok(())
+    }
+    fn phase_space(&self) -> Vec<f64> {
+        phase_space_of(&self.states, &self.feedback_history)
+    }
+    fn set_phase_space(&mut self, components: &[f64]) {
+        set_phase_space_of(&mut self.states, &mut self.feedback_history, components)
+    }
 }
 
 // // ++++++++++++++++++++++++++++++++
 
 #[derive(Default)]
 #[allow(dead_code)]
-pub struct MultipleDistinctFeedbackSystems<DynSystemT>
-where
+pub struct MultipleDistinctFeedbackSystems<
+    DynSystemT,
+    H = History<DynSystemT, RungeKuttaDelay<<DynSystemT as Feedback>::FeedbackT>>,
+> where
     DynSystemT: Feedback,
+    H: DelayCoupling<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>,
 {
     dt: f64,
     pub time: f64,
     nodes: usize,
     states: Vec<DynSystemT::StateT>,
     models: Vec<DynSystemT::ModelT>,
-    feedback_history: History<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>,
+    feedback_history: LayeredHistory<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>, H>,
+    // scratch reused every step, `[node * layers + layer]`
+    feedback: Vec<[RungeKuttaDelay<DynSystemT::FeedbackT>; 2]>,
+    keep_for_feedback: Vec<RungeKuttaDelay<DynSystemT::FeedbackT>>,
+    plastic: Vec<Vec<PlasticInputOf<DynSystemT>>>, // `[node]`, edges with smooth learning rules
 }
 
 #[allow(dead_code)]
@@ -250,47 +468,75 @@ where
     DynSystemT: Feedback + 'static,
 {
     pub fn new(network: &Network, dt: f64) -> Self {
+        Self::with_coupling(network, dt)
+    }
+}
+
+#[allow(dead_code)]
+impl<DynSystemT, H> MultipleDistinctFeedbackSystems<DynSystemT, H>
+where
+    DynSystemT: Feedback + 'static,
+    H: DelayCoupling<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>,
+{
+    // with another way to store the past than `History`, e.g. `CsrHistory` for large networks
+    pub fn with_coupling(network: &Network, dt: f64) -> Self {
         MultipleDistinctFeedbackSystems {
             dt,
             time: 0.0,
             nodes: network.get_nodes(),
             states: vec![DynSystemT::StateT::default(); network.get_nodes()],
             models: vec![DynSystemT::ModelT::default(); network.get_nodes()],
-            feedback_history: History::<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>::new(
-                dt,
-                &network,
-                EQUAL_RINGBUFFERS,
-            ),
+            feedback_history: LayeredHistory::new(dt, network, EQUAL_RINGBUFFERS),
+            feedback: vec![
+                [RungeKuttaDelay::default(); 2];
+                network.get_nodes() * network.number_of_layers()
+            ],
+            keep_for_feedback: vec![
+                RungeKuttaDelay::default();
+                network.get_nodes() * network.number_of_layers()
+            ],
+            plastic: vec![Vec::new(); network.get_nodes()],
         }
     }
 }
 
 #[allow(dead_code)]
-impl<DynSystemT> IntegrationMethods for MultipleDistinctFeedbackSystems<DynSystemT>
+impl<DynSystemT, H> IntegrationMethods for MultipleDistinctFeedbackSystems<DynSystemT, H>
 where
     DynSystemT: Feedback,
+    H: DelayCoupling<DynSystemT, RungeKuttaDelay<DynSystemT::FeedbackT>>,
 {
     fn single_step_rk4(&mut self) {
-        let mut keep_for_feedback =
-            vec![RungeKuttaDelay::<DynSystemT::FeedbackT>::default(); self.nodes];
-        for (((s, m), f), k) in &mut self
-            .states
+        let layers = self.feedback_history.number_of_layers();
+        self.feedback_history
+            .get_all_feedback_rk4_into(&mut self.feedback);
+        self.feedback_history.plastic_inputs_into(&mut self.plastic);
+        // nodes only read the delayed feedback and their own plastic edges, so they can be
+        // stepped in any order
+        let step = |((((s, m), f), k), p): ((((_, _), _), _), &mut Vec<_>)| {
+            integration_methods::rk4_delay_layers::<DynSystemT>(s, m, k, f, p, &self.dt)
+        };
+        #[cfg(not(feature = "parallel"))]
+        self.states
             .iter_mut()
             .zip(&self.models)
-            .zip(&self.feedback_history.get_all_feedback_rk4())
-            .zip(&mut keep_for_feedback)
-        {
-            integration_methods::rk4_delay::<DynSystemT>(
-                s,
-                m,
-                k,
-                f,
-                &self.dt,
-                DynSystemT::f,
-                DynSystemT::get_feedback,
-            );
-        }
-        self.feedback_history.push_node_states(keep_for_feedback);
+            .zip(self.feedback.chunks_exact(layers))
+            .zip(self.keep_for_feedback.chunks_exact_mut(layers))
+            .zip(self.plastic.iter_mut())
+            .for_each(step);
+        #[cfg(feature = "parallel")]
+        self.states
+            .par_iter_mut()
+            .zip(self.models.par_iter())
+            .zip(self.feedback.par_chunks_exact(layers))
+            .zip(self.keep_for_feedback.par_chunks_exact_mut(layers))
+            .zip(self.plastic.par_iter_mut())
+            .with_min_len(PARALLEL_MIN_LEN)
+            .for_each(step);
+        self.feedback_history
+            .push_node_states_from(&self.keep_for_feedback);
+        self.feedback_history.set_plastic_strengths(&self.plastic);
+        self.feedback_history.advance();
         self.time += self.dt;
     }
 
@@ -316,24 +562,442 @@ where
     fn keep_state(&self) -> Vec<f64> {
         self.states
             .iter()
-            .map(|s| DynSystemT::keep_state(&s))
-            .flatten()
+            .flat_map(|s| DynSystemT::keep_state(s))
             .collect::<Vec<f64>>()
     }
 
     fn integrate_and_keep_segment(&mut self, timeseries: &mut Timeseries) {
         timeseries.update_time(&self.time);
+        let row_len = self.timeseries_row_len();
         timeseries.segment().iter_mut().for_each(|row| {
             self.single_step_rk4();
-            row.iter_mut()
-                .zip(self.keep_state())
-                .for_each(|(el, keep_state)| *el = keep_state)
+            for (values, s) in row.chunks_exact_mut(row_len).zip(&self.states) {
+                DynSystemT::keep_state_into(s, values)
+            }
         });
     }
+    fn update_edges(&mut self, edges: &[Edge]) -> Result<(), NetworkError> {
+        self.feedback_history.update_edges(edges)
+    }
+    fn set_coupling_schedule(&mut self, schedule: CouplingSchedule) -> Result<(), NetworkError> {
+        self.feedback_history.set_schedule(schedule)
+    }
+    fn set_plasticity(&mut self, rules: Vec<Plasticity>) -> Result<(), NetworkError> {
+        self.feedback_history.set_plasticity(rules)
+    }
+    fn current_edges(&self) -> Vec<Edge> {
+        self.feedback_history.edges()
+    }
+    fn perturb_states(&mut self, amount: f64, rng: &mut SmallRng) {
+        for s in &mut self.states {
+            *s = *s * (1.0 + amount * rng.gen_range(-1.0..1.0));
+        }
+    }
+    fn coupling_names(&self) -> &'static [&'static str] {
+        DynSystemT::coupling_names()
+    }
+    fn memory_report(&self) -> MemoryReport {
+        self.feedback_history.memory_report()
+    }
     fn timeseries_row_len(&self) -> usize {
         DynSystemT::keep_state(&DynSystemT::StateT::default()).len()
     }
     fn timeseries_curve_names(&self) -> &'static [&'static str] {
         DynSystemT::keep_state_names()
     }
+    fn save_state(&self) -> Result<Vec<u8>, String> {
+        bincode::serialize(&(
+            self.time,
+            &self.states,
+            &self.models,
+            &self.feedback_history,
+        ))
+        .map_err(|e| e.to_string())
+    }
+    fn restore_state(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let (time, states, models, feedback_history): SavedState<
+            DynSystemT,
+            Vec<DynSystemT::StateT>,
+            Vec<DynSystemT::ModelT>,
+            H,
+        > = bincode::deserialize(bytes).map_err(|e| e.to_string())?;
+        if states.len() != self.nodes
+            || models.len() != self.nodes
+            || feedback_history.shape() != self.feedback_history.shape()
+        {
+            return Err(RESTORED_OTHER_NETWORK.to_string());
+        }
+        self.time = time;
+        self.states = states;
+        self.models = models;
+        self.feedback_history = feedback_history;
+        Ok(())
+    }
+    fn set_model_parameters(&mut self, parameters: &serde_json::Value) -> Result<(), String> {
+        for model in self.models.iter_mut() {
+            *model = integration_methods::with_parameters(model, parameters)?;
+        }
+        Ok(())
+    }
+    fn phase_space(&self) -> Vec<f64> {
+        phase_space_of(&self.states, &self.feedback_history)
+    }
+    fn set_phase_space(&mut self, components: &[f64]) {
+        set_phase_space_of(&mut self.states, &mut self.feedback_history, components)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculation::{new_composite_system_of_type, CouplingLayout, NodeSetup, SystemType};
+    use crate::coupling_schedule::{EdgeParameter, Modulation};
+    use crate::csr_history::CsrHistory;
+    use crate::delay_buffer::Compact;
+    use crate::edge_selection::EdgeSelection;
+    use crate::history::SinglePrecisionHistory;
+    use crate::network::SelectGroup;
+    use crate::plasticity::PlasticityRule;
+    use crate::{hindmarsh_rose, lang_kobayashi, mackey_glass};
+    use std::time::Instant;
+
+    fn ring(nodes: usize, dt: f64) -> Network {
+        let mut network = Network::new(nodes, 0.1, 0.0, 1.0, 0, dt);
+        network.put_bi_ring(0.1, 0.0, 10.0);
+        network
+    }
+
+    // electrical ring with random strengths plus a chemical ring in the "synapse" layer
+    fn hindmarsh_rose_rings(nodes: usize, dt: f64) -> Network {
+        let mut network = ring(nodes, dt);
+        network.randomize_strength(0.05, SelectGroup::AllGroups);
+        let synapse = network.add_layer("synapse");
+        network.put_ring(0.05, 0.0, 2.0);
+        network.move_to_layer(SelectGroup::SingleGroup(1), synapse);
+        network
+    }
+
+    // the serial step as it was written before the scratch buffers
+    fn reference_step<S: Feedback + 'static>(system: &mut MultipleDistinctFeedbackSystems<S>) {
+        let layers = system.feedback_history.number_of_layers();
+        let feedback = system.feedback_history.get_all_feedback_rk4();
+        let mut keep = vec![RungeKuttaDelay::default(); system.states.len() * layers];
+        for (((s, m), f), k) in system
+            .states
+            .iter_mut()
+            .zip(&system.models)
+            .zip(feedback.chunks_exact(layers))
+            .zip(keep.chunks_exact_mut(layers))
+        {
+            integration_methods::rk4_delay_layers::<S>(s, m, k, f, &mut [], &system.dt);
+        }
+        system.feedback_history.push_node_states(keep);
+        system.feedback_history.advance();
+        system.time += system.dt;
+    }
+
+    // with `--features parallel` the nodes are split across threads
+    #[test]
+    fn steps_are_bit_identical_to_serial_reference() {
+        let dt = 1.0 / 64.0;
+        let network = hindmarsh_rose_rings(300, dt);
+        let mut system =
+            MultipleDistinctFeedbackSystems::<hindmarsh_rose::System>::new(&network, dt);
+        let mut reference =
+            MultipleDistinctFeedbackSystems::<hindmarsh_rose::System>::new(&network, dt);
+        for _ in 0..1000 {
+            system.single_step_rk4();
+            reference_step(&mut reference);
+        }
+        let states = system.keep_state();
+        assert!(states.iter().any(|&x| x != states[0]));
+        assert_eq!(states, reference.keep_state());
+    }
+
+    // mean, standard deviation, minimum and maximum of every state variable
+    fn statistics(rows: &[Vec<f64>]) -> Vec<[f64; 4]> {
+        (0..rows[0].len())
+            .map(|v| {
+                let n = rows.len() as f64;
+                let mean = rows.iter().map(|r| r[v]).sum::<f64>() / n;
+                let variance = rows.iter().map(|r| (r[v] - mean).powi(2)).sum::<f64>() / n;
+                let (min, max) = rows
+                    .iter()
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), r| {
+                        (min.min(r[v]), max.max(r[v]))
+                    });
+                [mean, variance.sqrt(), min, max]
+            })
+            .collect()
+    }
+
+    // integrates with the history stored in f64 and in f32 and compares the attractors after
+    // `transient` steps: the largest difference of their statistics and the largest deviation
+    // of the trajectories, both relative to the largest state, and the memory of both histories
+    fn single_precision_deviation<S>(
+        network: &Network,
+        dt: f64,
+        transient: usize,
+        steps: usize,
+    ) -> (f64, f64, usize, usize)
+    where
+        S: Feedback + 'static,
+        S::FeedbackT: Compact,
+    {
+        let mut double = MultipleIdenticalFeedbackSystems::<S>::new(network, dt);
+        let mut single = MultipleIdenticalFeedbackSystems::<
+            S,
+            SinglePrecisionHistory<S, RungeKuttaDelay<S::FeedbackT>>,
+        >::with_coupling(network, dt);
+        double.n_steps_rk4(transient);
+        single.n_steps_rk4(transient);
+        let mut double_rows = Vec::with_capacity(steps);
+        let mut single_rows = Vec::with_capacity(steps);
+        for _ in 0..steps {
+            double.single_step_rk4();
+            single.single_step_rk4();
+            double_rows.push(double.keep_state());
+            single_rows.push(single.keep_state());
+        }
+        let largest = double_rows
+            .iter()
+            .flatten()
+            .fold(0.0, |l: f64, d| l.max(d.abs()));
+        let trajectory = double_rows
+            .iter()
+            .flatten()
+            .zip(single_rows.iter().flatten())
+            .fold(0.0, |dev: f64, (d, s)| dev.max((d - s).abs()));
+        let attractor = statistics(&double_rows)
+            .iter()
+            .flatten()
+            .zip(statistics(&single_rows).iter().flatten())
+            .fold(0.0, |dev: f64, (d, s)| dev.max((d - s).abs()));
+        (
+            attractor / largest,
+            trajectory / largest,
+            double.memory_report().total_bytes(),
+            single.memory_report().total_bytes(),
+        )
+    }
+
+    // the synapses of the second layer learn, the nodes integrate them from the inputs of
+    // either coupling backend
+    #[test]
+    fn plastic_strengths_agree_between_couplings() {
+        let dt = 1.0 / 64.0;
+        let network = hindmarsh_rose_rings(10, dt);
+        let hebbian = Plasticity {
+            rule: PlasticityRule::Hebbian {
+                rate: 0.5,
+                decay: 1.0,
+            },
+            selection: EdgeSelection::Group(1),
+            min_strength: -1.0,
+            max_strength: 1.0,
+        };
+        type Hr = hindmarsh_rose::System;
+        type Csr = CsrHistory<Hr, RungeKuttaDelay<<Hr as Feedback>::FeedbackT>>;
+        let mut history = MultipleDistinctFeedbackSystems::<Hr>::new(&network, dt);
+        let mut csr = MultipleDistinctFeedbackSystems::<Hr, Csr>::with_coupling(&network, dt);
+        history.set_plasticity(vec![hebbian.clone()]).unwrap();
+        csr.set_plasticity(vec![hebbian]).unwrap();
+        history.n_steps_rk4(2000);
+        csr.n_steps_rk4(2000);
+        let (learned, compared) = (history.current_edges(), csr.current_edges());
+        for ((l, c), e) in learned.iter().zip(&compared).zip(&network.edges) {
+            assert!((l.strength - c.strength).abs() < 1e-9, "{:?} {:?}", l, c);
+            assert_eq!(l.strength == e.strength, e.group == 0, "{:?} {:?}", l, e);
+        }
+    }
+
+    #[test]
+    fn plastic_edges_cannot_be_scheduled() {
+        let dt = 1.0 / 64.0;
+        let network = hindmarsh_rose_rings(10, dt);
+        let mut system =
+            MultipleDistinctFeedbackSystems::<hindmarsh_rose::System>::new(&network, dt);
+        let stdp = Plasticity {
+            rule: PlasticityRule::Stdp {
+                potentiation: 0.01,
+                depression: 0.01,
+                tau_plus: 20.0,
+                tau_minus: 20.0,
+                threshold: 1.0,
+            },
+            selection: EdgeSelection::Group(1),
+            min_strength: 0.0,
+            max_strength: 0.1,
+        };
+        let ramp = |group| {
+            CouplingSchedule::new(1).add(
+                SelectGroup::SingleGroup(group),
+                EdgeParameter::Strength,
+                Modulation::Ramp {
+                    start_time: 0.0,
+                    end_time: 10.0,
+                    from: 0.0,
+                    to: 0.05,
+                },
+            )
+        };
+        system.set_plasticity(vec![stdp.clone()]).unwrap();
+        // the electrical ring can be ramped, the synapses learn
+        system.set_coupling_schedule(ramp(0)).unwrap();
+        // edges 0..20 are the electrical ring, the chemical ring follows
+        assert_eq!(
+            system.set_coupling_schedule(ramp(1)),
+            Err(NetworkError::PlasticAndScheduled { edge: 20 })
+        );
+        system.set_plasticity(Vec::new()).unwrap();
+        system.set_coupling_schedule(ramp(1)).unwrap();
+        assert_eq!(
+            system.set_plasticity(vec![stdp]),
+            Err(NetworkError::PlasticAndScheduled { edge: 20 })
+        );
+    }
+
+    // mackey-glass with delay 17 is chaotic: the f32 rounding grows until the trajectories
+    // are unrelated (they differ by 40% of the largest state over 5000 time units), but both
+    // stay on the same attractor (mean, deviation and range agree to 3e-4)
+    #[test]
+    fn single_precision_history_is_accurate() {
+        let dt = 1.0 / 16.0;
+        let mut network = Network::new(1, 0.1, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 0, 1.0, 0.0, 17.0);
+        let (attractor, trajectory, double, single) =
+            single_precision_deviation::<mackey_glass::System>(&network, dt, 500 * 16, 5000 * 16);
+        assert!(trajectory > 0.1, "{}", trajectory);
+        assert!(attractor < 0.01, "{}", attractor);
+        assert_eq!(single * 2, double);
+
+        // lang-kobayashi with weak feedback settles on a continuous-wave state, the f32 rounding
+        // stays at the size of the rounding (about 3e-9 for the attractor, 4e-9 for the trajectory)
+        let dt = 1.0 / 64.0;
+        let mut network = Network::new(1, 0.1, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 0, 0.1, 0.0, 10.0);
+        let (attractor, trajectory, double, single) =
+            single_precision_deviation::<lang_kobayashi::System>(&network, dt, 500 * 64, 2000 * 64);
+        assert!(attractor < 1e-6, "{}", attractor);
+        assert!(trajectory < 1e-6, "{}", trajectory);
+        assert_eq!(single * 2, double);
+    }
+
+    #[test]
+    fn single_node_keeps_the_coupling_layout() {
+        let dt = 1.0 / 64.0;
+        let mut network = Network::new(1, 0.1, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 0, 0.2, 0.0, 20.0);
+        let bytes = |node_setup, coupling_layout| {
+            new_composite_system_of_type(
+                &network,
+                dt,
+                node_setup,
+                SystemType::MackeyGlass,
+                coupling_layout,
+            )
+            .memory_report()
+            .total_bytes()
+        };
+        let double = bytes(NodeSetup::Identical, CouplingLayout::RingBuffers);
+        for node_setup in [NodeSetup::Identical, NodeSetup::Distinct] {
+            let single = bytes(node_setup, CouplingLayout::SinglePrecisionRingBuffers);
+            assert_eq!(single * 2, double);
+        }
+    }
+
+    // the phase space is the whole state: a fresh system given it continues the same way
+    fn continues_from_phase_space(
+        mut system: impl IntegrationMethods,
+        mut fresh: impl IntegrationMethods,
+    ) {
+        system.n_steps_rk4(100);
+        fresh.set_phase_space(&system.phase_space());
+        assert_eq!(fresh.phase_space(), system.phase_space());
+        system.n_steps_rk4(100);
+        fresh.n_steps_rk4(100);
+        assert_eq!(fresh.phase_space(), system.phase_space());
+    }
+
+    #[test]
+    fn phase_space_holds_states_and_history() {
+        type Lk = lang_kobayashi::System;
+        type Csr = CsrHistory<Lk, RungeKuttaDelay<<Lk as Feedback>::FeedbackT>>;
+        let dt = 1.0 / 64.0;
+        let network = ring(3, dt);
+        let system = MultipleIdenticalFeedbackSystems::<Lk>::new(&network, dt);
+        // 3 complex fields and a float per node, state and slope of a complex per stored step
+        let stored: usize = system.memory_report().layers[0]
+            .iter()
+            .map(|m| m.capacity)
+            .sum();
+        assert_eq!(system.phase_space().len(), 3 * 3 + stored * 4);
+        continues_from_phase_space(
+            system,
+            MultipleIdenticalFeedbackSystems::<Lk>::new(&network, dt),
+        );
+        continues_from_phase_space(
+            MultipleIdenticalFeedbackSystems::<Lk, Csr>::with_coupling(&network, dt),
+            MultipleIdenticalFeedbackSystems::<Lk, Csr>::with_coupling(&network, dt),
+        );
+        continues_from_phase_space(
+            MultipleDistinctFeedbackSystems::<Lk>::new(&network, dt),
+            MultipleDistinctFeedbackSystems::<Lk>::new(&network, dt),
+        );
+        let mut network = Network::new(1, 0.1, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 0, 0.1, 0.0, 10.0);
+        continues_from_phase_space(
+            SingleFeedbackSystem::<Lk>::new(&network, dt),
+            SingleFeedbackSystem::<Lk>::new(&network, dt),
+        );
+    }
+
+    // cargo test --release --features parallel -- --ignored --nocapture benchmark_hindmarsh_rose
+    #[test]
+    #[ignore]
+    fn benchmark_hindmarsh_rose() {
+        let dt = 1.0 / 64.0;
+        for nodes in [1000, 5000] {
+            let network = hindmarsh_rose_rings(nodes, dt);
+            let mut system =
+                MultipleDistinctFeedbackSystems::<hindmarsh_rose::System>::new(&network, dt);
+            system.n_steps_rk4(200);
+            let steps = 1_000;
+            let fastest = (0..5)
+                .map(|_| {
+                    let start = Instant::now();
+                    system.n_steps_rk4(steps);
+                    start.elapsed().as_secs_f64()
+                })
+                .fold(f64::INFINITY, f64::min);
+            println!(
+                "{} Hindmarsh-Rose neurons: {:.1} µs per step",
+                nodes,
+                fastest / steps as f64 * 1e6
+            );
+        }
+    }
+
+    // cargo test --release -- --ignored --nocapture benchmark_ring_stepping
+    #[test]
+    #[ignore]
+    fn benchmark_ring_stepping() {
+        let dt = 1.0 / 64.0;
+        let mut system =
+            MultipleIdenticalFeedbackSystems::<mackey_glass::System>::new(&ring(1000, dt), dt);
+        system.n_steps_rk4(1000);
+        let steps = 5_000;
+        // the fastest of several runs, the others are disturbed by other processes
+        let fastest = (0..10)
+            .map(|_| {
+                let start = Instant::now();
+                system.n_steps_rk4(steps);
+                start.elapsed().as_secs_f64()
+            })
+            .fold(f64::INFINITY, f64::min);
+        println!(
+            "1000 node ring: {:.1} µs per step",
+            fastest / steps as f64 * 1e6
+        );
+    }
 }
@@ -0,0 +1,270 @@
+use crate::edge_selection::EdgeSelection;
+use crate::network::{delay_steps, Edge};
+use crate::network_validation::NetworkError;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EdgeParameter {
+    Strength,
+    Turn,
+    Delay,
+}
+
+// all times are simulation times (counted from the start of the integration)
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Modulation {
+    Step {
+        time: f64,
+        value: f64,
+    },
+    // linear from `from` to `to` between the two times, `to` is kept afterwards
+    Ramp {
+        start_time: f64,
+        end_time: f64,
+        from: f64,
+        to: f64,
+    },
+    // added on top of the value, `phase` in multiples of 2 pi like `turn`
+    Sinusoid {
+        amplitude: f64,
+        period: f64,
+        phase: f64,
+    },
+}
+
+#[allow(dead_code)]
+impl Modulation {
+    // before a step or ramp starts, the value is passed through unchanged. this way several
+    // modulations of the same parameter can follow each other (e.g. ramp up, then ramp down).
+    pub fn apply(&self, time: f64, value: f64) -> f64 {
+        match *self {
+            Modulation::Step { time: at, value: v } => {
+                if time >= at {
+                    v
+                } else {
+                    value
+                }
+            }
+            Modulation::Ramp {
+                start_time,
+                end_time,
+                from,
+                to,
+            } => {
+                if time < start_time {
+                    value
+                } else if time >= end_time {
+                    to
+                } else {
+                    from + (to - from) * (time - start_time) / (end_time - start_time)
+                }
+            }
+            Modulation::Sinusoid {
+                amplitude,
+                period,
+                phase,
+            } => value + amplitude * (TAU * (time / period + phase)).sin(),
+        }
+    }
+
+    // bounds of all values this modulation can produce from values in [low, high]
+    fn bounds(&self, [low, high]: [f64; 2]) -> [f64; 2] {
+        match *self {
+            Modulation::Step { value, .. } => [low.min(value), high.max(value)],
+            Modulation::Ramp { from, to, .. } => [low.min(from).min(to), high.max(from).max(to)],
+            Modulation::Sinusoid { amplitude, .. } => {
+                [low - amplitude.abs(), high + amplitude.abs()]
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScheduledChange {
+    pub selection: EdgeSelection, // `GroupNamed` is resolved with `resolve_group_names`
+    pub parameter: EdgeParameter,
+    pub modulation: Modulation,
+}
+
+// changes are applied in order, `update_every` steps (at least 1) the edges are recomputed
+#[allow(dead_code)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CouplingSchedule {
+    pub changes: Vec<ScheduledChange>,
+    pub update_every: usize,
+}
+
+#[allow(dead_code)]
+impl CouplingSchedule {
+    pub fn new(update_every: usize) -> Self {
+        CouplingSchedule {
+            changes: Vec::new(),
+            update_every,
+        }
+    }
+
+    pub fn add(
+        mut self,
+        selection: impl Into<EdgeSelection>,
+        parameter: EdgeParameter,
+        modulation: Modulation,
+    ) -> Self {
+        self.changes.push(ScheduledChange {
+            selection: selection.into(),
+            parameter,
+            modulation,
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn resolve_group_names(mut self, edge_groups: &[String]) -> Self {
+        for change in &mut self.changes {
+            change.selection = change.selection.resolve_group_names(edge_groups);
+        }
+        self
+    }
+
+    pub fn edges_at(&self, base: &[Edge], time: f64) -> Vec<Edge> {
+        let mut edges = Vec::with_capacity(base.len());
+        self.write_edges_at(base, time, &mut edges);
+        edges
+    }
+
+    // `edges_at` into a vector that is reused, it only allocates if it has to grow
+    pub fn write_edges_at(&self, base: &[Edge], time: f64, edges: &mut Vec<Edge>) {
+        edges.clear();
+        edges.extend(base.iter().map(|e| {
+            let mut edge = *e;
+            for change in self.changes.iter().filter(|c| c.selection.matches(e, &[])) {
+                let value = parameter_of(&mut edge, change.parameter);
+                *value = change.modulation.apply(time, *value);
+            }
+            edge
+        }));
+    }
+
+    // smallest delay any edge can reach during the schedule
+    pub fn shortest_delay(&self, base: &[Edge]) -> f64 {
+        base.iter()
+            .map(|e| self.delay_bounds(e)[0])
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    // longest delay any edge can reach during the schedule, the buffers are grown to it
+    pub fn longest_delay(&self, base: &[Edge]) -> f64 {
+        base.iter()
+            .map(|e| self.delay_bounds(e)[1])
+            .fold(0.0, f64::max)
+    }
+
+    fn delay_bounds(&self, edge: &Edge) -> [f64; 2] {
+        self.changes
+            .iter()
+            .filter(|c| c.parameter == EdgeParameter::Delay && c.selection.matches(edge, &[]))
+            .fold([edge.delay; 2], |bounds, c| c.modulation.bounds(bounds))
+    }
+
+    pub fn modulates(&self, edge: &Edge) -> bool {
+        self.changes.iter().any(|c| c.selection.matches(edge, &[]))
+    }
+
+    // every edge has to keep a delay of two steps during the whole schedule
+    pub fn check_delays(&self, base: &[Edge], dt: f64) -> Result<(), NetworkError> {
+        for (i, e) in base.iter().enumerate() {
+            let shortest_delay = self.shortest_delay(std::slice::from_ref(e));
+            let steps = delay_steps(shortest_delay, dt);
+            if steps < 2 {
+                return Err(NetworkError::DelayTooShort {
+                    edge: i,
+                    delay: shortest_delay,
+                    steps,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn parameter_of(edge: &mut Edge, parameter: EdgeParameter) -> &mut f64 {
+    match parameter {
+        EdgeParameter::Strength => &mut edge.strength,
+        EdgeParameter::Turn => &mut edge.turn,
+        EdgeParameter::Delay => &mut edge.delay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Network;
+
+    #[test]
+    fn hysteresis_ramps_follow_each_other() {
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_bi_ring(0.1, 0.0, 1.0);
+        network.put_edge(0, 0, 0.5, 0.0, 1.0);
+        let schedule = CouplingSchedule::new(1)
+            .add(
+                EdgeSelection::Group(0),
+                EdgeParameter::Strength,
+                Modulation::Ramp {
+                    start_time: 10.0,
+                    end_time: 20.0,
+                    from: 0.1,
+                    to: 0.3,
+                },
+            )
+            .add(
+                EdgeSelection::Group(0),
+                EdgeParameter::Strength,
+                Modulation::Ramp {
+                    start_time: 30.0,
+                    end_time: 40.0,
+                    from: 0.3,
+                    to: 0.1,
+                },
+            );
+        let strength_at = |time| schedule.edges_at(&network.edges, time)[0].strength;
+        assert_eq!(strength_at(5.0), 0.1);
+        assert!((strength_at(15.0) - 0.2).abs() < 1e-12);
+        assert_eq!(strength_at(25.0), 0.3);
+        assert!((strength_at(35.0) - 0.2).abs() < 1e-12);
+        assert_eq!(strength_at(45.0), 0.1);
+        // other groups are untouched
+        let edges = schedule.edges_at(&network.edges, 15.0);
+        assert_eq!(edges.last().unwrap().strength, 0.5);
+    }
+
+    #[test]
+    fn shortest_delay_bounds_modulations() {
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_bi_ring(0.1, 0.0, 1.0);
+        let schedule = CouplingSchedule::new(1)
+            .add(
+                EdgeSelection::All,
+                EdgeParameter::Delay,
+                Modulation::Step {
+                    time: 1.0,
+                    value: 2.0,
+                },
+            )
+            .add(
+                EdgeSelection::All,
+                EdgeParameter::Delay,
+                Modulation::Sinusoid {
+                    amplitude: 0.25,
+                    period: 3.0,
+                    phase: 0.0,
+                },
+            );
+        assert_eq!(schedule.shortest_delay(&network.edges), 0.75);
+    }
+}
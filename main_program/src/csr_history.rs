@@ -0,0 +1,622 @@
+use crate::coupling_schedule::CouplingSchedule;
+use crate::delay_coupling::DelayCoupling;
+use crate::dynamical_system::{Feedback, WeightFromEdge};
+use crate::history::NodeMemory;
+#[cfg(feature = "parallel")]
+use crate::integration_methods::PARALLEL_MIN_LEN;
+use crate::network::{delay_steps, Edge, Network};
+use crate::network_validation::{self, NetworkError};
+use crate::plasticity::{Activity, PlasticEdges, PlasticInput, Plasticity};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+use std::mem;
+
+// coupling backend for large sparse networks.
+// the past of all nodes is a single time-major ring `values[slot * nodes + node]`, as deep
+// as the longest delay, every step overwrites one slot. the edges are compressed sparse rows
+// by receiving node, each row sorted by delay, with one array per field. an edge is read at
+// `next * nodes + offset`, wrapped with a compare instead of a ring buffer lookup.
+// a value keeps its parts together (e.g. state and slope of `RungeKuttaDelay`): the reads are
+// gathers by source node, one array per part touches twice the cache lines and was about two
+// times slower for 10k nodes.
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize)]
+pub struct CsrHistory<S, T>
+where
+    S: Feedback,
+{
+    nodes: usize,
+    depth: usize, // stored steps, the longest delay
+    values: Vec<T>,
+    next: usize,           // slot that is written next, the oldest one
+    row_start: Vec<usize>, // the edges into node n are `row_start[n]..row_start[n + 1]`
+    from: Vec<usize>,
+    delay_steps: Vec<usize>,
+    offset: Vec<usize>, // (depth - delay_steps) * nodes + from
+    weight: Vec<S::WeightT>,
+    entry_of_edge: Vec<usize>, // network's edge i is stored at `entry_of_edge[i]`
+    dt: f64,
+    steps: u64,
+    edges: Vec<Edge>, // current edges without the schedule's modulation
+    schedule: CouplingSchedule,
+    #[serde(skip)]
+    modulated: Vec<Edge>, // scratch for the edges of the schedule, reused every update
+    plasticity: PlasticEdges,
+}
+
+#[allow(dead_code)]
+impl<S, T> CsrHistory<S, T>
+where
+    S: Feedback,
+    T: Sized
+        + Clone
+        + Copy
+        + Default
+        + core::iter::Sum
+        + std::ops::Add<Output = T>
+        + std::ops::AddAssign
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync,
+{
+    pub fn time(&self) -> f64 {
+        self.steps as f64 * self.dt
+    }
+
+    // sorts the edges into rows and grows the ring if a delay is longer than it.
+    // the network is expected to be validated (`Network::validate_for_dt`) beforehand.
+    fn build(&mut self, edges: &[Edge]) {
+        let delay_steps: Vec<usize> = edges
+            .iter()
+            .map(|e| delay_steps(e.delay, self.dt))
+            .collect();
+        let longest = delay_steps.iter().copied().max().unwrap_or(0).max(1);
+        if longest > self.depth {
+            self.grow(longest);
+        }
+
+        let mut order: Vec<usize> = (0..edges.len()).collect();
+        order.sort_by_key(|&i| (edges[i].into, delay_steps[i], edges[i].from));
+        self.row_start = vec![0; self.nodes + 1];
+        for e in edges {
+            self.row_start[e.into + 1] += 1;
+        }
+        for n in 0..self.nodes {
+            self.row_start[n + 1] += self.row_start[n];
+        }
+        self.from = order.iter().map(|&i| edges[i].from).collect();
+        self.delay_steps = order.iter().map(|&i| delay_steps[i]).collect();
+        self.weight = order
+            .iter()
+            .map(|&i| S::WeightT::from_edge(&edges[i]))
+            .collect();
+        self.entry_of_edge = vec![0; edges.len()];
+        for (k, &i) in order.iter().enumerate() {
+            self.entry_of_edge[i] = k;
+        }
+        self.offset = self
+            .delay_steps
+            .iter()
+            .zip(&self.from)
+            .map(|(&d, &from)| (self.depth - d) * self.nodes + from)
+            .collect();
+    }
+
+    // the part of the past that was never stored is filled with the oldest step
+    fn grow(&mut self, depth: usize) {
+        let mut values = Vec::with_capacity(depth * self.nodes);
+        if self.depth == 0 {
+            values.resize(depth * self.nodes, T::default());
+        } else {
+            // age 1 is the newest step, age `self.depth` the oldest
+            for age in (1..=depth).rev() {
+                let slot = (self.next + self.depth - age.min(self.depth)) % self.depth;
+                values.extend_from_slice(&self.values[slot * self.nodes..][..self.nodes]);
+            }
+        }
+        self.values = values;
+        self.depth = depth;
+        self.next = 0;
+    }
+
+    // position of edge entry k's value `delay_steps[k]` steps ago
+    #[inline]
+    fn index(&self, k: usize) -> usize {
+        let i = self.next * self.nodes + self.offset[k];
+        if i >= self.values.len() {
+            i - self.values.len()
+        } else {
+            i
+        }
+    }
+
+    fn newest_slot(&self) -> usize {
+        if self.next == 0 {
+            self.depth - 1
+        } else {
+            self.next - 1
+        }
+    }
+
+    fn sum_into(&self, feedback: &mut [[T; 2]]) {
+        let (values, offset, weight, row_start) =
+            (&self.values, &self.offset, &self.weight, &self.row_start);
+        let (cursor, len, nodes) = (self.next * self.nodes, self.values.len(), self.nodes);
+        let sum = |(node, sums): (usize, &mut [T; 2])| {
+            let entries = row_start[node]..row_start[node + 1];
+            let mut s = [T::default(); 2];
+            for (&o, &w) in offset[entries.clone()].iter().zip(&weight[entries]) {
+                // value at the delay, then one step later
+                let mut i = cursor + o;
+                if i >= len {
+                    i -= len;
+                }
+                let mut j = i + nodes;
+                if j >= len {
+                    j -= len;
+                }
+                s[0] += values[i] * w;
+                s[1] += values[j] * w;
+            }
+            *sums = s;
+        };
+        #[cfg(not(feature = "parallel"))]
+        feedback.iter_mut().enumerate().for_each(sum);
+        #[cfg(feature = "parallel")]
+        feedback
+            .par_iter_mut()
+            .enumerate()
+            .with_min_len(PARALLEL_MIN_LEN)
+            .for_each(sum);
+    }
+
+    // like `History`: the ring grows once to the longest delay of the schedule
+    fn reserve(&mut self, longest_delay: f64) {
+        let longest = delay_steps(longest_delay, self.dt);
+        if longest > self.depth {
+            self.grow(longest);
+        }
+    }
+
+    fn apply_schedule(&mut self) -> Result<(), NetworkError> {
+        let mut modulated = mem::take(&mut self.modulated);
+        self.schedule
+            .write_edges_at(&self.edges, self.time(), &mut modulated);
+        let result = self.apply_edges(&modulated);
+        self.modulated = modulated;
+        result
+    }
+
+    // weights are changed in place, the rows are sorted again if a delay changes
+    fn apply_edges(&mut self, edges: &[Edge]) -> Result<(), NetworkError> {
+        for (i, e) in edges.iter().enumerate() {
+            let steps = delay_steps(e.delay, self.dt);
+            if steps < 2 {
+                return Err(NetworkError::DelayTooShort {
+                    edge: i,
+                    delay: e.delay,
+                    steps,
+                });
+            }
+        }
+        let mut delays_changed = false;
+        for (e, &k) in edges.iter().zip(&self.entry_of_edge) {
+            self.weight[k] = S::WeightT::from_edge(e);
+            delays_changed |= self.delay_steps[k] != delay_steps(e.delay, self.dt);
+        }
+        if delays_changed {
+            self.build(edges);
+        }
+        Ok(())
+    }
+}
+
+impl<S, T> DelayCoupling<S, T> for CsrHistory<S, T>
+where
+    S: Feedback,
+    T: Sized
+        + Clone
+        + Copy
+        + Default
+        + core::iter::Sum
+        + std::ops::Add<Output = T>
+        + std::ops::AddAssign
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync
+        + Serialize
+        + DeserializeOwned,
+{
+    // all nodes share the ring, `equal_ringbuffers` makes no difference
+    fn new(dt: f64, network: &Network, _equal_ringbuffers: bool) -> Self {
+        let mut history = CsrHistory {
+            nodes: network.nodes,
+            dt,
+            edges: network.edges.clone(),
+            ..Default::default()
+        };
+        history.build(&network.edges);
+        history
+    }
+
+    fn get_all_feedback_rk4(&self) -> Vec<[T; 2]> {
+        let mut feedback = vec![[T::default(); 2]; self.nodes];
+        self.sum_into(&mut feedback);
+        feedback
+    }
+
+    fn get_all_feedback_rk4_into(&mut self, feedback: &mut [[T; 2]]) {
+        self.sum_into(feedback)
+    }
+
+    fn push_node_states_from(&mut self, new_states: &[T]) {
+        self.values[self.next * self.nodes..][..self.nodes].copy_from_slice(new_states);
+        self.next += 1;
+        if self.next == self.depth {
+            self.next = 0;
+        }
+    }
+
+    // like `History::update_edges`, an active schedule modulates the new edges right away
+    fn update_edges(&mut self, edges: &[Edge]) -> Result<(), NetworkError> {
+        self.check_edges(edges)?;
+        self.reserve(self.schedule.longest_delay(edges));
+        self.apply_edges(&self.schedule.edges_at(edges, self.time()))?;
+        self.edges = edges.to_vec();
+        Ok(())
+    }
+
+    fn set_schedule(&mut self, schedule: CouplingSchedule) -> Result<(), NetworkError> {
+        self.check_schedule(&schedule)?;
+        self.schedule = schedule;
+        self.reserve(self.schedule.longest_delay(&self.edges));
+        self.apply_schedule()
+    }
+
+    fn set_plasticity(&mut self, rules: Vec<Plasticity>) -> Result<(), NetworkError> {
+        let plasticity = PlasticEdges::new(rules, &self.edges);
+        plasticity.check_schedule(&self.schedule, &self.edges)?;
+        self.plasticity = plasticity;
+        Ok(())
+    }
+
+    fn check_edges(&self, edges: &[Edge]) -> Result<(), NetworkError> {
+        network_validation::check_same_edges(&self.edges, edges)?;
+        self.schedule.check_delays(edges, self.dt)
+    }
+
+    fn check_schedule(&self, schedule: &CouplingSchedule) -> Result<(), NetworkError> {
+        schedule.check_delays(&self.edges, self.dt)?;
+        self.plasticity.check_schedule(schedule, &self.edges)
+    }
+
+    fn check_plasticity(&self, rules: &[Plasticity]) -> Result<(), NetworkError> {
+        PlasticEdges::new(rules.to_vec(), &self.edges).check_schedule(&self.schedule, &self.edges)
+    }
+
+    fn advance_schedule(&mut self) {
+        self.steps += 1;
+        if !self.schedule.is_empty()
+            && self
+                .steps
+                .is_multiple_of(self.schedule.update_every.max(1) as u64)
+        {
+            self.apply_schedule()
+                .expect("delays are checked in `set_schedule` and `update_edges`");
+        }
+    }
+
+    fn plastic_inputs_into(&self, layer: usize, inputs: &mut [Vec<PlasticInput<T, S::WeightT>>]) {
+        if self.plasticity.is_empty() {
+            return;
+        }
+        for (i, e) in self.edges.iter().enumerate() {
+            if !self.plasticity.is_smooth(i) {
+                continue;
+            }
+            // at the delay, then one step later, as in `sum_into`
+            let at = self.index(self.entry_of_edge[i]);
+            let mut later = at + self.nodes;
+            if later >= self.values.len() {
+                later -= self.values.len();
+            }
+            let arriving = [self.values[at], self.values[later]];
+            let unit_weight = S::WeightT::from_edge(&Edge {
+                strength: 1.0,
+                ..*e
+            });
+            inputs[e.into].extend(self.plasticity.input(i, e, layer, arriving, unit_weight));
+        }
+    }
+
+    fn set_plastic_strengths(&mut self, layer: usize, inputs: &[Vec<PlasticInput<T, S::WeightT>>]) {
+        for p in inputs.iter().flatten().filter(|p| p.layer == layer) {
+            self.edges[p.edge].strength = p.strength;
+            self.weight[self.entry_of_edge[p.edge]] = S::WeightT::from_edge(&self.edges[p.edge]);
+        }
+    }
+
+    fn advance_plasticity(&mut self)
+    where
+        T: Activity,
+    {
+        if self.plasticity.is_empty() {
+            return;
+        }
+        let newest = self.newest_slot() * self.nodes;
+        let cursor = self.next * self.nodes;
+        let (values, offset, entry_of_edge) = (&self.values, &self.offset, &self.entry_of_edge);
+        self.plasticity.step(&mut self.edges, self.dt, |i, e| {
+            // `index` of the entry, without borrowing all of `self`
+            let mut at = cursor + offset[entry_of_edge[i]];
+            if at >= values.len() {
+                at -= values.len();
+            }
+            (values[at].activity(), values[newest + e.into].activity())
+        });
+        for (i, &k) in self.entry_of_edge.iter().enumerate() {
+            if self.plasticity.is_plastic(i) {
+                self.weight[k] = S::WeightT::from_edge(&self.edges[i]);
+            }
+        }
+    }
+
+    fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    fn memory_per_node(&self) -> Vec<NodeMemory> {
+        let mut longest = vec![0; self.nodes];
+        for (&from, &d) in self.from.iter().zip(&self.delay_steps) {
+            longest[from] = longest[from].max(d);
+        }
+        longest
+            .into_iter()
+            .enumerate()
+            .map(|(node, delay_steps)| NodeMemory {
+                node,
+                delay_steps,
+                capacity: self.depth,
+                bytes: self.depth * mem::size_of::<T>(),
+            })
+            .collect()
+    }
+
+    // slot by slot from the oldest, all nodes of a slot together
+    fn past(&self) -> Vec<T> {
+        let (newer, older) = self.values.split_at(self.next * self.nodes);
+        older.iter().chain(newer).copied().collect()
+    }
+
+    fn set_past(&mut self, past: &[T]) -> usize {
+        let len = self.values.len();
+        let (newer, older) = self.values.split_at_mut(self.next * self.nodes);
+        older.copy_from_slice(&past[..older.len()]);
+        newer.copy_from_slice(&past[older.len()..len]);
+        len
+    }
+}
+
+impl<S, T> Default for CsrHistory<S, T>
+where
+    S: Feedback,
+{
+    fn default() -> Self {
+        CsrHistory {
+            nodes: 0,
+            depth: 0,
+            values: Vec::new(),
+            next: 0,
+            row_start: vec![0],
+            from: Vec::new(),
+            delay_steps: Vec::new(),
+            offset: Vec::new(),
+            weight: Vec::new(),
+            entry_of_edge: Vec::new(),
+            dt: 1.0 / 64.0,
+            steps: 0,
+            edges: Vec::new(),
+            schedule: CouplingSchedule::default(),
+            modulated: Vec::new(),
+            plasticity: PlasticEdges::default(),
+        }
+    }
+}
+
+impl<S, T> fmt::Display for CsrHistory<S, T>
+where
+    S: Feedback,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "`csr history` stores {} steps of {} nodes = {} bytes",
+            self.depth,
+            self.nodes,
+            self.values.len() * mem::size_of::<T>()
+        )?;
+        writeln!(
+            f,
+            "{} edges in sparse rows, delays from {} to {} steps",
+            self.from.len(),
+            self.delay_steps.iter().min().unwrap_or(&0),
+            self.delay_steps.iter().max().unwrap_or(&0)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coupling_schedule::{EdgeParameter, Modulation};
+    use crate::history::History;
+    use crate::integration_methods::RungeKuttaDelay;
+    use crate::mackey_glass;
+    use crate::network::SelectGroup;
+    use crate::random_networks::{EdgeValues, ValueDistribution};
+    use std::hint::black_box;
+    use std::time::Instant;
+
+    type Value = RungeKuttaDelay<f64>;
+
+    // on average `k` edges into every node
+    fn sparse_network(nodes: usize, k: usize, dt: f64) -> Network {
+        let mut network = Network::new(nodes, 0.1, 0.0, 1.0, 0, dt);
+        let values = EdgeValues {
+            strength: ValueDistribution::Uniform {
+                low: -0.2,
+                high: 0.2,
+            },
+            turn: ValueDistribution::Constant(0.0),
+            delay: ValueDistribution::Uniform {
+                low: 0.5,
+                high: 20.0,
+            },
+        };
+        network.put_erdos_renyi(k as f64 / nodes as f64, values);
+        network
+    }
+
+    fn push_step(
+        history: &mut impl DelayCoupling<mackey_glass::System, f64>,
+        nodes: usize,
+        step: usize,
+    ) {
+        let states: Vec<f64> = (0..nodes)
+            .map(|n| ((step * 7 + n * 13) % 101) as f64 / 101.0)
+            .collect();
+        history.push_node_states_from(&states);
+    }
+
+    fn assert_close(a: &[[f64; 2]], b: &[[f64; 2]]) {
+        for (x, y) in a.iter().flatten().zip(b.iter().flatten()) {
+            assert!((x - y).abs() < 1e-12, "{} != {}", x, y);
+        }
+    }
+
+    #[test]
+    fn sums_like_ring_buffer_history() {
+        let dt = 1.0 / 16.0;
+        let mut network = sparse_network(50, 4, dt);
+        let mut csr = CsrHistory::<mackey_glass::System, f64>::new(dt, &network, false);
+        let mut history = History::<mackey_glass::System, f64>::new(dt, &network, false);
+        for step in 0..500 {
+            push_step(&mut csr, 50, step);
+            push_step(&mut history, 50, step);
+        }
+        let mut in_place = vec![[0.0; 2]; 50];
+        csr.get_all_feedback_rk4_into(&mut in_place);
+        assert_eq!(in_place, csr.get_all_feedback_rk4());
+        assert_close(&in_place, &history.get_all_feedback_rk4());
+
+        // a longer delay grows the ring. the backends fill the never stored past differently,
+        // they agree again once it is overwritten.
+        network.edges[3].delay = 40.0;
+        network.edges[5].strength = 1.0;
+        csr.update_edges(&network.edges).unwrap();
+        history.update_edges(&network.edges).unwrap();
+        assert_eq!(csr.depth, 640);
+        for step in 500..1200 {
+            push_step(&mut csr, 50, step);
+            push_step(&mut history, 50, step);
+        }
+        assert_close(&csr.get_all_feedback_rk4(), &history.get_all_feedback_rk4());
+        network.edges[0].delay = dt;
+        assert!(csr.update_edges(&network.edges).is_err());
+    }
+
+    #[test]
+    fn delays_a_few_ulps_short_are_rounded() {
+        let dt = 1.0 / 64.0;
+        let delay = f64::from_bits(3.0f64.to_bits() - 3); // 191.99999999999997 steps
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 1, 0.5, 0.0, delay);
+        network.put_edge(1, 0, 0.5, 0.0, 3.0);
+        assert!(network.validate().is_ok());
+        let csr = CsrHistory::<mackey_glass::System, f64>::new(dt, &network, false);
+        assert_eq!(csr.delay_steps, vec![192, 192]);
+    }
+
+    #[test]
+    fn updated_edges_stay_modulated() {
+        let dt = 1.0 / 16.0;
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 1, 0.5, 0.0, 1.0);
+        network.put_edge(1, 0, 0.5, 0.0, 1.0);
+        let mut csr = CsrHistory::<mackey_glass::System, f64>::new(dt, &network, false);
+        let sinusoid = Modulation::Sinusoid {
+            amplitude: 0.5,
+            period: 4.0,
+            phase: 0.25,
+        };
+        let schedule =
+            CouplingSchedule::new(2).add(SelectGroup::AllGroups, EdgeParameter::Delay, sinusoid);
+        csr.set_schedule(schedule).unwrap();
+        assert_eq!(csr.delay_steps[csr.entry_of_edge[1]], 24);
+        assert_eq!(csr.depth, 24); // the longest delay of the schedule, 1.5
+
+        // the schedule stays on top of the new base edges
+        let mut edges = network.edges.clone();
+        edges[1].delay = 2.0;
+        edges[1].strength = 2.0;
+        csr.update_edges(&edges).unwrap();
+        assert_eq!(csr.delay_steps[csr.entry_of_edge[1]], 40);
+        assert_eq!(csr.depth, 40);
+        assert_eq!(
+            csr.update_edges(&edges[..1]),
+            Err(NetworkError::EdgeCountChanged {
+                edges: 1,
+                expected: 2
+            })
+        );
+        assert_eq!(csr.weight[csr.entry_of_edge[1]], 2.0);
+        assert_eq!(csr.edges()[1].delay, 2.0);
+
+        // 0.55 - 0.5 is less than two steps
+        edges[0].delay = 0.55;
+        assert!(matches!(
+            csr.update_edges(&edges),
+            Err(NetworkError::DelayTooShort { edge: 0, .. })
+        ));
+        assert_eq!(csr.edges()[0].delay, 1.0);
+        for step in 0..256 {
+            push_step(&mut csr, 2, step);
+            csr.advance_schedule();
+        }
+    }
+
+    // cargo test --release -- --ignored --nocapture benchmark_csr_history
+    #[test]
+    #[ignore]
+    fn benchmark_csr_history() {
+        fn run<H: DelayCoupling<mackey_glass::System, Value>>(network: &Network) -> f64 {
+            let dt = 1.0 / 16.0;
+            let mut history = H::new(dt, network, false);
+            let mut feedback = vec![[Value::default(); 2]; network.nodes];
+            let states = vec![Value::default(); network.nodes];
+            let steps = 500;
+            (0..3)
+                .map(|_| {
+                    let start = Instant::now();
+                    for _ in 0..steps {
+                        history.get_all_feedback_rk4_into(&mut feedback);
+                        history.push_node_states_from(black_box(&states));
+                    }
+                    black_box(&feedback);
+                    start.elapsed().as_secs_f64() / steps as f64 * 1e6
+                })
+                .fold(f64::INFINITY, f64::min)
+        }
+        for k in [4, 10] {
+            let network = sparse_network(10_000, k, 1.0 / 16.0);
+            println!(
+                "10000 nodes, {} edges: ring buffers {:.0} µs per step, csr {:.0} µs per step",
+                network.edges.len(),
+                run::<History<mackey_glass::System, Value>>(&network),
+                run::<CsrHistory<mackey_glass::System, Value>>(&network)
+            );
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use crate::integration_methods::RungeKuttaDelay;
+use num_complex::Complex;
+use ringbuffer::{AllocRingBuffer, RingBuffer};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::mem;
+
+// storage of one node's past values in `History`.
+// `get(-k)` is the k-th newest value (k = 1: the last pushed), `get(i)` with i >= 0 the i-th
+// oldest one, like `ringbuffer::RingBuffer::get`.
+pub trait DelayStorage<T>: Clone + Send + Sync {
+    // the smallest buffer of this kind that can read `delay_steps` into the past
+    fn for_delay_steps(delay_steps: usize) -> Self;
+    fn push(&mut self, value: T);
+    fn get(&self, index: isize) -> Option<T>;
+    fn capacity(&self) -> usize;
+    fn len(&self) -> usize;
+    fn fill(&mut self, value: T);
+    // oldest first
+    fn to_vec(&self) -> Vec<T>;
+    // memory of one stored value
+    fn bytes_per_value() -> usize {
+        mem::size_of::<T>()
+    }
+}
+
+// values that can be kept with less precision in the history, e.g. f64 as f32
+pub trait Compact: Copy {
+    type Stored: Copy + Default + Send + Sync + Serialize + DeserializeOwned;
+    fn compact(self) -> Self::Stored;
+    fn expand(stored: Self::Stored) -> Self;
+}
+
+impl Compact for f64 {
+    type Stored = f32;
+    fn compact(self) -> f32 {
+        self as f32
+    }
+    fn expand(stored: f32) -> f64 {
+        stored as f64
+    }
+}
+
+impl Compact for Complex<f64> {
+    type Stored = Complex<f32>;
+    fn compact(self) -> Complex<f32> {
+        Complex::new(self.re as f32, self.im as f32)
+    }
+    fn expand(stored: Complex<f32>) -> Complex<f64> {
+        Complex::new(stored.re as f64, stored.im as f64)
+    }
+}
+
+impl<T> Compact for RungeKuttaDelay<T>
+where
+    T: Compact
+        + Sized
+        + Clone
+        + Copy
+        + Default
+        + core::iter::Sum
+        + std::ops::Add<Output = T>
+        + std::ops::AddAssign,
+{
+    type Stored = (T::Stored, T::Stored);
+    fn compact(self) -> Self::Stored {
+        (self.state.compact(), self.slope.compact())
+    }
+    fn expand((state, slope): Self::Stored) -> Self {
+        RungeKuttaDelay {
+            state: T::expand(state),
+            slope: T::expand(slope),
+        }
+    }
+}
+
+// ring buffer of exactly the needed length with modulo indexing. `AllocRingBuffer` rounds
+// up to a power of two (cheap masking instead of modulo), which can almost double the memory
+// for long delays.
+#[allow(dead_code)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DelayBuffer<T> {
+    values: Vec<T>,
+    capacity: usize,
+    next: usize, // where the next value is written, the oldest value once the buffer is full
+}
+
+#[allow(dead_code)]
+impl<T: Clone> DelayBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "a delay buffer needs space for at least one value"
+        );
+        DelayBuffer {
+            values: Vec::with_capacity(capacity),
+            capacity,
+            next: 0,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let (newer, older) = self.values.split_at(self.oldest());
+        older.iter().chain(newer)
+    }
+
+    fn oldest(&self) -> usize {
+        if self.values.len() < self.capacity {
+            0
+        } else {
+            self.next
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync> DelayStorage<T> for DelayBuffer<T> {
+    fn for_delay_steps(delay_steps: usize) -> Self {
+        DelayBuffer::new(delay_steps.max(1))
+    }
+
+    #[inline]
+    fn push(&mut self, value: T) {
+        if self.values.len() < self.capacity {
+            self.values.push(value);
+        } else {
+            self.values[self.next] = value;
+        }
+        self.next += 1;
+        if self.next == self.capacity {
+            self.next = 0;
+        }
+    }
+
+    #[inline]
+    fn get(&self, index: isize) -> Option<T> {
+        let len = self.values.len();
+        let from_oldest = if index < 0 {
+            len.checked_sub(index.unsigned_abs())?
+        } else if (index as usize) < len {
+            index as usize
+        } else {
+            return None;
+        };
+        let i = self.oldest() + from_oldest;
+        // a comparison instead of `%`, both summands are below `capacity`
+        self.values
+            .get(if i >= self.capacity {
+                i - self.capacity
+            } else {
+                i
+            })
+            .cloned()
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn fill(&mut self, value: T) {
+        self.values.clear();
+        self.values.resize(self.capacity, value);
+        self.next = 0;
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: Clone + Send + Sync> DelayStorage<T> for AllocRingBuffer<T> {
+    fn for_delay_steps(delay_steps: usize) -> Self {
+        AllocRingBuffer::new(delay_steps.max(1).next_power_of_two())
+    }
+
+    #[inline]
+    fn push(&mut self, value: T) {
+        RingBuffer::push(self, value)
+    }
+
+    #[inline]
+    fn get(&self, index: isize) -> Option<T> {
+        RingBuffer::get(self, index).cloned()
+    }
+
+    fn capacity(&self) -> usize {
+        RingBuffer::capacity(self)
+    }
+
+    fn len(&self) -> usize {
+        RingBuffer::len(self)
+    }
+
+    fn fill(&mut self, value: T) {
+        RingBuffer::fill(self, value)
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        RingBuffer::to_vec(self)
+    }
+}
+
+// history in single precision: the values are rounded when they are pushed, integration
+// stays in double precision. halves the memory of `DelayBuffer<f64>`.
+#[allow(dead_code)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompactBuffer<T: Compact> {
+    buffer: DelayBuffer<T::Stored>,
+}
+
+impl<T: Compact + Send + Sync> DelayStorage<T> for CompactBuffer<T> {
+    fn for_delay_steps(delay_steps: usize) -> Self {
+        CompactBuffer {
+            buffer: DelayBuffer::for_delay_steps(delay_steps),
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, value: T) {
+        self.buffer.push(value.compact())
+    }
+
+    #[inline]
+    fn get(&self, index: isize) -> Option<T> {
+        self.buffer.get(index).map(T::expand)
+    }
+
+    fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn fill(&mut self, value: T) {
+        self.buffer.fill(value.compact())
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        self.buffer.iter().map(|&v| T::expand(v)).collect()
+    }
+
+    fn bytes_per_value() -> usize {
+        mem::size_of::<T::Stored>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hint::black_box;
+    use std::time::Instant;
+
+    #[test]
+    fn reads_like_alloc_ring_buffer() {
+        let mut exact = DelayBuffer::<usize>::for_delay_steps(5);
+        let mut power_of_two = AllocRingBuffer::<usize>::for_delay_steps(5);
+        assert_eq!(exact.capacity(), 5);
+        assert_eq!(DelayStorage::capacity(&power_of_two), 8);
+        DelayStorage::fill(&mut power_of_two, 0);
+        exact.fill(0);
+        for value in 1..=13 {
+            exact.push(value);
+            DelayStorage::push(&mut power_of_two, value);
+            for k in 1..=5 {
+                assert_eq!(
+                    exact.get(-k),
+                    DelayStorage::get(&power_of_two, -k),
+                    "value {}, delay {}",
+                    value,
+                    k
+                );
+            }
+        }
+        assert_eq!(exact.to_vec(), vec![9, 10, 11, 12, 13]);
+        assert_eq!(exact.get(0), Some(9));
+        assert_eq!(exact.get(-6), None);
+        assert_eq!(exact.get(5), None);
+    }
+
+    // cargo test --release -- --ignored --nocapture delay_buffer
+    #[test]
+    #[ignore]
+    fn benchmark_delay_buffers() {
+        fn run<B: DelayStorage<f64>>(delay_steps: usize) -> (f64, usize) {
+            let nodes = 100;
+            let mut buffers: Vec<B> = (0..nodes)
+                .map(|_| B::for_delay_steps(delay_steps))
+                .collect();
+            buffers.iter_mut().for_each(|b| b.fill(0.0));
+            let start = Instant::now();
+            let mut sum = 0.0;
+            for step in 0..20_000 {
+                for b in &mut buffers {
+                    sum += b.get(-(delay_steps as isize)).unwrap()
+                        + b.get(1 - delay_steps as isize).unwrap();
+                    b.push(step as f64);
+                }
+            }
+            black_box(sum);
+            let bytes = buffers.iter().map(|b| b.capacity()).sum::<usize>() * 8;
+            (start.elapsed().as_secs_f64(), bytes)
+        }
+        for delay_steps in [100, 1000, 5000, 8192] {
+            let (exact, exact_bytes) = run::<DelayBuffer<f64>>(delay_steps);
+            let (alloc, alloc_bytes) = run::<AllocRingBuffer<f64>>(delay_steps);
+            println!(
+                "delay {:5} steps: exact {:.3} s {:9} bytes, power of two {:.3} s {:9} bytes",
+                delay_steps, exact, exact_bytes, alloc, alloc_bytes
+            );
+        }
+    }
+}
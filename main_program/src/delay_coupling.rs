@@ -0,0 +1,122 @@
+use crate::coupling_schedule::CouplingSchedule;
+use crate::delay_buffer::DelayStorage;
+use crate::dynamical_system::Feedback;
+use crate::history::{History, NodeMemory};
+use crate::network::{Edge, Network};
+use crate::network_validation::NetworkError;
+use crate::plasticity::{Activity, PlasticInput, Plasticity};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt;
+
+// how the past values of one layer are stored and summed up into the delayed feedback.
+// `History` keeps a ring buffer per node and shares lookups of equal (node, delay) pairs,
+// `CsrHistory` keeps one array for all nodes and sparse rows of edges, for large networks.
+// the whole coupling is serialized into checkpoints.
+pub trait DelayCoupling<S, T>: Default + fmt::Display + Serialize + DeserializeOwned
+where
+    S: Feedback,
+{
+    fn new(dt: f64, network: &Network, equal_ringbuffers: bool) -> Self;
+    // one entry per node: the weighted sums at the start and the end of the step
+    fn get_all_feedback_rk4(&self) -> Vec<[T; 2]>;
+    fn get_all_feedback_rk4_into(&mut self, feedback: &mut [[T; 2]]);
+    fn push_node_states_from(&mut self, new_states: &[T]);
+    fn update_edges(&mut self, edges: &[Edge]) -> Result<(), NetworkError>;
+    fn set_schedule(&mut self, schedule: CouplingSchedule) -> Result<(), NetworkError>;
+    fn set_plasticity(&mut self, rules: Vec<Plasticity>) -> Result<(), NetworkError>;
+    // the checks of the three above without any change, e.g. to check all layers first
+    fn check_edges(&self, edges: &[Edge]) -> Result<(), NetworkError>;
+    fn check_schedule(&self, schedule: &CouplingSchedule) -> Result<(), NetworkError>;
+    fn check_plasticity(&self, rules: &[Plasticity]) -> Result<(), NetworkError>;
+    // the smooth learning rules are integrated by the nodes: `inputs[node]` gets the node's
+    // plastic edges before the step, their new strengths are taken over after it
+    fn plastic_inputs_into(&self, layer: usize, inputs: &mut [Vec<PlasticInput<T, S::WeightT>>]);
+    fn set_plastic_strengths(&mut self, layer: usize, inputs: &[Vec<PlasticInput<T, S::WeightT>>]);
+    // both called once per integration step, after the new states are pushed
+    fn advance_schedule(&mut self);
+    fn advance_plasticity(&mut self)
+    where
+        T: Activity;
+    fn edges(&self) -> &[Edge];
+    fn memory_per_node(&self) -> Vec<NodeMemory>;
+    // every stored value, oldest first. the past is part of the state of a delay system.
+    fn past(&self) -> Vec<T>;
+    // `past` as returned by `past` (or longer), returns how many values were taken
+    fn set_past(&mut self, past: &[T]) -> usize;
+}
+
+impl<S, T, B> DelayCoupling<S, T> for History<S, T, B>
+where
+    S: Feedback,
+    T: Sized
+        + Clone
+        + Copy
+        + Default
+        + core::iter::Sum
+        + std::ops::Add<Output = T>
+        + std::ops::AddAssign
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync
+        + Serialize
+        + DeserializeOwned,
+    B: DelayStorage<T> + Serialize + DeserializeOwned,
+{
+    fn new(dt: f64, network: &Network, equal_ringbuffers: bool) -> Self {
+        History::new(dt, network, equal_ringbuffers)
+    }
+    fn get_all_feedback_rk4(&self) -> Vec<[T; 2]> {
+        History::get_all_feedback_rk4(self)
+    }
+    fn get_all_feedback_rk4_into(&mut self, feedback: &mut [[T; 2]]) {
+        History::get_all_feedback_rk4_into(self, feedback)
+    }
+    fn push_node_states_from(&mut self, new_states: &[T]) {
+        History::push_node_states_from(self, new_states)
+    }
+    fn update_edges(&mut self, edges: &[Edge]) -> Result<(), NetworkError> {
+        History::update_edges(self, edges)
+    }
+    fn set_schedule(&mut self, schedule: CouplingSchedule) -> Result<(), NetworkError> {
+        History::set_schedule(self, schedule)
+    }
+    fn set_plasticity(&mut self, rules: Vec<Plasticity>) -> Result<(), NetworkError> {
+        History::set_plasticity(self, rules)
+    }
+    fn check_edges(&self, edges: &[Edge]) -> Result<(), NetworkError> {
+        History::check_edges(self, edges)
+    }
+    fn check_schedule(&self, schedule: &CouplingSchedule) -> Result<(), NetworkError> {
+        History::check_schedule(self, schedule)
+    }
+    fn check_plasticity(&self, rules: &[Plasticity]) -> Result<(), NetworkError> {
+        History::check_plasticity(self, rules)
+    }
+    fn plastic_inputs_into(&self, layer: usize, inputs: &mut [Vec<PlasticInput<T, S::WeightT>>]) {
+        History::plastic_inputs_into(self, layer, inputs)
+    }
+    fn set_plastic_strengths(&mut self, layer: usize, inputs: &[Vec<PlasticInput<T, S::WeightT>>]) {
+        History::set_plastic_strengths(self, layer, inputs)
+    }
+    fn advance_schedule(&mut self) {
+        History::advance_schedule(self)
+    }
+    fn advance_plasticity(&mut self)
+    where
+        T: Activity,
+    {
+        History::advance_plasticity(self)
+    }
+    fn edges(&self) -> &[Edge] {
+        History::edges(self)
+    }
+    fn memory_per_node(&self) -> Vec<NodeMemory> {
+        History::memory_per_node(self)
+    }
+    fn past(&self) -> Vec<T> {
+        History::past(self)
+    }
+    fn set_past(&mut self, past: &[T]) -> usize {
+        History::set_past(self, past)
+    }
+}
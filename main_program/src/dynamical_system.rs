@@ -1,4 +1,5 @@
 use crate::network::Edge;
+use serde::{de::DeserializeOwned, Serialize};
 
 use std::f64::consts::PI;
 
@@ -34,11 +35,20 @@ pub trait DynamicalSystem {
         + std::ops::Mul<f64, Output = Self::StateT>
         + std::ops::Add<Self::StateT, Output = Self::StateT>
         + std::ops::AddAssign
-        + std::ops::Div<f64, Output = Self::StateT>;
-    type ModelT: Clone + Copy + Default;
+        + std::ops::Div<f64, Output = Self::StateT>
+        + Send
+        + Sync
+        + Serialize
+        + DeserializeOwned
+        + Components;
+    type ModelT: Clone + Copy + Default + Send + Sync + Serialize + DeserializeOwned;
     // fn keep_state(state: &Self::StateT) -> Self::KeepT;
     // type KeepT: Clone + Copy + Default;
     fn keep_state(state: &Self::StateT) -> Vec<f64>;
+    // `keep_state` written into a row of the timeseries, without allocating
+    fn keep_state_into(state: &Self::StateT, values: &mut [f64]) {
+        values.copy_from_slice(&Self::keep_state(state))
+    }
     fn keep_state_names() -> &'static [&'static str];
 }
 
@@ -52,7 +62,13 @@ pub trait Feedback: DynamicalSystem {
         + std::ops::AddAssign
         + std::ops::Sub<Output = Self::FeedbackT>
         + std::ops::Mul<f64, Output = Self::FeedbackT>
-        + std::ops::Mul<Self::WeightT, Output = Self::FeedbackT>;
+        + std::ops::Mul<Self::WeightT, Output = Self::FeedbackT>
+        + crate::plasticity::Activity
+        + Send
+        + Sync
+        + Serialize
+        + DeserializeOwned
+        + Components;
     type WeightT: WeightFromEdge
         + Sized
         + Clone
@@ -61,7 +77,11 @@ pub trait Feedback: DynamicalSystem {
         + std::ops::Mul
         + std::ops::Mul<num_complex::Complex<f64>>
         + std::ops::Mul<f64>
-        + std::ops::Mul<Self::FeedbackT>;
+        + std::ops::Mul<Self::FeedbackT>
+        + Send
+        + Sync
+        + Serialize
+        + DeserializeOwned;
     fn f(
         state: &Self::StateT,
         model: &Self::ModelT,
@@ -71,6 +91,60 @@ pub trait Feedback: DynamicalSystem {
     fn get_feedback(state: &Self::StateT) -> Self::FeedbackT;
     fn keep_state_and_delay(state: &Self::StateT, feedback: &Self::FeedbackT) -> Vec<f64>;
     fn keep_state_and_delay_names() -> &'static [&'static str];
+
+    // multiplex networks: layer l of the network couples through `get_coupling(state, l)`.
+    // layer 0 has to be `get_feedback`, single layer systems keep the defaults.
+    fn coupling_names() -> &'static [&'static str] {
+        &["feedback"]
+    }
+    fn get_coupling(state: &Self::StateT, _layer: usize) -> Self::FeedbackT {
+        Self::get_feedback(state)
+    }
+    // time derivative of the coupling variable, `slope` is the derivative of `state`
+    fn get_coupling_slope(
+        _state: &Self::StateT,
+        slope: &Self::StateT,
+        layer: usize,
+    ) -> Self::FeedbackT {
+        Self::get_coupling(slope, layer)
+    }
+    // `feedback[l]` is the weighted sum arriving through layer l
+    fn f_layers(
+        state: &Self::StateT,
+        model: &Self::ModelT,
+        feedback: &[Self::FeedbackT],
+    ) -> Self::StateT {
+        Self::f(state, model, &feedback.iter().copied().sum())
+    }
+}
+
+// the plain numbers a state or feedback value consists of, in a fixed order. a perturbation of
+// the whole state of a delay system is a vector of them (`IntegrationMethods::phase_space`).
+pub trait Components: Copy {
+    const LEN: usize;
+    // `into` has `LEN` numbers
+    fn write_components(&self, into: &mut [f64]);
+    fn from_components(components: &[f64]) -> Self;
+}
+
+impl Components for f64 {
+    const LEN: usize = 1;
+    fn write_components(&self, into: &mut [f64]) {
+        into[0] = *self;
+    }
+    fn from_components(components: &[f64]) -> Self {
+        components[0]
+    }
+}
+
+impl Components for num_complex::Complex<f64> {
+    const LEN: usize = 2;
+    fn write_components(&self, into: &mut [f64]) {
+        into.copy_from_slice(&[self.re, self.im]);
+    }
+    fn from_components(components: &[f64]) -> Self {
+        num_complex::Complex::new(components[0], components[1])
+    }
 }
 
 pub type WeightReal = f64;
@@ -92,6 +166,39 @@ impl WeightFromEdge for WeightComplex {
     }
 }
 
+// the weight of several parallel edges is their sum, `write_to_edge` turns it back into the
+// strength (and turn) of the one edge that replaces them
+pub trait CombinedWeight: WeightFromEdge + Copy + std::iter::Sum {
+    fn magnitude(&self) -> f64;
+    fn write_to_edge(self, edge: &mut Edge);
+}
+
+// the turn is ignored by real weights, it is left as it is
+impl CombinedWeight for WeightReal {
+    fn magnitude(&self) -> f64 {
+        self.abs()
+    }
+    fn write_to_edge(self, edge: &mut Edge) {
+        edge.strength = self;
+    }
+}
+
+impl CombinedWeight for WeightComplex {
+    fn magnitude(&self) -> f64 {
+        self.norm()
+    }
+    // a real sum (all turns 0) keeps its sign instead of turning by half a period
+    fn write_to_edge(self, edge: &mut Edge) {
+        if self.im == 0.0 {
+            edge.strength = self.re;
+            edge.turn = 0.0;
+        } else {
+            edge.strength = self.norm();
+            edge.turn = (self.arg() / (2.0 * PI)).rem_euclid(1.0);
+        }
+    }
+}
+
 // experimental traits
 // not yet used
 pub trait SystemInitialization {} // todo!()
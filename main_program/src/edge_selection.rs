@@ -0,0 +1,276 @@
+use crate::coupling_schedule::{self, EdgeParameter};
+use crate::network::{Edge, Network, SelectGroup};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::rc::Rc;
+
+// queries for the edges a network operation acts on. `SelectGroup` converts into it, so every
+// operation taking `impl Into<EdgeSelection>` still accepts the old group selections.
+// a `Predicate` cannot be written to a checkpoint, saving one returns an error.
+#[allow(dead_code)]
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub enum EdgeSelection {
+    #[default]
+    All,
+    Group(usize),
+    GroupNamed(String), // every group with this name in `Network::edge_groups`
+    Layer(usize),
+    Into(Vec<usize>),    // edges into one of the nodes
+    From(Vec<usize>),    // edges from one of the nodes
+    Between(Vec<usize>), // both ends in the node set
+    Delay {
+        min: f64,
+        max: f64,
+    }, // min <= delay < max
+    #[serde(skip)] // closures cannot be read from configuration files or saved
+    Predicate(Rc<dyn Fn(&Edge) -> bool>),
+    Not(Box<EdgeSelection>),
+    And(Vec<EdgeSelection>),
+    Or(Vec<EdgeSelection>),
+}
+
+#[allow(dead_code)]
+impl EdgeSelection {
+    pub fn predicate(predicate: impl Fn(&Edge) -> bool + 'static) -> Self {
+        EdgeSelection::Predicate(Rc::new(predicate))
+    }
+
+    pub fn and(self, other: EdgeSelection) -> Self {
+        match self {
+            EdgeSelection::And(mut all) => {
+                all.push(other);
+                EdgeSelection::And(all)
+            }
+            first => EdgeSelection::And(vec![first, other]),
+        }
+    }
+
+    pub fn or(self, other: EdgeSelection) -> Self {
+        match self {
+            EdgeSelection::Or(mut any) => {
+                any.push(other);
+                EdgeSelection::Or(any)
+            }
+            first => EdgeSelection::Or(vec![first, other]),
+        }
+    }
+
+    // `edge_groups` are the names of the network's groups, needed for `GroupNamed`
+    pub fn matches(&self, edge: &Edge, edge_groups: &[String]) -> bool {
+        match self {
+            EdgeSelection::All => true,
+            EdgeSelection::Group(group) => edge.group == *group,
+            EdgeSelection::GroupNamed(name) => edge_groups
+                .get(edge.group)
+                .is_some_and(|group_name| group_name == name),
+            EdgeSelection::Layer(layer) => edge.layer == *layer,
+            EdgeSelection::Into(nodes) => nodes.contains(&edge.into),
+            EdgeSelection::From(nodes) => nodes.contains(&edge.from),
+            EdgeSelection::Between(nodes) => {
+                nodes.contains(&edge.into) && nodes.contains(&edge.from)
+            }
+            EdgeSelection::Delay { min, max } => *min <= edge.delay && edge.delay < *max,
+            EdgeSelection::Predicate(predicate) => predicate(edge),
+            EdgeSelection::Not(selection) => !selection.matches(edge, edge_groups),
+            EdgeSelection::And(all) => all.iter().all(|s| s.matches(edge, edge_groups)),
+            EdgeSelection::Or(any) => any.iter().any(|s| s.matches(edge, edge_groups)),
+        }
+    }
+
+    // replaces `GroupNamed` by the indices of the groups with that name. coupling schedules and
+    // plasticity rules only see the edges, their selections are resolved when they are set
+    pub fn resolve_group_names(&self, edge_groups: &[String]) -> EdgeSelection {
+        match self {
+            EdgeSelection::GroupNamed(name) => EdgeSelection::Or(
+                (0..edge_groups.len())
+                    .filter(|&g| edge_groups[g] == *name)
+                    .map(EdgeSelection::Group)
+                    .collect(),
+            ),
+            EdgeSelection::Not(selection) => !selection.resolve_group_names(edge_groups),
+            EdgeSelection::And(all) => EdgeSelection::And(
+                all.iter()
+                    .map(|s| s.resolve_group_names(edge_groups))
+                    .collect(),
+            ),
+            EdgeSelection::Or(any) => EdgeSelection::Or(
+                any.iter()
+                    .map(|s| s.resolve_group_names(edge_groups))
+                    .collect(),
+            ),
+            selection => selection.clone(),
+        }
+    }
+}
+
+impl std::ops::Not for EdgeSelection {
+    type Output = EdgeSelection;
+    fn not(self) -> Self::Output {
+        EdgeSelection::Not(Box::new(self))
+    }
+}
+
+impl From<SelectGroup> for EdgeSelection {
+    fn from(selection: SelectGroup) -> Self {
+        match selection {
+            SelectGroup::AllGroups => EdgeSelection::All,
+            SelectGroup::SingleGroup(group) => EdgeSelection::Group(group),
+            SelectGroup::NotGroup(group) => !EdgeSelection::Group(group),
+        }
+    }
+}
+
+impl fmt::Debug for EdgeSelection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EdgeSelection::All => write!(f, "All"),
+            EdgeSelection::Group(group) => write!(f, "Group({})", group),
+            EdgeSelection::GroupNamed(name) => write!(f, "GroupNamed({:?})", name),
+            EdgeSelection::Layer(layer) => write!(f, "Layer({})", layer),
+            EdgeSelection::Into(nodes) => write!(f, "Into({:?})", nodes),
+            EdgeSelection::From(nodes) => write!(f, "From({:?})", nodes),
+            EdgeSelection::Between(nodes) => write!(f, "Between({:?})", nodes),
+            EdgeSelection::Delay { min, max } => write!(f, "Delay({}..{})", min, max),
+            EdgeSelection::Predicate(_) => write!(f, "Predicate(..)"),
+            EdgeSelection::Not(selection) => write!(f, "Not({:?})", selection),
+            EdgeSelection::And(all) => write!(f, "And({:?})", all),
+            EdgeSelection::Or(any) => write!(f, "Or({:?})", any),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Network {
+    // indices of the selected edges
+    pub fn select_edges(&self, selection: impl Into<EdgeSelection>) -> Vec<usize> {
+        let selection = selection.into();
+        (0..self.edges.len())
+            .filter(|&i| selection.matches(&self.edges[i], &self.edge_groups))
+            .collect()
+    }
+
+    // returns the number of changed edges
+    pub fn set_parameter(
+        &mut self,
+        parameter: EdgeParameter,
+        value: f64,
+        selection: impl Into<EdgeSelection>,
+    ) -> usize {
+        self.modify_edges(selection, |e| {
+            *coupling_schedule::parameter_of(e, parameter) = value
+        })
+    }
+
+    pub fn scale_parameter(
+        &mut self,
+        parameter: EdgeParameter,
+        factor: f64,
+        selection: impl Into<EdgeSelection>,
+    ) -> usize {
+        self.modify_edges(selection, |e| {
+            *coupling_schedule::parameter_of(e, parameter) *= factor
+        })
+    }
+
+    pub fn scale_strength(&mut self, factor: f64, selection: impl Into<EdgeSelection>) -> usize {
+        self.scale_parameter(EdgeParameter::Strength, factor, selection)
+    }
+
+    pub fn modify_edges(
+        &mut self,
+        selection: impl Into<EdgeSelection>,
+        mut modify: impl FnMut(&mut Edge),
+    ) -> usize {
+        let selection = selection.into();
+        let mut changed = 0;
+        for e in self
+            .edges
+            .iter_mut()
+            .filter(|e| selection.matches(e, &self.edge_groups))
+        {
+            modify(e);
+            changed += 1;
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coupling_schedule::{CouplingSchedule, Modulation};
+
+    fn example_network() -> Network {
+        let mut network = Network::new(4, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_ring(0.1, 0.0, 1.0); // group 0, edges 0..4
+        network.put_ring_reverse(0.2, 0.0, 2.0); // group 1, edges 4..8
+        network.put_edge(2, 0, 0.3, 0.0, 3.0); // group 2, edge 8
+        network
+    }
+
+    #[test]
+    fn queries_select_edges() {
+        let network = example_network();
+        assert_eq!(network.select_edges(SelectGroup::AllGroups).len(), 9);
+        assert_eq!(network.select_edges(SelectGroup::NotGroup(1)).len(), 5);
+        assert_eq!(
+            network.select_edges(EdgeSelection::GroupNamed("single edge".to_string())),
+            vec![8]
+        );
+        assert_eq!(
+            network.select_edges(EdgeSelection::Into(vec![2])),
+            vec![1, 6, 8]
+        );
+        assert_eq!(
+            network.select_edges(EdgeSelection::Between(vec![0, 1])),
+            vec![0, 4]
+        );
+        assert_eq!(
+            network.select_edges(EdgeSelection::Delay { min: 1.5, max: 3.0 }),
+            vec![4, 5, 6, 7]
+        );
+        let strong_into_2 =
+            EdgeSelection::predicate(|e| e.strength > 0.15).and(EdgeSelection::Into(vec![2]));
+        assert_eq!(network.select_edges(strong_into_2), vec![6, 8]);
+    }
+
+    #[test]
+    fn not_group_only_changes_other_groups() {
+        let mut network = example_network();
+        network.randomize_strength(0.05, SelectGroup::NotGroup(0));
+        assert!(network.edges[..4].iter().all(|e| e.strength == 0.1));
+        assert!(network.edges[4..].iter().any(|e| e.strength != 0.2));
+
+        let changed = network.scale_strength(2.0, EdgeSelection::Group(0));
+        assert_eq!(changed, 4);
+        assert!(network.edges[..4].iter().all(|e| e.strength == 0.2));
+        // turns wrap around into [0, 1)
+        network.turn_angles(1.25, SelectGroup::SingleGroup(2));
+        assert_eq!(network.edges[8].turn, 0.25);
+    }
+
+    #[test]
+    fn group_names_are_resolved() {
+        let network = example_network();
+        let named = !EdgeSelection::GroupNamed("single edge".to_string());
+        let resolved = named.resolve_group_names(&network.edge_groups);
+        for e in &network.edges {
+            assert_eq!(
+                resolved.matches(e, &[]),
+                named.matches(e, &network.edge_groups)
+            );
+        }
+        let schedule = CouplingSchedule::new(1)
+            .add(
+                named,
+                EdgeParameter::Strength,
+                Modulation::Step {
+                    time: 0.0,
+                    value: 1.0,
+                },
+            )
+            .resolve_group_names(&network.edge_groups);
+        let modulated = network.edges.iter().filter(|e| schedule.modulates(e));
+        assert_eq!(modulated.count(), 8);
+    }
+}
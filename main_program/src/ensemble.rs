@@ -0,0 +1,244 @@
+use std::fmt;
+use std::fs;
+use std::thread;
+
+use timeseries::SaveItems;
+
+use crate::calculation::{Calculation, CouplingLayout, NodeSetup, SystemType, Tasks};
+use crate::network::Network;
+use crate::timer::Timer;
+
+// the configuration of one ensemble member. it is built inside the member's thread, tasks can
+// hold edge selections that cannot be sent between threads.
+pub struct EnsembleMember {
+    pub network: Network,
+    pub tasks: Vec<Tasks>,
+}
+
+// runs many independent copies of a `Calculation`, one thread per member.
+// member i saves its curves in `{directory}/member_{i:03}`.
+#[allow(dead_code)]
+pub struct Ensemble {
+    pub directory: String,
+    pub dt: f64,
+    pub segment_length: usize,
+    pub node_setup: NodeSetup,
+    pub system_type: SystemType,
+    pub coupling_layout: CouplingLayout,
+    pub save_items: SaveItems,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct MemberResult {
+    pub steps: u64,
+    pub milliseconds: f64,
+    pub variables: &'static [&'static str],
+    // every variable's value at the end, averaged over the nodes
+    pub final_means: Vec<f64>,
+}
+
+#[allow(dead_code)]
+pub struct EnsembleSummary {
+    pub directory: String,
+    pub members: Vec<Result<MemberResult, String>>,
+}
+
+#[allow(dead_code)]
+impl Ensemble {
+    pub fn member_directory(&self, member: usize) -> String {
+        format!("{}/member_{:03}", self.directory, member)
+    }
+
+    // `member(i)` sets up member i, e.g. with its own seed, initial conditions or parameters.
+    // a failing member is reported in the summary, the others keep running.
+    pub fn run(
+        &self,
+        members: usize,
+        member: impl Fn(usize) -> EnsembleMember + Sync,
+    ) -> EnsembleSummary {
+        let member = &member;
+        let results = thread::scope(|scope| {
+            let handles: Vec<_> = (0..members)
+                .map(|i| scope.spawn(move || self.run_member(i, member(i))))
+                .collect();
+            handles
+                .into_iter()
+                .enumerate()
+                .map(|(i, handle)| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err(format!("member {} panicked", i)))
+                })
+                .collect()
+        });
+        EnsembleSummary {
+            directory: self.directory.clone(),
+            members: results,
+        }
+    }
+
+    fn run_member(&self, i: usize, member: EnsembleMember) -> Result<MemberResult, String> {
+        let mut calculation = Calculation::examples_in_directory(
+            &self.member_directory(i),
+            self.dt,
+            &member.network,
+            self.segment_length,
+            self.node_setup,
+            self.system_type,
+            self.coupling_layout,
+            &member.tasks,
+            self.save_items.clone(),
+        )
+        .map_err(|errors| {
+            errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>()
+                .join("; ")
+        })?;
+        let timer = Timer::new();
+        calculation.perform_tasks()?;
+        let milliseconds = timer.get_nanoseconds() as f64 / 1e6;
+
+        let variables = calculation.system.timeseries_curve_names();
+        let state = calculation.system.keep_state();
+        let nodes = state.len() / variables.len();
+        let final_means = (0..variables.len())
+            .map(|v| state.iter().skip(v).step_by(variables.len()).sum::<f64>() / nodes as f64)
+            .collect();
+        Ok(MemberResult {
+            steps: calculation.total_steps,
+            milliseconds,
+            variables,
+            final_means,
+        })
+    }
+}
+
+#[allow(dead_code)]
+impl EnsembleSummary {
+    pub fn finished(&self) -> usize {
+        self.members.iter().filter(|m| m.is_ok()).count()
+    }
+
+    // (variable, mean, standard deviation) over the finished members of the final node averages
+    pub fn statistics(&self) -> Vec<(&'static str, f64, f64)> {
+        let finished: Vec<&MemberResult> = self.members.iter().flatten().collect();
+        let Some(first) = finished.first() else {
+            return Vec::new();
+        };
+        let n = finished.len() as f64;
+        first
+            .variables
+            .iter()
+            .enumerate()
+            .map(|(v, &name)| {
+                let mean = finished.iter().map(|m| m.final_means[v]).sum::<f64>() / n;
+                let variance = finished
+                    .iter()
+                    .map(|m| (m.final_means[v] - mean).powi(2))
+                    .sum::<f64>()
+                    / n;
+                (name, mean, variance.sqrt())
+            })
+            .collect()
+    }
+
+    // writes this summary to `{directory}/summary.txt`
+    pub fn save(&self) -> std::io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        fs::write(format!("{}/summary.txt", self.directory), self.to_string())
+    }
+}
+
+impl fmt::Display for EnsembleSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "ensemble {}: {} of {} members finished",
+            self.directory,
+            self.finished(),
+            self.members.len()
+        )?;
+        for (i, member) in self.members.iter().enumerate() {
+            match member {
+                Ok(m) => {
+                    write!(
+                        f,
+                        "member {:03}: {} steps in {:.1} ms, final",
+                        i, m.steps, m.milliseconds
+                    )?;
+                    for (name, value) in m.variables.iter().zip(&m.final_means) {
+                        write!(f, " {} = {:.6}", name, value)?;
+                    }
+                    writeln!(f)?;
+                }
+                Err(error) => writeln!(f, "member {:03}: failed: {}", i, error)?,
+            }
+        }
+        for (name, mean, std) in self.statistics() {
+            writeln!(
+                f,
+                "final {}: mean {:.6}, standard deviation {:.6}",
+                name, mean, std
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn members_run_independently() {
+        let dt = 1.0 / 64.0;
+        let directory = std::env::temp_dir().join("ensemble_members_run_independently");
+        let ensemble = Ensemble {
+            directory: directory.to_string_lossy().to_string(),
+            dt,
+            segment_length: 64,
+            node_setup: NodeSetup::Identical,
+            system_type: SystemType::MackeyGlass,
+            coupling_layout: CouplingLayout::RingBuffers,
+            save_items: SaveItems::Timeseries,
+        };
+        let summary = ensemble.run(4, |i| {
+            let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, dt);
+            network.put_bi_ring(0.1, 0.0, 2.0);
+            if i == 3 {
+                network.put_edge(0, 1, f64::NAN, 0.0, 2.0);
+            }
+            EnsembleMember {
+                network,
+                tasks: vec![
+                    // members 0 and 2 start from the same initial conditions
+                    Tasks::PerturbStates {
+                        amount: 0.2,
+                        seed: i as u64 % 2,
+                    },
+                    Tasks::IntegrateSegmentsAndSave {
+                        segments: 4,
+                        epsilon: 0.01,
+                    },
+                ],
+            }
+        });
+        summary.save().unwrap();
+
+        assert_eq!(summary.finished(), 3);
+        assert!(summary.members[3].is_err());
+        let finals: Vec<Vec<f64>> = summary.members[..3]
+            .iter()
+            .map(|m| m.as_ref().unwrap().final_means.clone())
+            .collect();
+        assert_eq!(finals[0], finals[2]);
+        assert_ne!(finals[0], finals[1]);
+        assert_eq!(summary.members[0].as_ref().unwrap().steps, 4 * 64);
+        assert!(directory.join("member_001").is_dir());
+        assert!(directory.join("summary.txt").is_file());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,6 +1,7 @@
-use crate::dynamical_system::{DynamicalSystem, Feedback, WeightReal};
+use crate::dynamical_system::{Components, DynamicalSystem, Feedback, WeightReal};
 
 use derive_more::{Add, AddAssign, Div, Mul, MulAssign};
+use serde::{Deserialize, Serialize};
 
 pub struct System {}
 impl DynamicalSystem for System {
@@ -9,6 +10,9 @@ impl DynamicalSystem for System {
     fn keep_state(state: &Self::StateT) -> Vec<f64> {
         vec![state.v, state.w]
     }
+    fn keep_state_into(state: &Self::StateT, values: &mut [f64]) {
+        values.copy_from_slice(&[state.v, state.w])
+    }
     fn keep_state_names() -> &'static [&'static str] {
         &["v", "w"]
     }
@@ -38,19 +42,33 @@ impl Feedback for System {
 type FeedbackState = f64;
 type Weight = WeightReal;
 
-#[derive(Copy, Clone, Add, AddAssign, Mul, MulAssign, Div, Debug)]
+#[derive(Copy, Clone, Add, AddAssign, Mul, MulAssign, Div, Debug, Serialize, Deserialize)]
 pub struct State {
     v: f64,
     w: f64,
 }
 
+impl Components for State {
+    const LEN: usize = 2;
+    fn write_components(&self, into: &mut [f64]) {
+        into.copy_from_slice(&[self.v, self.w]);
+    }
+    fn from_components(components: &[f64]) -> Self {
+        State {
+            v: components[0],
+            w: components[1],
+        }
+    }
+}
+
 impl Default for State {
     fn default() -> Self {
         State { v: 1.0, w: 1.0 }
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Model {
     tau: f64,
     a: f64,
@@ -3,8 +3,14 @@ use itertools::Itertools;
 use std::collections::HashMap;
 use std::fmt;
 
-// use crate::{parameter::Parameter, var::Var};
+use crate::{
+    parameter::{ArgumentError, Parameter},
+    var::{Var, VarTypes},
+};
 
+// command line options, sorted into domains. an option is written as `-name values...`, a
+// domain word in front of options (e.g. `network -edge 0 1 ...`) selects the domain that is
+// searched first, it is only needed for names that exist in several domains.
 #[allow(dead_code)]
 pub struct GlobalParameterMap<'a> {
     map_map: HashMap<&'static str, HashMap<&'static str, Parameter<'a>>>,
@@ -19,12 +25,10 @@ impl<'a> GlobalParameterMap<'a> {
     }
 
     pub fn create_domain(&mut self, domain: &'static str) {
-        if !self.map_map.contains_key(domain) {
-            self.map_map
-                .insert(domain, HashMap::<&'static str, Parameter>::new());
-        }
+        self.map_map.entry(domain).or_default();
     }
 
+    // like `insert`, but only into an existing domain
     pub fn insert_into_domain(
         &mut self,
         domain: &'static str,
@@ -35,30 +39,18 @@ impl<'a> GlobalParameterMap<'a> {
         is_unique: bool,
     ) {
         if self.map_map.contains_key(domain) {
-            self.map_map.get_mut(domain).unwrap().insert(
-                &variable_name,
-                Parameter {
-                    parameter: vec![default_parameter],
-                    set_by,
-                    explanation,
-                    was_set: false,
-                    is_unique,
-                },
+            self.insert(
+                domain,
+                variable_name,
+                default_parameter,
+                set_by,
+                explanation,
+                is_unique,
             );
         }
-        // if let Some(sub_map) = &mut self.map_map.get(domain) {
-        // }
-        //  else {
-        //     &mut self
-        //         .map_map
-        //         .insert(&domain, HashMap::<&'static str, Parameter>::new());
-
-        // if let Some(sub_map) = &mut self.map_map.get(domain) {
-        //     sub_map
-        // }
-        // }
     }
 
+    // `is_unique` = false: the option can be repeated, every occurrence adds a set of values
     pub fn insert(
         &mut self,
         domain: &'static str,
@@ -68,111 +60,203 @@ impl<'a> GlobalParameterMap<'a> {
         explanation: &'static str,
         is_unique: bool,
     ) {
-        if self.map_map.contains_key(&domain) {
-            // insert into sub_map
-            // if is_unique {}
-            self.map_map.get_mut(&domain).unwrap().insert(
-                &variable_name,
-                Parameter {
-                    parameter: vec![default_parameter],
-                    set_by,
-                    explanation,
-                    was_set: false,
-                    is_unique,
-                },
-            );
-        } else {
-            // create sub_map
-            self.map_map
-                .insert(&domain, HashMap::<&'static str, Parameter>::new());
-            self.map_map.get_mut(&domain).unwrap().insert(
-                &variable_name,
-                Parameter {
-                    parameter: vec![default_parameter],
-                    set_by,
-                    explanation,
-                    was_set: false,
-                    is_unique,
-                },
-            );
-        }
-    }
-
-    pub fn set_parameter(parameter: &Parameter) {
-        print!("explanation: ");
-        println!("{}", parameter.explanation);
-        // if let set_strings = &parameter.set_by {
-        //     print!("{} possible set strings: [", set_strings.len());
-        //     for set_by in set_strings {
-        //         print!("-{} ", &set_by);
-        //     }
-        //     println!("]")
-        // }
+        self.map_map.entry(domain).or_default().insert(
+            variable_name,
+            Parameter {
+                parameter: vec![default_parameter],
+                set_by,
+                explanation,
+                was_set: false,
+                is_unique,
+            },
+        );
     }
 
-    pub fn get<'b, 'c>(&'b self, domain_name: &'c str, parameter_name: &'c str) -> &'c Parameter
-    where
-        'b: 'c,
-    {
-        if let Some(domain_map) = self.map_map.get(&domain_name) {
-            if let Some(parameter) = domain_map.get(&parameter_name) {
+    // panics for names that were never inserted, that is a mistake in the program
+    pub fn get(&self, domain_name: &str, parameter_name: &str) -> &Parameter<'a> {
+        if let Some(domain_map) = self.map_map.get(domain_name) {
+            if let Some(parameter) = domain_map.get(parameter_name) {
                 parameter
             } else {
                 panic!("parameter `{}` could not be found in global_parameter_map. Did you forget to put it there, or misspell it?", &parameter_name);
-                // None
             }
         } else {
             panic!("domain `{}` could not be found in global_parameter_map. Did you forget to put it there, or misspell it?", &domain_name);
-            // None
         }
     }
 
-    pub fn set_from_args(&mut self, args: &'a Vec<String>) {
-        for arg_index in 0..args.len() {
-            let domain_string = format!("{}", &args[arg_index].as_str());
-            if let Some(sub_map) = self.map_map.get_mut(domain_string.as_str()) {
-                println!("setting up domain `{}`:", &domain_string);
-                for (_, parameter) in sub_map.iter_mut() {
-                    parameter.parse_parameter(args, arg_index + 1);
+    // the first value of a unique parameter
+    pub fn value<T>(&self, domain_name: &str, parameter_name: &str) -> T
+    where
+        T: VarTypes + Default,
+    {
+        self.get(domain_name, parameter_name).parameter[0][0]
+            .get::<T>()
+            .unwrap_or_else(|| {
+                panic!(
+                    "parameter `{}` is read with the wrong type",
+                    &parameter_name
+                )
+            })
+    }
+
+    pub fn word(&self, domain_name: &str, parameter_name: &str) -> &'a str {
+        self.get(domain_name, parameter_name).parameter[0][0]
+            .word()
+            .unwrap_or_else(|| panic!("parameter `{}` is not a word", &parameter_name))
+    }
+
+    // a UInt parameter that must not be smaller than `min`
+    pub fn uint_at_least(
+        &self,
+        domain_name: &str,
+        parameter_name: &str,
+        min: u32,
+    ) -> Result<u32, ArgumentError> {
+        let value = self.value::<u32>(domain_name, parameter_name);
+        if value < min {
+            return Err(ArgumentError::OutOfRange {
+                option: self.option_name(domain_name, parameter_name),
+                value: value.to_string(),
+                expected: format!("at least {}", min),
+            });
+        }
+        Ok(value)
+    }
+
+    // the first name the parameter can be set by, with the dash
+    pub fn option_name(&self, domain_name: &str, parameter_name: &str) -> String {
+        format!("-{}", self.get(domain_name, parameter_name).set_by[0])
+    }
+
+    pub fn was_set(&self, domain_name: &str, parameter_name: &str) -> bool {
+        self.get(domain_name, parameter_name).was_set
+    }
+
+    // every set of values of a repeatable parameter
+    pub fn sets(&self, domain_name: &str, parameter_name: &str) -> &[Vec<Var<'a>>] {
+        self.get(domain_name, parameter_name).sets()
+    }
+
+    // a word parameter that selects one of `choices`
+    pub fn choice<T: Clone>(
+        &self,
+        domain_name: &str,
+        parameter_name: &str,
+        choices: &[(&'static str, T)],
+    ) -> Result<T, ArgumentError> {
+        let word = self.word(domain_name, parameter_name);
+        choices
+            .iter()
+            .find(|(name, _)| *name == word)
+            .map(|(_, choice)| choice.clone())
+            .ok_or_else(|| ArgumentError::InvalidChoice {
+                option: self.option_name(domain_name, parameter_name),
+                value: word.to_string(),
+                choices: choices.iter().map(|(name, _)| *name).collect(),
+            })
+    }
+
+    // `args` without the program name
+    pub fn set_from_args(&mut self, args: &'a [String]) -> Result<(), ArgumentError> {
+        let mut domain = None;
+        let mut i = 0;
+        while i < args.len() {
+            let argument = args[i].as_str();
+            if let Some(name) = argument.strip_prefix('-') {
+                let (domain_key, key) = self.find(domain, name, argument)?;
+                let parameter = self
+                    .map_map
+                    .get_mut(domain_key)
+                    .and_then(|sub_map| sub_map.get_mut(key))
+                    .expect("found above");
+                i += 1 + parameter.parse_parameter(argument, &args[i + 1..])?;
+            } else if let Some(&domain_key) = self.map_map.keys().find(|d| **d == argument) {
+                domain = Some(domain_key);
+                i += 1;
+            } else {
+                return Err(ArgumentError::UnexpectedArgument(argument.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    // (domain, parameter) set by `-name`: from `domain` if it has it, otherwise from the only
+    // domain that has it
+    fn find(
+        &self,
+        domain: Option<&'static str>,
+        name: &str,
+        option: &str,
+    ) -> Result<(&'static str, &'static str), ArgumentError> {
+        let in_domain = |domain_key: &'static str| {
+            self.map_map[domain_key]
+                .iter()
+                .find(|(_, parameter)| parameter.set_by.contains(&name))
+                .map(|(&key, _)| (domain_key, key))
+        };
+        if let Some(found) = domain.and_then(in_domain) {
+            return Ok(found);
+        }
+        let found: Vec<(&'static str, &'static str)> = self
+            .map_map
+            .keys()
+            .sorted()
+            .filter_map(|&domain_key| in_domain(domain_key))
+            .collect();
+        match found[..] {
+            [] => Err(ArgumentError::UnknownOption(option.to_string())),
+            [found] => Ok(found),
+            _ => Err(ArgumentError::AmbiguousOption {
+                option: option.to_string(),
+                domains: found.iter().map(|&(domain_key, _)| domain_key).collect(),
+            }),
+        }
+    }
+
+    // generated from the inserted parameters, with their defaults
+    pub fn help(&self, program: &str) -> String {
+        let mut help = format!(
+            "usage: {} [domain] -option values... [domain] -option values...\n\
+             a domain word is only needed in front of options that exist in several domains.\n\
+             -help shows this text.\n",
+            program
+        );
+        for domain_key in self.map_map.keys().sorted() {
+            help += &format!("\n{}\n", format!(" {} ", domain_key).bold().reversed());
+            for value_key in self.map_map[domain_key].keys().sorted() {
+                let parameter = &self.map_map[domain_key][value_key];
+                help += &format!("  {}\n", parameter.usage().bold());
+                help += &format!("        {}", parameter.explanation);
+                let default = parameter.parameter[0].iter().join(" ");
+                if !parameter.is_switch() && parameter.is_unique && !default.is_empty() {
+                    help += &format!(" (default: {})", default);
                 }
+                help += "\n";
             }
         }
-        // println!();
+        help
     }
 }
 
-// argh...
-// die key=("domain", "parameter_name")-lösung ist doch nicht so toll glaube ich.
-// meine print-methode (folgnd) kann ich nicht so einfach schreiben, dass sie die keys nach domain sortiert
-
 #[allow(dead_code)]
 impl<'a> fmt::Display for GlobalParameterMap<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "+++ commandline_options +++")?;
         for domain_key in self.map_map.keys().sorted() {
-            // for (domain_key, sub_map) in &self.map_map {
             let domain_string = format!(" {} ", domain_key).bold().reversed().dimmed();
             write!(f, "\n{}", domain_string)?;
             for value_key in self.map_map[domain_key].keys().sorted() {
                 let sub_value = &self.map_map[domain_key][value_key];
                 write!(f, "\n  - {} = ", value_key)?;
 
-                // if sub_value.parameter.len() > 1 {
-                //     let br_open = "[".dimmed();
-                //     write!(f, "{}", br_open)?;
-                // }
-
                 let value = if sub_value.was_set {
                     format!("{}", sub_value).bold().green()
                 } else {
                     format!("{}", sub_value).normal()
                 };
-                write!(f, "{}", value).unwrap();
+                write!(f, "{}", value)?;
 
-                // if sub_value.parameter.len() > 1 {
-                //     let br_close = "]".dimmed();
-                //     write!(f, "{}", br_close)?;
-                // }
                 write!(f, ", can be set via [ ")?;
 
                 for i in &sub_value.set_by {
@@ -180,10 +264,127 @@ impl<'a> fmt::Display for GlobalParameterMap<'a> {
                     write!(f, "\'{}\' ", word)?;
                 }
                 write!(f, "] ")?;
-                let explanation = format!("{}", sub_value.explanation).dimmed();
+                let explanation = sub_value.explanation.dimmed();
                 write!(f, "\n        {}", explanation)?;
             }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameter_map<'a>() -> GlobalParameterMap<'a> {
+        let mut map = GlobalParameterMap::new();
+        map.insert(
+            "integration",
+            "inverse_dt",
+            vec![Var::UFloat(512.0)],
+            vec!["idt"],
+            "",
+            true,
+        );
+        map.insert(
+            "integration",
+            "csr",
+            vec![Var::Switch(false)],
+            vec!["csr"],
+            "",
+            true,
+        );
+        map.insert(
+            "network",
+            "seed",
+            vec![Var::UInt(0)],
+            vec!["seed"],
+            "",
+            true,
+        );
+        map.insert("tasks", "seed", vec![Var::UInt(0)], vec!["seed"], "", true);
+        map.insert(
+            "network",
+            "edge",
+            vec![Var::Int(0), Var::Int(0), Var::Float(0.1), Var::Turn(0.0)],
+            vec!["edge"],
+            "",
+            false,
+        );
+        map
+    }
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn options_are_parsed_into_their_domains() {
+        let arguments = args("-idt 256 -csr network -seed 3 -edge 0 1 0.5 1.25 -edge -1 2 -0.5 0");
+        let mut map = parameter_map();
+        map.set_from_args(&arguments).unwrap();
+        assert_eq!(map.value::<f64>("integration", "inverse_dt"), 256.0);
+        assert!(map.value::<bool>("integration", "csr"));
+        assert_eq!(map.value::<u32>("network", "seed"), 3);
+        assert_eq!(map.value::<u32>("tasks", "seed"), 0);
+        assert_eq!(
+            map.sets("network", "edge"),
+            [
+                vec![Var::Int(0), Var::Int(1), Var::Float(0.5), Var::Turn(0.25)],
+                vec![Var::Int(-1), Var::Int(2), Var::Float(-0.5), Var::Turn(0.0)],
+            ]
+        );
+        assert!(parameter_map().sets("network", "edge").is_empty());
+    }
+
+    #[test]
+    fn mistakes_are_reported() {
+        let error = |line: &str| {
+            let arguments = args(line);
+            parameter_map().set_from_args(&arguments).unwrap_err()
+        };
+        assert_eq!(
+            error("-idt fast"),
+            ArgumentError::InvalidValue {
+                option: "-idt".to_string(),
+                value: "fast".to_string(),
+                expected: "float >= 0",
+            }
+        );
+        assert_eq!(
+            error("-idt -4"),
+            ArgumentError::InvalidValue {
+                option: "-idt".to_string(),
+                value: "-4".to_string(),
+                expected: "float >= 0",
+            }
+        );
+        assert_eq!(
+            error("-edge 0 1"),
+            ArgumentError::MissingValues {
+                option: "-edge".to_string(),
+                expected: 4,
+                found: 2,
+            }
+        );
+        assert_eq!(
+            error("-seed 3"),
+            ArgumentError::AmbiguousOption {
+                option: "-seed".to_string(),
+                domains: vec!["network", "tasks"],
+            }
+        );
+        assert_eq!(
+            error("-idt 1 -idt 2"),
+            ArgumentError::RepeatedOption("-idt".to_string())
+        );
+        assert_eq!(
+            error("-tua 2"),
+            ArgumentError::UnknownOption("-tua".to_string())
+        );
+        assert_eq!(
+            error("-idt 1 2"),
+            ArgumentError::UnexpectedArgument("2".to_string())
+        );
+    }
+}
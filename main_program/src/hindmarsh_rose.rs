@@ -1,5 +1,6 @@
-use crate::dynamical_system::{DynamicalSystem, Feedback, WeightReal};
+use crate::dynamical_system::{Components, DynamicalSystem, Feedback, WeightReal};
 use derive_more::{Add, AddAssign, Div, Mul, MulAssign};
+use serde::{Deserialize, Serialize};
 
 pub struct System {}
 impl DynamicalSystem for System {
@@ -8,6 +9,9 @@ impl DynamicalSystem for System {
     fn keep_state(state: &Self::StateT) -> Vec<f64> {
         vec![state.x, state.y, state.z]
     }
+    fn keep_state_into(state: &Self::StateT, values: &mut [f64]) {
+        values.copy_from_slice(&[state.x, state.y, state.z])
+    }
     fn keep_state_names() -> &'static [&'static str] {
         &["x", "y", "z"]
     }
@@ -32,6 +36,47 @@ impl Feedback for System {
     fn keep_state_and_delay_names() -> &'static [&'static str] {
         &["x", "y", "z", "x_delay", "y_delay", "z_delay"]
     }
+    // layer 0: electrical (diffusive) coupling via x,
+    // layer 1: chemical synapses, the presynaptic activity Γ(x) gates the current (e_syn - x)
+    fn coupling_names() -> &'static [&'static str] {
+        &["x", "synapse"]
+    }
+    fn get_coupling(state: &Self::StateT, layer: usize) -> Self::FeedbackT {
+        match layer {
+            0 => state.x,
+            _ => synaptic_activation(state.x),
+        }
+    }
+    fn get_coupling_slope(
+        state: &Self::StateT,
+        slope: &Self::StateT,
+        layer: usize,
+    ) -> Self::FeedbackT {
+        match layer {
+            0 => slope.x,
+            _ => {
+                let gamma = synaptic_activation(state.x);
+                LAMBDA_SYN * gamma * (1.0 - gamma) * slope.x
+            }
+        }
+    }
+    fn f_layers(
+        state: &Self::StateT,
+        model: &Self::ModelT,
+        feedback: &[Self::FeedbackT],
+    ) -> Self::StateT {
+        let chemical = feedback.get(1).map_or(0.0, |s| (model.e_syn - state.x) * s);
+        Self::f(state, model, &(feedback[0] + chemical))
+    }
+}
+
+// sigmoidal presynaptic activation, the network's layer 1 sums it. the history stores it
+// without the model, so threshold and steepness are the same for all nodes.
+const THETA_SYN: f64 = -0.25;
+const LAMBDA_SYN: f64 = 10.0;
+
+fn synaptic_activation(x: f64) -> f64 {
+    1.0 / (1.0 + (-LAMBDA_SYN * (x - THETA_SYN)).exp())
 }
 
 fn phi(x: &f64, model: &Model) -> f64 {
@@ -44,13 +89,27 @@ fn psi(x: &f64, model: &Model) -> f64 {
 
 pub type FeedbackState = f64;
 
-#[derive(Copy, Clone, Add, AddAssign, Mul, MulAssign, Div, Debug)]
+#[derive(Copy, Clone, Add, AddAssign, Mul, MulAssign, Div, Debug, Serialize, Deserialize)]
 pub struct State {
     x: f64,
     y: f64,
     z: f64,
 }
 
+impl Components for State {
+    const LEN: usize = 3;
+    fn write_components(&self, into: &mut [f64]) {
+        into.copy_from_slice(&[self.x, self.y, self.z]);
+    }
+    fn from_components(components: &[f64]) -> Self {
+        State {
+            x: components[0],
+            y: components[1],
+            z: components[2],
+        }
+    }
+}
+
 impl Default for State {
     fn default() -> Self {
         State {
@@ -61,7 +120,8 @@ impl Default for State {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Model {
     a: f64,
     b: f64,
@@ -71,6 +131,7 @@ pub struct Model {
     s: f64,
     x_r: f64,
     i: f64,
+    e_syn: f64, // reversal potential of the chemical synapses, > all x: excitatory
 }
 
 impl Default for Model {
@@ -84,6 +145,7 @@ impl Default for Model {
             s: 4.0,
             x_r: -2.0,
             i: 4.0,
+            e_syn: 2.0,
         }
     }
 }
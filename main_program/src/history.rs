@@ -1,11 +1,21 @@
+use crate::coupling_schedule::CouplingSchedule;
+use crate::delay_buffer::{CompactBuffer, DelayBuffer, DelayStorage};
 use crate::dynamical_system::{Feedback, WeightFromEdge};
-use crate::network::{Edge, Network};
-use ringbuffer::{AllocRingBuffer, RingBuffer};
+#[cfg(feature = "parallel")]
+use crate::integration_methods::PARALLEL_MIN_LEN;
+use crate::network::{delay_steps, Edge, Network};
+use crate::network_validation::{self, NetworkError};
+use crate::plasticity::{Activity, PlasticEdges, PlasticInput, Plasticity};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
+use std::marker::PhantomData;
 use std::mem;
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct ReadAtMultiply<WeightT>
 where
     WeightT: Sized + Clone,
@@ -13,6 +23,7 @@ where
     pub at_node: usize,
     pub at_delay: usize,
     pub weight: WeightT,
+    pub lookup: usize, // index into `History::lookups`, readers of the same (node, delay) share it
 }
 
 #[allow(dead_code)]
@@ -20,18 +31,77 @@ impl<WeightT> ReadAtMultiply<WeightT>
 where
     WeightT: Sized + Clone,
 {
-    pub fn new(at_node: usize, at_delay: usize, weight: WeightT) -> Self {
+    pub fn new(at_node: usize, at_delay: usize, weight: WeightT, lookup: usize) -> Self {
         ReadAtMultiply {
             at_node,
             at_delay,
             weight,
+            lookup,
         }
     }
 }
 
 #[allow(dead_code)]
-#[derive(Clone)]
-pub struct History<S, T>
+#[derive(Clone, Copy, Debug)]
+pub struct NodeMemory {
+    pub node: usize,
+    pub delay_steps: usize, // longest delay read from the node's buffer
+    pub capacity: usize,
+    pub bytes: usize,
+}
+
+// buffer sizes of all layers of a history, `layers[l][node]`
+#[allow(dead_code)]
+#[derive(Clone, Debug, Default)]
+pub struct MemoryReport {
+    pub layers: Vec<Vec<NodeMemory>>,
+}
+
+#[allow(dead_code)]
+impl MemoryReport {
+    pub fn total_bytes(&self) -> usize {
+        self.layers.iter().flatten().map(|n| n.bytes).sum()
+    }
+
+    // memory of buffer entries that are never read
+    pub fn unused_bytes(&self) -> usize {
+        self.layers
+            .iter()
+            .flatten()
+            .map(|n| n.bytes / n.capacity * (n.capacity - n.delay_steps.min(n.capacity)))
+            .sum()
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (l, nodes) in self.layers.iter().enumerate() {
+            if self.layers.len() > 1 {
+                writeln!(f, "layer {}:", l)?;
+            }
+            for n in nodes {
+                writeln!(
+                    f,
+                    "node {:4}: longest delay {:8} steps, buffer {:8} entries = {:10} bytes",
+                    n.node, n.delay_steps, n.capacity, n.bytes
+                )?;
+            }
+        }
+        writeln!(
+            f,
+            "history: {} bytes, {} of them never read",
+            self.total_bytes(),
+            self.unused_bytes()
+        )
+    }
+}
+
+// the past is stored in f32 (`CompactBuffer`), feedback is still summed up in f64
+pub type SinglePrecisionHistory<S, T> = History<S, T, CompactBuffer<T>>;
+
+#[allow(dead_code)]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct History<S, T, B = DelayBuffer<T>>
 // FeedbackT - delay type,
 // WeightT float type for multiplication of delay in weighted sum.
 where
@@ -43,15 +113,28 @@ where
         + core::iter::Sum
         + std::ops::Add<Output = T>
         + std::ops::AddAssign
-        + std::ops::Mul<S::WeightT, Output = T>,
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync,
+    B: DelayStorage<T>,
 {
-    history: Vec<AllocRingBuffer<T>>,
+    history: Vec<B>, // one buffer per node, `B::capacity` >= the longest delay read from it
+    lookups: Vec<(usize, usize)>, // distinct (node, delay) pairs that are read each step
+    looked_up: Vec<[T; 2]>, // scratch for the values of `lookups`, reused every step
     readers: Vec<Vec<ReadAtMultiply<S::WeightT>>>,
+    reader_of_edge: Vec<(usize, usize)>, // network's edge i is read by `readers[into][k]`
     dt: f64,
+    steps: u64,
+    edges: Vec<Edge>, // current edges without the schedule's modulation
+    schedule: CouplingSchedule,
+    #[serde(skip)]
+    modulated: Vec<Edge>, // scratch for the edges of the schedule, reused every update
+    plasticity: PlasticEdges,
+    values: PhantomData<T>, // the buffers `B` store values of type `T`
 }
 
 #[allow(dead_code)]
-impl<S, T> History<S, T>
+impl<S, T, B> History<S, T, B>
 where
     S: Feedback,
     T: Sized
@@ -61,59 +144,243 @@ where
         + core::iter::Sum
         + std::ops::Add<Output = T>
         + std::ops::AddAssign
-        + std::ops::Mul<S::WeightT, Output = T>,
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync,
+    B: DelayStorage<T>,
 {
     pub fn new(dt: f64, network: &Network, equal_ringbuffers: bool) -> Self {
         let mut history = History {
             history: Vec::new(),
+            lookups: Vec::new(),
+            looked_up: Vec::new(),
             readers: Vec::new(),
+            reader_of_edge: Vec::new(),
             dt,
+            steps: 0,
+            edges: Vec::new(),
+            schedule: CouplingSchedule::default(),
+            modulated: Vec::new(),
+            plasticity: PlasticEdges::default(),
+            values: PhantomData,
         };
-        history.setup_connections(&network, equal_ringbuffers);
+        history.setup_connections(network, equal_ringbuffers);
         history
     }
 
     pub fn setup_connections(&mut self, network: &Network, equal_ringbuffers: bool) {
-        // ++++
-        // Self::edges_valid(network.nodes, &network.get_edges_into_nodes());
-
-        // ++++
+        // the network is expected to be validated (`Network::validate_for_dt`) beforehand
         let delay_steps_needed = Self::max_delay_steps_needed(
             network.nodes,
             &network.get_edges_into_nodes(),
             equal_ringbuffers,
         );
-        let ringbuffers: Vec<AllocRingBuffer<T>> = delay_steps_needed
+        self.history = delay_steps_needed
             .iter()
-            .map(|delay| {
-                let delay_steps = ((delay / self.dt) as usize).next_power_of_two();
-                AllocRingBuffer::<T>::new(delay_steps)
-            })
+            .map(|&delay| B::for_delay_steps(delay_steps(delay, self.dt)))
             .collect();
 
-        self.history = ringbuffers;
-
         Self::initialize_history(None, &mut self.history);
 
-        self.readers = network
-            .get_edges_into_nodes()
+        self.readers = vec![Vec::new(); network.nodes];
+        self.reader_of_edge = network
+            .edges
             .iter()
-            .map(|es| {
-                es.iter()
-                    .map(|e| ReadAtMultiply::<S::WeightT> {
-                        at_node: e.from,
-                        at_delay: (e.delay / self.dt) as usize,
-                        weight: S::WeightT::from_edge(e),
-                    })
-                    .collect()
+            .map(|e| {
+                self.readers[e.into].push(ReadAtMultiply::<S::WeightT> {
+                    at_node: e.from,
+                    at_delay: delay_steps(e.delay, self.dt),
+                    weight: S::WeightT::from_edge(e),
+                    lookup: 0,
+                });
+                (e.into, self.readers[e.into].len() - 1)
             })
             .collect();
+        self.edges = network.edges.clone();
+        self.share_lookups();
+    }
+
+    fn share_lookups(&mut self) {
+        let mut lookup_of = HashMap::<(usize, usize), usize>::new();
+        self.lookups.clear();
+        for r in self.readers.iter_mut().flatten() {
+            r.lookup = *lookup_of.entry((r.at_node, r.at_delay)).or_insert_with(|| {
+                self.lookups.push((r.at_node, r.at_delay));
+                self.lookups.len() - 1
+            });
+        }
+        self.looked_up
+            .resize(self.lookups.len(), [T::default(), T::default()]);
+    }
+
+    // new values for the network's edges (same order, same nodes as at construction).
+    // weights and delays are changed in place, ring buffers that are too short for a longer
+    // delay grow. the part of the past that was never stored is filled with the oldest value.
+    // an active schedule modulates the new edges right away, nothing changes on an error.
+    pub fn update_edges(&mut self, edges: &[Edge]) -> Result<(), NetworkError> {
+        self.check_edges(edges)?;
+        Self::reserve_for_schedule(&self.schedule, edges, &mut self.history, self.dt);
+        self.apply_edges(&self.schedule.edges_at(edges, self.time()))?;
+        self.edges = edges.to_vec();
+        Ok(())
+    }
+
+    // the schedule modulates the current edges from now on, an empty schedule ends it
+    pub fn set_schedule(&mut self, schedule: CouplingSchedule) -> Result<(), NetworkError> {
+        self.check_schedule(&schedule)?;
+        self.schedule = schedule;
+        Self::reserve_for_schedule(&self.schedule, &self.edges, &mut self.history, self.dt);
+        self.apply_schedule()
+    }
+
+    // buffers grow once to the longest delay of the schedule, so they store the past that a
+    // delay jump reads later, and a modulated delay does not reallocate
+    fn reserve_for_schedule(
+        schedule: &CouplingSchedule,
+        edges: &[Edge],
+        history: &mut [B],
+        dt: f64,
+    ) {
+        for e in edges {
+            let steps = delay_steps(schedule.longest_delay(std::slice::from_ref(e)), dt);
+            if steps > history[e.from].capacity() {
+                Self::grow(&mut history[e.from], steps);
+            }
+        }
+    }
+
+    // the checks of `update_edges`, `set_schedule` and `set_plasticity`, without changes
+    pub fn check_edges(&self, edges: &[Edge]) -> Result<(), NetworkError> {
+        network_validation::check_same_edges(&self.edges, edges)?;
+        self.schedule.check_delays(edges, self.dt)
+    }
+
+    pub fn check_schedule(&self, schedule: &CouplingSchedule) -> Result<(), NetworkError> {
+        schedule.check_delays(&self.edges, self.dt)?;
+        self.plasticity.check_schedule(schedule, &self.edges)
+    }
+
+    pub fn check_plasticity(&self, rules: &[Plasticity]) -> Result<(), NetworkError> {
+        PlasticEdges::new(rules.to_vec(), &self.edges).check_schedule(&self.schedule, &self.edges)
+    }
+
+    // call once per integration step, after the new states are pushed
+    pub fn advance_schedule(&mut self) {
+        self.steps += 1;
+        if !self.schedule.is_empty()
+            && self
+                .steps
+                .is_multiple_of(self.schedule.update_every.max(1) as u64)
+        {
+            self.apply_schedule()
+                .expect("delays are checked in `set_schedule` and `update_edges`");
+        }
+    }
+
+    // current edges, including the strengths changed by plasticity
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    // plastic edges cannot be modulated by a schedule at the same time
+    pub fn set_plasticity(&mut self, rules: Vec<Plasticity>) -> Result<(), NetworkError> {
+        let plasticity = PlasticEdges::new(rules, &self.edges);
+        plasticity.check_schedule(&self.schedule, &self.edges)?;
+        self.plasticity = plasticity;
+        Ok(())
+    }
+
+    pub fn time(&self) -> f64 {
+        self.steps as f64 * self.dt
+    }
+
+    pub fn memory_per_node(&self) -> Vec<NodeMemory> {
+        let mut longest = vec![0; self.history.len()];
+        for r in self.readers.iter().flatten() {
+            longest[r.at_node] = longest[r.at_node].max(r.at_delay);
+        }
+        self.history
+            .iter()
+            .zip(longest)
+            .enumerate()
+            .map(|(node, (buffer, delay_steps))| NodeMemory {
+                node,
+                delay_steps,
+                capacity: buffer.capacity(),
+                bytes: buffer.capacity() * B::bytes_per_value(),
+            })
+            .collect()
+    }
+
+    fn apply_schedule(&mut self) -> Result<(), NetworkError> {
+        let mut modulated = mem::take(&mut self.modulated);
+        self.schedule
+            .write_edges_at(&self.edges, self.time(), &mut modulated);
+        let result = self.apply_edges(&modulated);
+        self.modulated = modulated;
+        result
+    }
+
+    fn apply_edges(&mut self, edges: &[Edge]) -> Result<(), NetworkError> {
+        for (i, e) in edges.iter().enumerate() {
+            let steps = delay_steps(e.delay, self.dt);
+            if steps < 2 {
+                return Err(NetworkError::DelayTooShort {
+                    edge: i,
+                    delay: e.delay,
+                    steps,
+                });
+            }
+        }
+        let mut delays_changed = false;
+        for (e, &(into, k)) in edges.iter().zip(&self.reader_of_edge) {
+            let at_delay = delay_steps(e.delay, self.dt);
+            let reader = &mut self.readers[into][k];
+            reader.weight = S::WeightT::from_edge(e);
+            if reader.at_delay != at_delay {
+                reader.at_delay = at_delay;
+                delays_changed = true;
+                if at_delay > self.history[e.from].capacity() {
+                    Self::grow(&mut self.history[e.from], at_delay);
+                }
+            }
+        }
+        if delays_changed {
+            self.share_lookups();
+        }
+        Ok(())
+    }
+
+    fn grow(buffer: &mut B, delay_steps: usize) {
+        let stored = buffer.to_vec();
+        let mut grown = B::for_delay_steps(delay_steps);
+        let oldest = stored.first().copied().unwrap_or_default();
+        for _ in stored.len()..grown.capacity() {
+            grown.push(oldest);
+        }
+        for value in stored {
+            grown.push(value);
+        }
+        *buffer = grown;
+    }
+
+    // every distinct (node, delay) is read once, all readers then share the values
+    fn read_lookups_rk4(&self) -> Vec<[T; 2]> {
+        self.lookups
+            .iter()
+            .map(|&(at_node, at_delay)| {
+                [
+                    self.history[at_node].get(-(at_delay as isize)).unwrap(),
+                    self.history[at_node].get(1 - (at_delay as isize)).unwrap(),
+                ]
+            })
+            .collect()
     }
 
     pub fn get_node_feedback(&mut self, into: usize) -> T {
         self.readers[into]
             .iter()
-            .map(|r| *self.history[r.at_node].get(-(r.at_delay as isize)).unwrap() * r.weight)
+            .map(|r| self.history[r.at_node].get(-(r.at_delay as isize)).unwrap() * r.weight)
             .sum()
     }
 
@@ -121,12 +388,12 @@ where
         [
             self.readers[0]
                 .iter()
-                .map(|r| *self.history[r.at_node].get(-(r.at_delay as isize)).unwrap() * r.weight)
+                .map(|r| self.history[r.at_node].get(-(r.at_delay as isize)).unwrap() * r.weight)
                 .sum(),
             self.readers[0]
                 .iter()
                 .map(|r| {
-                    *self.history[r.at_node]
+                    self.history[r.at_node]
                         .get(1 - (r.at_delay as isize))
                         .unwrap()
                         * r.weight
@@ -139,12 +406,12 @@ where
         [
             self.readers[into]
                 .iter()
-                .map(|r| *self.history[r.at_node].get(-(r.at_delay as isize)).unwrap() * r.weight)
+                .map(|r| self.history[r.at_node].get(-(r.at_delay as isize)).unwrap() * r.weight)
                 .sum(),
             self.readers[into]
                 .iter()
                 .map(|r| {
-                    *self.history[r.at_node]
+                    self.history[r.at_node]
                         .get(1 - (r.at_delay as isize))
                         .unwrap()
                         * r.weight
@@ -159,7 +426,7 @@ where
             .map(|rs| {
                 rs.iter()
                     .map(|r| {
-                        *self.history[r.at_node].get(-(r.at_delay as isize)).unwrap() * r.weight
+                        self.history[r.at_node].get(-(r.at_delay as isize)).unwrap() * r.weight
                     })
                     .sum()
             })
@@ -170,12 +437,12 @@ where
         [
             self.readers[into_node]
                 .iter()
-                .map(|r| *self.history[r.at_node].get(-(r.at_delay as isize)).unwrap() * r.weight)
+                .map(|r| self.history[r.at_node].get(-(r.at_delay as isize)).unwrap() * r.weight)
                 .sum(),
             self.readers[into_node]
                 .iter()
                 .map(|r| {
-                    *self.history[r.at_node]
+                    self.history[r.at_node]
                         .get(1 - (r.at_delay as isize))
                         .unwrap()
                         * r.weight
@@ -186,31 +453,62 @@ where
     }
 
     pub fn get_all_feedback_rk4(&self) -> Vec<[T; 2]> {
+        let looked_up = self.read_lookups_rk4();
         self.readers
             .iter()
             .map(|rs| {
                 [
-                    rs.iter()
-                        .map(|r| {
-                            *self.history[r.at_node].get(-(r.at_delay as isize)).unwrap() * r.weight
-                        })
-                        .sum(),
-                    rs.iter()
-                        .map(|r| {
-                            *self.history[r.at_node]
-                                .get(1 - (r.at_delay as isize))
-                                .unwrap()
-                                * r.weight
-                        })
-                        .sum(),
+                    rs.iter().map(|r| looked_up[r.lookup][0] * r.weight).sum(),
+                    rs.iter().map(|r| looked_up[r.lookup][1] * r.weight).sum(),
                 ]
             })
             .collect()
     }
 
+    // like `get_all_feedback_rk4`, written into `feedback` (one entry per node) without
+    // allocating. with the `parallel` feature lookups and nodes are split across threads, each
+    // node's sum is still added up in the same order.
+    pub fn get_all_feedback_rk4_into(&mut self, feedback: &mut [[T; 2]]) {
+        let history = &self.history;
+        let read = |(value, &(at_node, at_delay)): (&mut [T; 2], &(usize, usize))| {
+            *value = [
+                history[at_node].get(-(at_delay as isize)).unwrap(),
+                history[at_node].get(1 - (at_delay as isize)).unwrap(),
+            ];
+        };
+        #[cfg(not(feature = "parallel"))]
+        self.looked_up.iter_mut().zip(&self.lookups).for_each(read);
+        #[cfg(feature = "parallel")]
+        self.looked_up
+            .par_iter_mut()
+            .zip(self.lookups.par_iter())
+            .with_min_len(PARALLEL_MIN_LEN)
+            .for_each(read);
+
+        let looked_up = &self.looked_up;
+        let sum = |(sums, rs): (&mut [T; 2], &Vec<ReadAtMultiply<S::WeightT>>)| {
+            *sums = [
+                rs.iter().map(|r| looked_up[r.lookup][0] * r.weight).sum(),
+                rs.iter().map(|r| looked_up[r.lookup][1] * r.weight).sum(),
+            ];
+        };
+        #[cfg(not(feature = "parallel"))]
+        feedback.iter_mut().zip(&self.readers).for_each(sum);
+        #[cfg(feature = "parallel")]
+        feedback
+            .par_iter_mut()
+            .zip(self.readers.par_iter())
+            .with_min_len(PARALLEL_MIN_LEN)
+            .for_each(sum);
+    }
+
     pub fn push_node_states(&mut self, new_states: Vec<T>) {
+        self.push_node_states_from(&new_states)
+    }
+
+    pub fn push_node_states_from(&mut self, new_states: &[T]) {
         for (h, s) in self.history.iter_mut().zip(new_states) {
-            h.push(s)
+            h.push(*s)
         }
     }
 
@@ -218,35 +516,29 @@ where
         self.history[node].push(new_state)
     }
 
-    fn edges_valid(nodes: usize, edges: &Vec<Vec<Edge>>) -> Result<(), String> {
-        for es in edges {
-            for e in es {
-                if e.from >= nodes {
-                    return Err("edge from nonexistent node".to_string());
-                } else if e.delay == 0.0 {
-                    return Err("delay too small - cannot use with runge kutta 4".to_string());
-                }
+    // the buffers of all nodes one after the other, each oldest first
+    pub fn past(&self) -> Vec<T> {
+        self.history.iter().flat_map(|h| h.to_vec()).collect()
+    }
+
+    // the buffers are always full, pushing as many values as they hold replaces all of them
+    pub fn set_past(&mut self, past: &[T]) -> usize {
+        let mut taken = 0;
+        for h in &mut self.history {
+            let len = h.len();
+            for value in &past[taken..taken + len] {
+                h.push(*value);
             }
+            taken += len;
         }
-        Ok(())
+        taken
     }
 
     fn max_delay_steps_needed(
         nodes: usize,
-        edges: &Vec<Vec<Edge>>,
+        edges: &[Vec<Edge>],
         use_equal_ringbuffers: bool, // test
     ) -> Vec<f64> {
-        // check if edges are well formed, might be improved.
-        for es in edges {
-            for e in es {
-                if e.from >= nodes {
-                    panic!("edge from nonexistent node");
-                } else if e.delay == 0.0 {
-                    panic!("delay too small - cannot use with runge kutta 4");
-                }
-            }
-        }
-
         let longest_needed_delays: Vec<f64> = (0..nodes)
             .map(|n| {
                 edges
@@ -257,7 +549,7 @@ where
             })
             .collect();
 
-        if !use_equal_ringbuffers {
+        if use_equal_ringbuffers {
             let longest_global_delay = longest_needed_delays
                 .iter()
                 .fold(0.0f64, |max_delay, &delay| max_delay.max(delay));
@@ -267,23 +559,97 @@ where
         }
     }
 
-    fn initialize_history(fill_value: Option<T>, history: &mut Vec<AllocRingBuffer<T>>) {
-        match fill_value {
-            Some(value) => {
-                for h in history {
-                    h.fill(value)
-                }
+    // appends the edges with smooth learning rules to the inputs of their target node, with
+    // the values they deliver at the start and the end of the coming step
+    pub fn plastic_inputs_into(
+        &self,
+        layer: usize,
+        inputs: &mut [Vec<PlasticInput<T, S::WeightT>>],
+    ) {
+        if self.plasticity.is_empty() {
+            return;
+        }
+        for (i, e) in self.edges.iter().enumerate() {
+            if !self.plasticity.is_smooth(i) {
+                continue;
             }
-            None => {
-                for h in history {
-                    h.fill_default()
-                }
+            let (into, k) = self.reader_of_edge[i];
+            let r = &self.readers[into][k];
+            let arriving = [
+                self.history[r.at_node].get(-(r.at_delay as isize)).unwrap(),
+                self.history[r.at_node]
+                    .get(1 - (r.at_delay as isize))
+                    .unwrap(),
+            ];
+            let unit_weight = S::WeightT::from_edge(&Edge {
+                strength: 1.0,
+                ..*e
+            });
+            inputs[into].extend(self.plasticity.input(i, e, layer, arriving, unit_weight));
+        }
+    }
+
+    // takes over the strengths the nodes integrated for the inputs of this layer
+    pub fn set_plastic_strengths(
+        &mut self,
+        layer: usize,
+        inputs: &[Vec<PlasticInput<T, S::WeightT>>],
+    ) {
+        for p in inputs.iter().flatten().filter(|p| p.layer == layer) {
+            self.edges[p.edge].strength = p.strength;
+            let (into, k) = self.reader_of_edge[p.edge];
+            self.readers[into][k].weight = S::WeightT::from_edge(&self.edges[p.edge]);
+        }
+    }
+
+    fn initialize_history(fill_value: Option<T>, history: &mut [B]) {
+        for h in history {
+            h.fill(fill_value.unwrap_or_default())
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl<S, T, B> History<S, T, B>
+where
+    S: Feedback,
+    T: Sized
+        + Clone
+        + Copy
+        + Default
+        + Activity
+        + core::iter::Sum
+        + std::ops::Add<Output = T>
+        + std::ops::AddAssign
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync,
+    B: DelayStorage<T>,
+{
+    // call once per integration step, after the new states are pushed
+    // the stdp changes, after the new states are pushed
+    pub fn advance_plasticity(&mut self) {
+        if self.plasticity.is_empty() {
+            return;
+        }
+        let (history, readers, reader_of_edge) =
+            (&self.history, &self.readers, &self.reader_of_edge);
+        self.plasticity.step(&mut self.edges, self.dt, |i, _| {
+            let (into, k) = reader_of_edge[i];
+            let r = &readers[into][k];
+            let arriving = history[r.at_node].get(-(r.at_delay as isize)).unwrap();
+            let current = history[into].get(-1).unwrap();
+            (arriving.activity(), current.activity())
+        });
+        for (i, &(into, k)) in self.reader_of_edge.iter().enumerate() {
+            if self.plasticity.is_plastic(i) {
+                self.readers[into][k].weight = S::WeightT::from_edge(&self.edges[i]);
             }
         }
     }
 }
 
-impl<S, T> Default for History<S, T>
+impl<S, T, B> Default for History<S, T, B>
 where
     S: Feedback,
     T: Sized
@@ -293,19 +659,31 @@ where
         + core::iter::Sum
         + std::ops::Add<Output = T>
         + std::ops::AddAssign
-        + std::ops::Mul<S::WeightT, Output = T>,
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync,
+    B: DelayStorage<T>,
 {
     fn default() -> Self {
         History {
             history: Vec::new(),
+            lookups: Vec::new(),
+            looked_up: Vec::new(),
             readers: Vec::new(),
+            reader_of_edge: Vec::new(),
             dt: 1.0 / 64.0,
+            steps: 0,
+            edges: Vec::new(),
+            schedule: CouplingSchedule::default(),
+            modulated: Vec::new(),
+            plasticity: PlasticEdges::default(),
+            values: PhantomData,
         }
     }
 }
 
 #[allow(dead_code)]
-impl<S, T> fmt::Display for History<S, T>
+impl<S, T, B> fmt::Display for History<S, T, B>
 where
     S: Feedback,
     T: Sized
@@ -315,7 +693,10 @@ where
         + core::iter::Sum
         + std::ops::Add<Output = T>
         + std::ops::AddAssign
-        + std::ops::Mul<S::WeightT, Output = T>,
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync,
+    B: DelayStorage<T>,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(
@@ -330,10 +711,17 @@ where
                 "node {}: {:+e} entries = {} bytes",
                 i,
                 buffer.len(),
-                buffer.len() * mem::size_of::<T>()
+                buffer.len() * B::bytes_per_value()
             )
             .unwrap();
         }
+        writeln!(
+            f,
+            "{} readers share {} distinct lookups",
+            self.readers.iter().map(|rs| rs.len()).sum::<usize>(),
+            self.lookups.len()
+        )
+        .unwrap();
         for (i, rs) in self.readers.iter().enumerate() {
             writeln!(f, "node {} reads data from buffers: ", i).unwrap();
             for r in rs {
@@ -343,3 +731,169 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coupling_schedule::{EdgeParameter, Modulation};
+    use crate::mackey_glass;
+    use crate::network::SelectGroup;
+
+    #[test]
+    fn readers_share_lookups() {
+        let mut network = Network::new(3, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_edge(0, 2, 0.5, 0.0, 1.0);
+        network.put_edge(1, 2, 0.25, 0.0, 1.0);
+        network.put_edge(1, 2, 0.25, 0.0, 0.5);
+        network.put_edge(2, 0, 1.0, 0.0, 1.0);
+        let mut history = History::<mackey_glass::System, f64>::new(1.0 / 64.0, &network, false);
+        assert_eq!(history.lookups.len(), 3);
+        assert_eq!(history.readers[0][0].lookup, history.readers[1][0].lookup);
+
+        for step in 0..128 {
+            history.push_node_states(vec![step as f64, 0.0, step as f64]);
+        }
+        let feedback = history.get_all_feedback_rk4();
+        for (into, rs) in feedback.iter().enumerate() {
+            assert_eq!(rs[0], history.get_feedback_rk4(into)[0]);
+            assert_eq!(rs[1], history.get_feedback_rk4(into)[1]);
+        }
+        let mut in_place = vec![[0.0; 2]; 3];
+        history.get_all_feedback_rk4_into(&mut in_place);
+        assert_eq!(in_place, feedback);
+    }
+
+    #[test]
+    fn delays_a_few_ulps_short_are_rounded() {
+        let dt = 1.0 / 64.0;
+        // 191.99999999999997 steps, as a distance over a propagation speed can give
+        let delay = f64::from_bits(3.0f64.to_bits() - 3);
+        assert!(delay / dt < 192.0);
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 1, 0.5, 0.0, delay);
+        network.put_edge(1, 0, 0.5, 0.0, 3.0);
+        assert!(network.validate().is_ok());
+        let history = History::<mackey_glass::System, f64>::new(dt, &network, false);
+        assert_eq!(history.readers[0][0].at_delay, 192);
+        assert_eq!(history.readers[1][0].at_delay, 192);
+        assert_eq!(history.history[1].capacity(), 192);
+    }
+
+    #[test]
+    fn updates_keep_the_edges() {
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_bi_ring(0.5, 0.0, 1.0);
+        let mut history = History::<mackey_glass::System, f64>::new(1.0 / 64.0, &network, false);
+        assert_eq!(
+            history.update_edges(&network.edges[..1]),
+            Err(NetworkError::EdgeCountChanged {
+                edges: 1,
+                expected: 4
+            })
+        );
+        let mut edges = network.edges.clone();
+        edges[1].from = edges[1].into;
+        assert_eq!(
+            history.update_edges(&edges),
+            Err(NetworkError::EndpointsChanged { edge: 1 })
+        );
+        assert_eq!(history.edges(), network.edges);
+    }
+
+    #[test]
+    fn schedules_record_their_longest_delay() {
+        let dt = 1.0 / 64.0;
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 1, 1.0, 0.0, 1.0);
+        network.put_edge(1, 0, 1.0, 0.0, 1.0);
+        let mut history = History::<mackey_glass::System, f64>::new(dt, &network, false);
+        let jump = Modulation::Step {
+            time: 4.0,
+            value: 2.0,
+        };
+        let schedule =
+            CouplingSchedule::new(1).add(SelectGroup::SingleGroup(0), EdgeParameter::Delay, jump);
+        history.set_schedule(schedule).unwrap();
+        assert_eq!(history.history[1].capacity(), 128);
+        assert_eq!(history.history[0].capacity(), 64);
+
+        for step in 1..=288 {
+            history.push_node_states(vec![0.0, step as f64]);
+            history.advance_schedule();
+        }
+        // 2 time units after the jump the past from 2 time units ago was stored
+        assert_eq!(history.readers[0][0].at_delay, 128);
+        assert_eq!(history.get_all_feedback_rk4()[0][0], 161.0);
+    }
+
+    #[test]
+    fn longer_delays_grow_buffers() {
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_edge(0, 1, 0.5, 0.0, 1.0);
+        network.put_edge(1, 0, 0.5, 0.0, 1.0);
+        let mut history = History::<mackey_glass::System, f64>::new(1.0 / 64.0, &network, false);
+        for step in 0..64 {
+            history.push_node_states(vec![step as f64 + 1.0, 0.0]);
+        }
+        let mut edges = network.edges.clone();
+        edges[1].delay = 2.0; // into 1 from 0
+        edges[1].strength = 2.0;
+        history.update_edges(&edges).unwrap();
+
+        assert_eq!(history.history[1].capacity(), 64);
+        assert_eq!(history.history[0].capacity(), 128);
+        let memory = history.memory_per_node();
+        assert_eq!(memory[0].delay_steps, 128);
+        assert_eq!(memory[0].bytes, 128 * std::mem::size_of::<f64>());
+        assert_eq!(history.history[0].get(-1).unwrap(), 64.0);
+        assert_eq!(history.history[0].get(-64).unwrap(), 1.0);
+        // the unknown part of the past is filled with the oldest value
+        assert_eq!(history.history[0].get(-128).unwrap(), 1.0);
+        assert_eq!(history.get_all_feedback_rk4()[1], [2.0, 2.0]);
+        assert_eq!(history.readers[1][0].weight, 2.0);
+
+        edges[1].delay = 1.0 / 64.0;
+        assert!(history.update_edges(&edges).is_err());
+    }
+    #[test]
+    fn updated_edges_stay_modulated() {
+        let dt = 1.0 / 64.0;
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 1, 0.5, 0.0, 1.0);
+        network.put_edge(1, 0, 0.5, 0.0, 1.0);
+        let mut history = History::<mackey_glass::System, f64>::new(dt, &network, false);
+        let sinusoid = Modulation::Sinusoid {
+            amplitude: 0.5,
+            period: 4.0,
+            phase: 0.25,
+        };
+        let schedule = CouplingSchedule::new(1).add(
+            SelectGroup::SingleGroup(1),
+            EdgeParameter::Delay,
+            sinusoid,
+        );
+        history.set_schedule(schedule).unwrap();
+        assert_eq!(history.readers[1][0].at_delay, 96);
+
+        // the schedule stays on top of the new base edges
+        let mut edges = network.edges.clone();
+        edges[1].delay = 2.0;
+        edges[1].strength = 2.0;
+        history.update_edges(&edges).unwrap();
+        assert_eq!(history.readers[1][0].at_delay, 160);
+        assert_eq!(history.readers[1][0].weight, 2.0);
+        assert_eq!(history.edges()[1].delay, 2.0);
+
+        // 0.5 - 0.5 would be no delay at all during the schedule
+        edges[1].delay = 0.5;
+        assert!(matches!(
+            history.update_edges(&edges),
+            Err(NetworkError::DelayTooShort { edge: 1, .. })
+        ));
+        assert_eq!(history.edges()[1].delay, 2.0);
+        for step in 0..256 {
+            history.push_node_states(vec![step as f64, 0.0]);
+            history.advance_schedule();
+        }
+    }
+}
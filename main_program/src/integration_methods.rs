@@ -1,15 +1,68 @@
 use derive_more::{Add, AddAssign, Mul, Sum};
+use rand::rngs::SmallRng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use timeseries::Timeseries;
 
-use crate::dynamical_system::{DynamicalSystem, Feedback};
+use crate::coupling_schedule::CouplingSchedule;
+use crate::dynamical_system::{Components, DynamicalSystem, Feedback};
+use crate::history::MemoryReport;
+use crate::network::Edge;
+use crate::network_validation::NetworkError;
+use crate::plasticity::{PlasticInput, Plasticity};
 
 pub trait IntegrationMethods {
     fn single_step_rk4(&mut self);
     fn n_steps_rk4(&mut self, n: usize);
+    #[allow(dead_code)]
     fn keep_state(&self) -> Vec<f64>;
     fn integrate_and_keep_segment(&mut self, timeseries: &mut Timeseries);
+    // the network's edges with new values, changes take effect with the next step
+    fn update_edges(&mut self, edges: &[Edge]) -> Result<(), NetworkError>;
+    fn set_coupling_schedule(&mut self, schedule: CouplingSchedule) -> Result<(), NetworkError>;
+    fn set_plasticity(&mut self, rules: Vec<Plasticity>) -> Result<(), NetworkError>;
+    fn current_edges(&self) -> Vec<Edge>;
+    // different initial conditions: every node's state is scaled by a random factor in
+    // [1 - amount, 1 + amount)
+    fn perturb_states(&mut self, amount: f64, rng: &mut SmallRng);
+    // one name per layer a multiplex network can use
+    fn coupling_names(&self) -> &'static [&'static str];
+    fn memory_report(&self) -> MemoryReport;
     fn timeseries_row_len(&self) -> usize;
     fn timeseries_curve_names(&self) -> &'static [&'static str];
+    // everything that changes while integrating: time, states, models and the history with
+    // its current edges, schedule and learning rules. `restore_state` continues bit-identically,
+    // the system has to be built from the same network.
+    fn save_state(&self) -> Result<Vec<u8>, String>;
+    fn restore_state(&mut self, bytes: &[u8]) -> Result<(), String>;
+    // a json object with some of the model's parameters, e.g. `{"gamma": 0.2}`, for every node.
+    // the other parameters keep their values.
+    fn set_model_parameters(&mut self, parameters: &serde_json::Value) -> Result<(), String>;
+    // the whole state as plain numbers: the states of all nodes, then every value stored in the
+    // history. the state of a delay system includes its past, a perturbation perturbs both.
+    fn phase_space(&self) -> Vec<f64>;
+    // `components` as returned by `phase_space`, time, models and edges are kept
+    fn set_phase_space(&mut self, components: &[f64]);
+}
+
+// `model` with the fields named in the json object `parameters` replaced
+pub fn with_parameters<M>(model: &M, parameters: &serde_json::Value) -> Result<M, String>
+where
+    M: Serialize + DeserializeOwned,
+{
+    let mut value = serde_json::to_value(model).map_err(|e| e.to_string())?;
+    let (Some(fields), Some(new_fields)) = (value.as_object_mut(), parameters.as_object()) else {
+        return Err(format!(
+            "model parameters have to be an object, got {}",
+            parameters
+        ));
+    };
+    for (name, new_value) in new_fields {
+        match fields.get_mut(name) {
+            Some(field) => *field = new_value.clone(),
+            None => return Err(format!("the model has no parameter `{}`", name)),
+        }
+    }
+    serde_json::from_value(value).map_err(|e| format!("invalid model parameters: {}", e))
 }
 
 #[allow(dead_code)]
@@ -39,7 +92,7 @@ pub fn rk4<S>(
     *state += (k1 + k2 * 2.0 + k3 * 2.0 + k4) / 6.0 * *dt;
 }
 
-#[derive(Clone, Copy, Default, Mul, Add, AddAssign, Sum)]
+#[derive(Clone, Copy, Default, Mul, Add, AddAssign, Sum, Serialize, Deserialize)]
 pub struct RungeKuttaDelay<T>
 where
     T: Sized
@@ -54,6 +107,39 @@ where
     pub slope: T,
 }
 
+// state, then slope
+impl<T> Components for RungeKuttaDelay<T>
+where
+    T: Components
+        + Sized
+        + Clone
+        + Copy
+        + Default
+        + core::iter::Sum
+        + std::ops::Add<Output = T>
+        + std::ops::AddAssign,
+{
+    const LEN: usize = 2 * T::LEN;
+    fn write_components(&self, into: &mut [f64]) {
+        let (state, slope) = into.split_at_mut(T::LEN);
+        self.state.write_components(state);
+        self.slope.write_components(slope);
+    }
+    fn from_components(components: &[f64]) -> Self {
+        RungeKuttaDelay {
+            state: T::from_components(&components[..T::LEN]),
+            slope: T::from_components(&components[T::LEN..]),
+        }
+    }
+}
+
+// right hand side of a delay system, `Feedback::f`
+pub type DelayRhs<S> = fn(
+    &<S as DynamicalSystem>::StateT,
+    &<S as DynamicalSystem>::ModelT,
+    &<S as Feedback>::FeedbackT,
+) -> <S as DynamicalSystem>::StateT;
+
 #[allow(dead_code)]
 pub fn rk4_delay<S>(
     state: &mut S::StateT,
@@ -63,15 +149,14 @@ pub fn rk4_delay<S>(
     // at k1, (k2+k3) and k4 are calculated through hermite interpolations
     delay: &[RungeKuttaDelay<S::FeedbackT>; 2],
     dt: &f64,
-    f: fn(&S::StateT, &S::ModelT, &S::FeedbackT) -> S::StateT,
+    f: DelayRhs<S>,
     d: fn(&S::StateT) -> S::FeedbackT,
 ) where
     S: DynamicalSystem + Feedback,
 {
     let k1 = f(state, model, &delay[0].state);
     // the middle steps k2,k3 need a delay value not existing in the history. it is created through hermite interpolation.
-    let middle =
-        (delay[0].state + delay[1].state) * 0.5 + (delay[0].slope - delay[1].slope) * 0.125;
+    let middle = hermite_middle::<S>(delay);
     let k2 = f(&(*state + k1 * 0.5 * *dt), model, &middle);
     let k3 = f(&(*state + k2 * 0.5 * *dt), model, &middle);
     let k4 = f(&(*state + k3 * *dt), model, &delay[1].state);
@@ -81,6 +166,140 @@ pub fn rk4_delay<S>(
     keep_state.slope = d(&k1);
 }
 
+// with the `parallel` feature, nodes are handed to threads in chunks of at least this size
+#[cfg(feature = "parallel")]
+pub const PARALLEL_MIN_LEN: usize = 64;
+
+pub const MAX_LAYERS: usize = 8; // coupling variables of one system (`Feedback::coupling_names`)
+
+// the delayed value halfway between the two stored steps `delay`
+fn hermite_middle<S>(delay: &[RungeKuttaDelay<S::FeedbackT>; 2]) -> S::FeedbackT
+where
+    S: Feedback,
+{
+    (delay[0].state + delay[1].state) * 0.5 + (delay[0].slope - delay[1].slope) * 0.125
+}
+
+// an incoming plastic edge of a node of system `S`, see `rk4_delay_layers`
+pub type PlasticInputOf<S> =
+    PlasticInput<RungeKuttaDelay<<S as Feedback>::FeedbackT>, <S as Feedback>::WeightT>;
+
+// like `rk4_delay` with one feedback per layer of a multiplex network:
+// `delay[l]` and `keep_state[l]` belong to layer l. the strengths of the node's incoming edges
+// with smooth learning rules (`plastic`) are integrated along with the state and updated.
+#[allow(dead_code)]
+pub fn rk4_delay_layers<S>(
+    state: &mut S::StateT,
+    model: &S::ModelT,
+    keep_state: &mut [RungeKuttaDelay<S::FeedbackT>],
+    delay: &[[RungeKuttaDelay<S::FeedbackT>; 2]],
+    plastic: &mut [PlasticInputOf<S>],
+    dt: &f64,
+) where
+    S: DynamicalSystem + Feedback,
+{
+    if delay.len() == 1 && plastic.is_empty() {
+        rk4_delay::<S>(
+            state,
+            model,
+            &mut keep_state[0],
+            &delay[0],
+            dt,
+            S::f,
+            S::get_feedback,
+        );
+        return;
+    }
+    let layers = delay.len();
+    assert!(
+        layers <= MAX_LAYERS,
+        "at most {} coupling layers",
+        MAX_LAYERS
+    );
+    // on the stack, this runs for every node in every step
+    let mut start = [S::FeedbackT::default(); MAX_LAYERS];
+    let mut middle = [S::FeedbackT::default(); MAX_LAYERS];
+    let mut end = [S::FeedbackT::default(); MAX_LAYERS];
+    for (l, d) in delay.iter().enumerate() {
+        start[l] = d[0].state;
+        middle[l] = hermite_middle::<S>(d);
+        end[l] = d[1].state;
+    }
+    let [k1, k2, k3, k4] = if plastic.is_empty() {
+        let k1 = S::f_layers(state, model, &start[..layers]);
+        let k2 = S::f_layers(&(*state + k1 * 0.5 * *dt), model, &middle[..layers]);
+        let k3 = S::f_layers(&(*state + k2 * 0.5 * *dt), model, &middle[..layers]);
+        let k4 = S::f_layers(&(*state + k3 * *dt), model, &end[..layers]);
+        [k1, k2, k3, k4]
+    } else {
+        plastic_stages::<S>(
+            state,
+            model,
+            [&start[..layers], &middle[..layers], &end[..layers]],
+            plastic,
+            *dt,
+        )
+    };
+
+    let before = *state;
+    *state += (k1 + k2 * 2.0 + k3 * 2.0 + k4) / 6.0 * *dt;
+    for (l, keep) in keep_state.iter_mut().enumerate() {
+        keep.state = S::get_coupling(state, l);
+        keep.slope = S::get_coupling_slope(&before, &k1, l);
+    }
+}
+
+// the four stages of `rk4_delay_layers` with plastic edges. `feedback` are the sums of all
+// layers at the start, the middle and the end of the step with the strengths at the start, each
+// stage adds how far the plastic edges changed until then. the strengths take the same stages
+// as the state, with the arriving value and the node's coupling variable of that stage.
+fn plastic_stages<S>(
+    state: &S::StateT,
+    model: &S::ModelT,
+    feedback: [&[S::FeedbackT]; 3],
+    plastic: &mut [PlasticInputOf<S>],
+    dt: f64,
+) -> [S::StateT; 4]
+where
+    S: DynamicalSystem + Feedback,
+{
+    // (time after the start in dt, index into `feedback`, weight in the sum of the stages)
+    const STAGES: [(f64, usize, f64); 4] =
+        [(0.0, 0, 1.0), (0.5, 1, 2.0), (0.5, 1, 2.0), (1.0, 2, 1.0)];
+    let layers = feedback[0].len();
+    let mut k = [S::StateT::default(); 4];
+    let mut stage_feedback = [S::FeedbackT::default(); MAX_LAYERS];
+    for p in plastic.iter_mut() {
+        p.stage_slope = 0.0;
+        p.change = 0.0;
+    }
+    for (s, &(after, at, weight)) in STAGES.iter().enumerate() {
+        let stage_state = if s == 0 {
+            *state
+        } else {
+            *state + k[s - 1] * after * dt
+        };
+        stage_feedback[..layers].copy_from_slice(feedback[at]);
+        for p in plastic.iter_mut() {
+            p.stage_strength = p.strength + p.stage_slope * after * dt;
+            let pre = match at {
+                0 => p.arriving[0].state,
+                1 => hermite_middle::<S>(&p.arriving),
+                _ => p.arriving[1].state,
+            };
+            stage_feedback[p.layer] += pre * p.unit_weight * (p.stage_strength - p.strength);
+            let post = S::get_coupling(&stage_state, p.layer);
+            p.stage_slope = p.rule.rate_of_change(p.stage_strength, &pre, &post);
+            p.change += p.stage_slope * weight;
+        }
+        k[s] = S::f_layers(&stage_state, model, &stage_feedback[..layers]);
+    }
+    for p in plastic.iter_mut() {
+        p.strength = (p.strength + p.change / 6.0 * dt).clamp(p.min_strength, p.max_strength);
+    }
+    k
+}
+
 #[allow(dead_code)]
 pub fn euler<S>(
     state: &mut S::StateT,
@@ -99,9 +318,78 @@ pub fn euler_delay<S>(
     model: &S::ModelT,
     delay: &S::FeedbackT,
     dt: &f64,
-    f: fn(&S::StateT, &S::ModelT, &S::FeedbackT) -> S::StateT,
+    f: DelayRhs<S>,
 ) where
     S: DynamicalSystem + Feedback,
 {
     *state += f(state, model, delay) * *dt;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mackey_glass;
+    use crate::plasticity::PlasticityRule;
+
+    // a mackey-glass node with one hebbian edge, the arriving value rises linearly so that the
+    // hermite interpolation is exact. state and strength after 4 time units.
+    fn hebbian_node(dt: f64) -> (f64, f64) {
+        let arriving_at = |t: f64| RungeKuttaDelay {
+            state: 0.5 + 0.1 * t,
+            slope: 0.1,
+        };
+        let model = mackey_glass::Model::default();
+        let mut state = mackey_glass::State::default();
+        let mut keep = [RungeKuttaDelay::default()];
+        let mut strength = 0.2;
+        for step in 0..(4.0 / dt) as usize {
+            let t = step as f64 * dt;
+            let arriving = [arriving_at(t), arriving_at(t + dt)];
+            let mut plastic = [PlasticInput {
+                layer: 0,
+                edge: 0,
+                rule: PlasticityRule::Hebbian {
+                    rate: 1.0,
+                    decay: 1.0,
+                },
+                min_strength: -10.0,
+                max_strength: 10.0,
+                arriving,
+                unit_weight: 1.0,
+                strength,
+                stage_strength: strength,
+                stage_slope: 0.0,
+                change: 0.0,
+            }];
+            rk4_delay_layers::<mackey_glass::System>(
+                &mut state,
+                &model,
+                &mut keep,
+                &[[arriving[0] * strength, arriving[1] * strength]],
+                &mut plastic,
+                &dt,
+            );
+            strength = plastic[0].strength;
+        }
+        (state.q, strength)
+    }
+
+    // the strength takes the stages with the state, both are fourth order in dt
+    #[test]
+    fn plastic_strengths_take_the_runge_kutta_stages() {
+        let reference = hebbian_node(1.0 / 1024.0);
+        let errors: Vec<(f64, f64)> = [4.0, 8.0, 16.0]
+            .iter()
+            .map(|steps| {
+                let (q, strength) = hebbian_node(1.0 / steps);
+                ((q - reference.0).abs(), (strength - reference.1).abs())
+            })
+            .collect();
+        for pair in errors.windows(2) {
+            let state_order = (pair[0].0 / pair[1].0).log2();
+            let strength_order = (pair[0].1 / pair[1].1).log2();
+            assert!((3.5..4.5).contains(&state_order), "{:?}", errors);
+            assert!((3.5..4.5).contains(&strength_order), "{:?}", errors);
+        }
+    }
+}
@@ -1,6 +1,7 @@
-use crate::dynamical_system::{AsData, DynamicalSystem, Feedback, WeightComplex};
+use crate::dynamical_system::{AsData, Components, DynamicalSystem, Feedback, WeightComplex};
 use derive_more::{Add, AddAssign, Div, Mul, MulAssign};
 use num_complex::Complex;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 // const DOMAIN_NAME: &'static str = "lang_kobayashi";
@@ -31,6 +32,9 @@ impl DynamicalSystem for System {
     fn keep_state(state: &Self::StateT) -> Vec<f64> {
         vec![state.e.norm_sqr(), state.n]
     }
+    fn keep_state_into(state: &Self::StateT, values: &mut [f64]) {
+        values.copy_from_slice(&[state.e.norm_sqr(), state.n])
+    }
     fn keep_state_names() -> &'static [&'static str] {
         &["e_norm", "n"]
     }
@@ -61,12 +65,25 @@ impl Feedback for System {
 pub type FeedbackState = Complex<f64>;
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Add, AddAssign, Mul, MulAssign, Div, Debug)]
+#[derive(Copy, Clone, Add, AddAssign, Mul, MulAssign, Div, Debug, Serialize, Deserialize)]
 pub struct State {
     pub e: Complex<f64>,
     pub n: f64,
 }
 
+impl Components for State {
+    const LEN: usize = 3;
+    fn write_components(&self, into: &mut [f64]) {
+        into.copy_from_slice(&[self.e.re, self.e.im, self.n]);
+    }
+    fn from_components(components: &[f64]) -> Self {
+        State {
+            e: Complex::new(components[0], components[1]),
+            n: components[2],
+        }
+    }
+}
+
 impl Default for State {
     fn default() -> Self {
         State {
@@ -87,7 +104,8 @@ impl fmt::Display for State {
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Model {
     pub alpha: f64,
     pub pump: f64,
@@ -0,0 +1,425 @@
+use crate::coupling_schedule::CouplingSchedule;
+use crate::delay_coupling::DelayCoupling;
+use crate::dynamical_system::Feedback;
+use crate::history::{History, MemoryReport};
+use crate::network::{Edge, Network};
+use crate::network_layers::SelectLayer;
+use crate::network_validation::NetworkError;
+use crate::plasticity::{Activity, PlasticInput, Plasticity};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+use std::marker::PhantomData;
+
+// one `History` (or another `DelayCoupling`) per layer of a multiplex network. values of all
+// layers are passed around node-major: `values[node * layers + layer]`. with a single layer
+// this is just the history.
+#[allow(dead_code)]
+#[derive(Clone, Serialize, Deserialize)]
+// `H` is serializable as a `DelayCoupling`
+#[serde(bound(serialize = "T: Serialize", deserialize = "T: DeserializeOwned"))]
+pub struct LayeredHistory<S, T, H = History<S, T>>
+where
+    S: Feedback,
+    T: Sized
+        + Clone
+        + Copy
+        + Default
+        + core::iter::Sum
+        + std::ops::Add<Output = T>
+        + std::ops::AddAssign
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync,
+    H: DelayCoupling<S, T>,
+{
+    layers: Vec<H>,
+    edges_of_layer: Vec<Vec<usize>>, // edge i of layer l is the network's edge `edges_of_layer[l][i]`
+    // scratch of multiplex networks, reused every step
+    layer_feedback: Vec<Vec<[T; 2]>>,
+    layer_states: Vec<T>,
+    system: PhantomData<S>, // the layers `H` couple systems of type `S`
+}
+
+#[allow(dead_code)]
+impl<S, T, H> LayeredHistory<S, T, H>
+where
+    S: Feedback,
+    T: Sized
+        + Clone
+        + Copy
+        + Default
+        + core::iter::Sum
+        + std::ops::Add<Output = T>
+        + std::ops::AddAssign
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync,
+    H: DelayCoupling<S, T>,
+{
+    pub fn new(dt: f64, network: &Network, equal_ringbuffers: bool) -> Self {
+        let (layers, edges_of_layer): (Vec<_>, Vec<_>) = (0..network.number_of_layers())
+            .map(|l| {
+                let selection = SelectLayer::SingleLayer(l);
+                (
+                    H::new(dt, &network.select_layers(selection), equal_ringbuffers),
+                    network.edges_in_layers(selection),
+                )
+            })
+            .unzip();
+        let layer_feedback = vec![vec![[T::default(); 2]; network.nodes]; layers.len()];
+        LayeredHistory {
+            layers,
+            edges_of_layer,
+            layer_feedback,
+            layer_states: vec![T::default(); network.nodes],
+            system: PhantomData,
+        }
+    }
+
+    pub fn number_of_layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    // the number of nodes of every layer, e.g. to check a restored history
+    pub fn shape(&self) -> Vec<usize> {
+        self.memory_report().layers.iter().map(Vec::len).collect()
+    }
+
+    pub fn layer(&self, layer: usize) -> &H {
+        &self.layers[layer]
+    }
+
+    pub fn get_all_feedback_rk4(&self) -> Vec<[T; 2]> {
+        if self.layers.len() == 1 {
+            return self.layers[0].get_all_feedback_rk4();
+        }
+        let per_layer: Vec<Vec<[T; 2]>> = self
+            .layers
+            .iter()
+            .map(|h| h.get_all_feedback_rk4())
+            .collect();
+        (0..per_layer[0].len())
+            .flat_map(|node| per_layer.iter().map(move |feedback| feedback[node]))
+            .collect()
+    }
+
+    pub fn get_all_feedback_rk4_into(&mut self, feedback: &mut [[T; 2]]) {
+        if self.layers.len() == 1 {
+            self.layers[0].get_all_feedback_rk4_into(feedback);
+            return;
+        }
+        let layers = self.layers.len();
+        for (l, (h, layer_feedback)) in self
+            .layers
+            .iter_mut()
+            .zip(&mut self.layer_feedback)
+            .enumerate()
+        {
+            h.get_all_feedback_rk4_into(layer_feedback);
+            for (f, sums) in feedback
+                .iter_mut()
+                .skip(l)
+                .step_by(layers)
+                .zip(layer_feedback.iter())
+            {
+                *f = *sums;
+            }
+        }
+    }
+
+    pub fn push_node_states(&mut self, new_states: Vec<T>) {
+        self.push_node_states_from(&new_states)
+    }
+
+    pub fn push_node_states_from(&mut self, new_states: &[T]) {
+        if self.layers.len() == 1 {
+            self.layers[0].push_node_states_from(new_states);
+            return;
+        }
+        let layers = self.layers.len();
+        for (l, h) in self.layers.iter_mut().enumerate() {
+            for (state, s) in self
+                .layer_states
+                .iter_mut()
+                .zip(new_states.iter().skip(l).step_by(layers))
+            {
+                *state = *s;
+            }
+            h.push_node_states_from(&self.layer_states);
+        }
+    }
+
+    // the edges keep their layer, only strength, turn and delay change. like the following
+    // two, every layer is checked before any of them changes: nothing changes on an error
+    pub fn update_edges(&mut self, edges: &[Edge]) -> Result<(), NetworkError> {
+        let expected = self.edges_of_layer.iter().map(Vec::len).sum();
+        if edges.len() != expected {
+            return Err(NetworkError::EdgeCountChanged {
+                edges: edges.len(),
+                expected,
+            });
+        }
+        let layer_edges: Vec<Vec<Edge>> = self
+            .edges_of_layer
+            .iter()
+            .map(|indices| indices.iter().map(|&i| edges[i]).collect())
+            .collect();
+        for ((h, indices), layer_edges) in self
+            .layers
+            .iter()
+            .zip(&self.edges_of_layer)
+            .zip(&layer_edges)
+        {
+            h.check_edges(layer_edges)
+                .map_err(|e| e.renumber_edge(indices))?;
+        }
+        for ((h, indices), layer_edges) in self
+            .layers
+            .iter_mut()
+            .zip(&self.edges_of_layer)
+            .zip(&layer_edges)
+        {
+            h.update_edges(layer_edges)
+                .map_err(|e| e.renumber_edge(indices))?;
+        }
+        Ok(())
+    }
+
+    pub fn set_schedule(&mut self, schedule: CouplingSchedule) -> Result<(), NetworkError> {
+        for (h, indices) in self.layers.iter().zip(&self.edges_of_layer) {
+            h.check_schedule(&schedule)
+                .map_err(|e| e.renumber_edge(indices))?;
+        }
+        for (h, indices) in self.layers.iter_mut().zip(&self.edges_of_layer) {
+            h.set_schedule(schedule.clone())
+                .map_err(|e| e.renumber_edge(indices))?;
+        }
+        Ok(())
+    }
+
+    pub fn set_plasticity(&mut self, rules: Vec<Plasticity>) -> Result<(), NetworkError> {
+        for (h, indices) in self.layers.iter().zip(&self.edges_of_layer) {
+            h.check_plasticity(&rules)
+                .map_err(|e| e.renumber_edge(indices))?;
+        }
+        for (h, indices) in self.layers.iter_mut().zip(&self.edges_of_layer) {
+            h.set_plasticity(rules.clone())
+                .map_err(|e| e.renumber_edge(indices))?;
+        }
+        Ok(())
+    }
+
+    // `inputs[node]`: the incoming edges of all layers with smooth learning rules, which the node
+    // integrates in its next step
+    pub fn plastic_inputs_into(&self, inputs: &mut [Vec<PlasticInput<T, S::WeightT>>]) {
+        inputs.iter_mut().for_each(Vec::clear);
+        for (l, h) in self.layers.iter().enumerate() {
+            h.plastic_inputs_into(l, inputs);
+        }
+    }
+
+    // after the step, before `advance`
+    pub fn set_plastic_strengths(&mut self, inputs: &[Vec<PlasticInput<T, S::WeightT>>]) {
+        for (l, h) in self.layers.iter_mut().enumerate() {
+            h.set_plastic_strengths(l, inputs);
+        }
+    }
+
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport {
+            layers: self.layers.iter().map(|h| h.memory_per_node()).collect(),
+        }
+    }
+
+    // the layers one after the other, see `DelayCoupling::past`
+    pub fn past(&self) -> Vec<T> {
+        self.layers.iter().flat_map(|h| h.past()).collect()
+    }
+
+    pub fn set_past(&mut self, past: &[T]) -> usize {
+        self.layers
+            .iter_mut()
+            .fold(0, |taken, h| taken + h.set_past(&past[taken..]))
+    }
+
+    // current edges of all layers in network order
+    pub fn edges(&self) -> Vec<Edge> {
+        let mut edges = vec![Edge::default(); self.edges_of_layer.iter().map(Vec::len).sum()];
+        for (h, indices) in self.layers.iter().zip(&self.edges_of_layer) {
+            for (e, &i) in h.edges().iter().zip(indices) {
+                edges[i] = *e;
+            }
+        }
+        edges
+    }
+}
+
+#[allow(dead_code)]
+impl<S, T, H> LayeredHistory<S, T, H>
+where
+    S: Feedback,
+    T: Sized
+        + Clone
+        + Copy
+        + Default
+        + Activity
+        + core::iter::Sum
+        + std::ops::Add<Output = T>
+        + std::ops::AddAssign
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync,
+    H: DelayCoupling<S, T>,
+{
+    // call once per integration step, after the new states are pushed
+    pub fn advance(&mut self) {
+        for h in &mut self.layers {
+            h.advance_schedule();
+            h.advance_plasticity();
+        }
+    }
+}
+
+impl<S, T, H> Default for LayeredHistory<S, T, H>
+where
+    S: Feedback,
+    T: Sized
+        + Clone
+        + Copy
+        + Default
+        + core::iter::Sum
+        + std::ops::Add<Output = T>
+        + std::ops::AddAssign
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync,
+    H: DelayCoupling<S, T>,
+{
+    fn default() -> Self {
+        LayeredHistory {
+            layers: vec![H::default()],
+            edges_of_layer: vec![Vec::new()],
+            layer_feedback: vec![Vec::new()],
+            layer_states: Vec::new(),
+            system: PhantomData,
+        }
+    }
+}
+
+impl<S, T, H> fmt::Display for LayeredHistory<S, T, H>
+where
+    S: Feedback,
+    T: Sized
+        + Clone
+        + Copy
+        + Default
+        + core::iter::Sum
+        + std::ops::Add<Output = T>
+        + std::ops::AddAssign
+        + std::ops::Mul<S::WeightT, Output = T>
+        + Send
+        + Sync,
+    H: DelayCoupling<S, T>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (l, h) in self.layers.iter().enumerate() {
+            writeln!(f, "layer {} ({} edges):", l, self.edges_of_layer[l].len())?;
+            write!(f, "{}", h)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coupling_schedule::{EdgeParameter, Modulation};
+    use crate::mackey_glass;
+    use crate::network::SelectGroup;
+
+    #[test]
+    fn layers_read_their_own_values() {
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_edge(0, 1, 1.0, 0.0, 1.0);
+        network.put_edge(1, 0, 0.5, 0.0, 2.0);
+        let second = network.add_layer("second");
+        network.move_to_layer(SelectGroup::SingleGroup(1), second);
+        let mut history =
+            LayeredHistory::<mackey_glass::System, f64>::new(1.0 / 64.0, &network, false);
+        assert_eq!(history.number_of_layers(), 2);
+
+        // node-major: node 0 layer 0, node 0 layer 1, node 1 layer 0, node 1 layer 1
+        for _ in 0..256 {
+            history.push_node_states(vec![2.0, 20.0, 1.0, 10.0]);
+        }
+        let feedback = history.get_all_feedback_rk4();
+        let mut in_place = vec![[0.0; 2]; 4];
+        history.get_all_feedback_rk4_into(&mut in_place);
+        assert_eq!(in_place, feedback);
+        assert_eq!(feedback[0], [1.0, 1.0]); // from node 1 through layer 0
+        assert_eq!(feedback[1], [0.0, 0.0]);
+        assert_eq!(feedback[2], [0.0, 0.0]);
+        assert_eq!(feedback[3], [10.0, 10.0]); // from node 0 through layer 1
+
+        let mut edges = history.edges();
+        assert_eq!(edges, network.edges);
+        edges[1].delay = 1.0 / 64.0;
+        assert_eq!(
+            history.update_edges(&edges),
+            Err(NetworkError::DelayTooShort {
+                edge: 1,
+                delay: 1.0 / 64.0,
+                steps: 1
+            })
+        );
+    }
+
+    #[test]
+    fn failing_layer_changes_no_layer() {
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_edge(0, 1, 1.0, 0.0, 1.0);
+        network.put_edge(1, 0, 0.5, 0.0, 2.0);
+        let second = network.add_layer("second");
+        network.move_to_layer(SelectGroup::SingleGroup(1), second);
+        let mut history =
+            LayeredHistory::<mackey_glass::System, f64>::new(1.0 / 64.0, &network, false);
+        for _ in 0..256 {
+            history.push_node_states(vec![2.0, 20.0, 1.0, 10.0]);
+        }
+        let feedback = history.get_all_feedback_rk4();
+
+        // only the edge of layer 1 is too short
+        let mut edges = history.edges();
+        edges[0].strength = 3.0;
+        edges[1].delay = 1.0 / 64.0;
+        assert!(history.update_edges(&edges).is_err());
+        assert_eq!(history.edges(), network.edges);
+
+        let schedule = CouplingSchedule::new(1)
+            .add(
+                SelectGroup::AllGroups,
+                EdgeParameter::Strength,
+                Modulation::Step {
+                    time: 0.0,
+                    value: 3.0,
+                },
+            )
+            .add(
+                SelectGroup::SingleGroup(1),
+                EdgeParameter::Delay,
+                Modulation::Step {
+                    time: 0.0,
+                    value: 1.0 / 64.0,
+                },
+            );
+        assert_eq!(
+            history.set_schedule(schedule),
+            Err(NetworkError::DelayTooShort {
+                edge: 1,
+                delay: 1.0 / 64.0,
+                steps: 1
+            })
+        );
+        assert_eq!(history.get_all_feedback_rk4(), feedback);
+    }
+}
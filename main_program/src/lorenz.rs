@@ -1,6 +1,7 @@
 // extern crate derive_more;
-use crate::dynamical_system::{DynamicalSystem, Feedback, WeightReal};
+use crate::dynamical_system::{Components, DynamicalSystem, Feedback, WeightReal};
 use derive_more::{Add, AddAssign, Div, Mul, MulAssign};
+use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display};
 
 pub struct System {}
@@ -10,6 +11,9 @@ impl DynamicalSystem for System {
     fn keep_state(state: &Self::StateT) -> Vec<f64> {
         vec![state.x, state.y, state.z]
     }
+    fn keep_state_into(state: &Self::StateT, values: &mut [f64]) {
+        values.copy_from_slice(&[state.x, state.y, state.z])
+    }
     fn keep_state_names() -> &'static [&'static str] {
         &["x", "y", "z"]
     }
@@ -38,13 +42,27 @@ impl Feedback for System {
 
 type FeedbackState = f64;
 
-#[derive(Copy, Clone, Add, AddAssign, Mul, MulAssign, Div, Debug)]
+#[derive(Copy, Clone, Add, AddAssign, Mul, MulAssign, Div, Debug, Serialize, Deserialize)]
 pub struct State {
     pub x: f64,
     pub y: f64,
     pub z: f64,
 }
 
+impl Components for State {
+    const LEN: usize = 3;
+    fn write_components(&self, into: &mut [f64]) {
+        into.copy_from_slice(&[self.x, self.y, self.z]);
+    }
+    fn from_components(components: &[f64]) -> Self {
+        State {
+            x: components[0],
+            y: components[1],
+            z: components[2],
+        }
+    }
+}
+
 impl Default for State {
     fn default() -> Self {
         State {
@@ -65,7 +83,8 @@ impl Display for State {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Model {
     pub sigma: f64,
     pub beta: f64,
@@ -0,0 +1,250 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::Deserialize;
+
+use crate::integration_methods::IntegrationMethods;
+
+// distance of the perturbed copies from the trajectory, in the whole phase space
+const SEPARATION: f64 = 1e-8;
+
+// the first `exponents` lyapunov exponents of the trajectory from the current state, averaged
+// over `time` after `settle` time units that only align the perturbations. renormalized every
+// `interval`, which has to be short enough that the most contracting perturbation stays well
+// above the rounding of the state. `file` in the data directory gets one exponent per line.
+#[allow(dead_code)]
+#[derive(Clone, Deserialize)]
+pub struct LyapunovExponents {
+    #[serde(default = "default_exponents")]
+    pub exponents: usize,
+    #[serde(default = "default_interval")]
+    pub interval: f64,
+    pub settle: f64,
+    pub time: f64,
+    pub file: String,
+}
+
+fn default_exponents() -> usize {
+    1
+}
+
+fn default_interval() -> f64 {
+    1.0
+}
+
+// largest lyapunov exponent, `lyapunov_spectrum` with a single perturbation
+#[allow(dead_code)]
+pub fn largest_lyapunov_exponent(
+    system: &mut dyn IntegrationMethods,
+    dt: f64,
+    steps: usize,
+    settle: usize,
+    intervals: usize,
+    on_step: impl FnMut(&dyn IntegrationMethods),
+) -> Result<f64, String> {
+    Ok(lyapunov_spectrum(system, dt, steps, settle, intervals, 1, on_step)?[0])
+}
+
+// the first `exponents` lyapunov exponents from perturbed copies of the trajectory (benettin).
+// the copies differ in the whole state of the delay system, history included
+// (`phase_space`), so they explore the infinite dimensional state, discretized by the history.
+// every copy is integrated for `steps` steps, then the perturbations are orthonormalized
+// (gram-schmidt) and scaled back to `SEPARATION`, `intervals` times. the first `settle`
+// intervals only turn the perturbations into the most unstable directions and are not counted.
+// one system integrates everything: the copies from the saved trajectory, then the trajectory
+// again. `on_step` sees every counted step of the trajectory. nan if the trajectory diverges.
+#[allow(dead_code)]
+pub fn lyapunov_spectrum(
+    system: &mut dyn IntegrationMethods,
+    dt: f64,
+    steps: usize,
+    settle: usize,
+    intervals: usize,
+    exponents: usize,
+    mut on_step: impl FnMut(&dyn IntegrationMethods),
+) -> Result<Vec<f64>, String> {
+    let dimension = system.phase_space().len();
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut directions: Vec<Vec<f64>> = (0..exponents.min(dimension))
+        .map(|_| (0..dimension).map(|_| rng.gen_range(-1.0..1.0)).collect())
+        .collect();
+    orthonormalize(&mut directions);
+
+    let mut sums = vec![0.0; directions.len()];
+    for interval in 0..settle + intervals {
+        let counted = interval >= settle;
+        let start = system.save_state()?;
+        let origin = system.phase_space();
+        let mut copies = Vec::with_capacity(directions.len());
+        for direction in &directions {
+            system.restore_state(&start)?;
+            let copy: Vec<f64> = origin
+                .iter()
+                .zip(direction)
+                .map(|(x, d)| x + SEPARATION * d)
+                .collect();
+            system.set_phase_space(&copy);
+            system.n_steps_rk4(steps);
+            copies.push(system.phase_space());
+        }
+
+        system.restore_state(&start)?;
+        for _ in 0..steps {
+            system.single_step_rk4();
+            if counted {
+                on_step(system);
+            }
+        }
+        let end = system.phase_space();
+        directions = copies
+            .iter()
+            .map(|copy| {
+                copy.iter()
+                    .zip(&end)
+                    .map(|(y, x)| (y - x) / SEPARATION)
+                    .collect()
+            })
+            .collect();
+        let growths = orthonormalize(&mut directions);
+        if counted {
+            for (sum, growth) in sums.iter_mut().zip(growths) {
+                *sum += growth.ln();
+            }
+        }
+        if sums.iter().any(|sum| sum.is_nan()) {
+            break;
+        }
+    }
+    let time = (intervals * steps) as f64 * dt;
+    Ok(sums.into_iter().map(|sum| sum / time).collect())
+}
+
+// j + (l_1 + ... + l_j) / |l_(j+1)| for the largest j whose sum is not negative, the exponents
+// sorted from the largest. none if the sum of all of them is not negative yet.
+#[allow(dead_code)]
+pub fn kaplan_yorke_dimension(exponents: &[f64]) -> Option<f64> {
+    let mut sum = 0.0;
+    for (j, exponent) in exponents.iter().enumerate() {
+        if sum + exponent < 0.0 {
+            return Some(j as f64 + sum / exponent.abs());
+        }
+        sum += exponent;
+    }
+    None
+}
+
+// modified gram-schmidt, returns the length of every vector after the earlier ones were
+// removed from it
+fn orthonormalize(vectors: &mut [Vec<f64>]) -> Vec<f64> {
+    (0..vectors.len())
+        .map(|i| {
+            let (done, rest) = vectors.split_at_mut(i);
+            let vector = &mut rest[0];
+            for unit in done.iter() {
+                let projection: f64 = unit.iter().zip(vector.iter()).map(|(u, v)| u * v).sum();
+                for (v, u) in vector.iter_mut().zip(unit) {
+                    *v -= projection * u;
+                }
+            }
+            normalize(vector)
+        })
+        .collect()
+}
+
+// returns the length before
+fn normalize(vector: &mut [f64]) -> f64 {
+    let length = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+    vector.iter_mut().for_each(|x| *x /= length);
+    length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculation::{Calculation, CouplingLayout, NodeSetup, SystemType, Tasks};
+    use crate::composite_system::SingleFeedbackSystem;
+    use crate::lorenz;
+    use crate::network::Network;
+    use std::fs;
+    use timeseries::SaveItems;
+
+    #[test]
+    fn lorenz_spectrum() {
+        // (0.906, 0, -14.572) for the classic parameters. the uncoupled feedback is as short as
+        // possible, the history it keeps would otherwise weigh in the norm of the perturbations.
+        let dt = 1.0 / 256.0;
+        let mut network = Network::new(1, 0.0, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 0, 0.0, 0.0, 2.0 * dt);
+        let mut system = SingleFeedbackSystem::<lorenz::System>::new(&network, dt);
+        system.n_steps_rk4(20 * 256);
+        let exponents = lyapunov_spectrum(&mut system, dt, 32, 100, 2000, 3, |_| {}).unwrap();
+        assert!((exponents[0] - 0.906).abs() < 0.1, "{:?}", exponents);
+        assert!(exponents[1].abs() < 0.03, "{:?}", exponents);
+        assert!((exponents[2] + 14.572).abs() < 0.2, "{:?}", exponents);
+        let dimension = kaplan_yorke_dimension(&exponents).unwrap();
+        assert!((dimension - 2.06).abs() < 0.01, "{}", dimension);
+    }
+
+    #[test]
+    fn mackey_glass_spectrum() {
+        // one positive exponent of about 0.007 at a delay of 17, the attractor a little above two
+        // dimensions
+        let dt = 1.0 / 32.0;
+        let directory = std::env::temp_dir().join("lyapunov_mackey_glass");
+        let mut network = Network::new(1, 0.0, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 0, 1.0, 0.0, 17.0);
+        let tasks = vec![
+            Tasks::IntegrateUntilTimeNoSave { time: 500.0 },
+            Tasks::LyapunovExponents(LyapunovExponents {
+                exponents: 3,
+                interval: 1.0,
+                settle: 200.0,
+                time: 6000.0,
+                file: "exponents.txt".to_string(),
+            }),
+        ];
+        let mut calculation = Calculation::examples_in_directory(
+            &directory.to_string_lossy(),
+            dt,
+            &network,
+            256,
+            NodeSetup::Identical,
+            SystemType::MackeyGlass,
+            CouplingLayout::RingBuffers,
+            &tasks,
+            SaveItems::Timeseries,
+        )
+        .unwrap();
+        calculation.perform_tasks().unwrap();
+        let exponents: Vec<f64> = fs::read_to_string(directory.join("exponents.txt"))
+            .unwrap()
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        fs::remove_dir_all(directory).unwrap();
+        assert_eq!(exponents.len(), 3);
+        assert!(
+            0.003 < exponents[0] && exponents[0] < 0.011,
+            "{:?}",
+            exponents
+        );
+        assert!(exponents[1].abs() < 0.002, "{:?}", exponents);
+        assert!(exponents[2] < -0.02, "{:?}", exponents);
+        let dimension = kaplan_yorke_dimension(&exponents).unwrap();
+        assert!(2.0 < dimension && dimension < 2.4, "{}", dimension);
+    }
+
+    #[test]
+    fn kaplan_yorke_of_known_spectra() {
+        assert_eq!(kaplan_yorke_dimension(&[-1.0]), Some(0.0));
+        assert_eq!(kaplan_yorke_dimension(&[0.0, -2.0]), Some(1.0));
+        assert_eq!(kaplan_yorke_dimension(&[1.0, 0.0, -4.0]), Some(2.25));
+        assert_eq!(kaplan_yorke_dimension(&[1.0, 0.0]), None);
+    }
+
+    #[test]
+    fn orthonormalize_returns_the_lengths() {
+        let mut vectors = vec![vec![3.0, 4.0, 0.0], vec![3.0, 4.0, 2.0]];
+        let lengths = orthonormalize(&mut vectors);
+        assert_eq!(lengths, vec![5.0, 2.0]);
+        assert_eq!(vectors[1], vec![0.0, 0.0, 1.0]);
+    }
+}
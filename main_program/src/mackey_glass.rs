@@ -1,6 +1,7 @@
-use crate::dynamical_system::{DynamicalSystem, Feedback, WeightReal};
+use crate::dynamical_system::{Components, DynamicalSystem, Feedback, WeightReal};
 
 use derive_more::{Add, AddAssign, Div, Mul, MulAssign};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 #[allow(dead_code)]
@@ -11,6 +12,9 @@ impl DynamicalSystem for System {
     fn keep_state(state: &Self::StateT) -> Vec<f64> {
         vec![state.q]
     }
+    fn keep_state_into(state: &Self::StateT, values: &mut [f64]) {
+        values.copy_from_slice(&[state.q])
+    }
     fn keep_state_names() -> &'static [&'static str] {
         &["p"]
     }
@@ -39,11 +43,21 @@ impl Feedback for System {
 pub type FeedbackState = f64;
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Add, AddAssign, Mul, MulAssign, Div, Debug)]
+#[derive(Copy, Clone, Add, AddAssign, Mul, MulAssign, Div, Debug, Serialize, Deserialize)]
 pub struct State {
     pub q: f64,
 }
 
+impl Components for State {
+    const LEN: usize = 1;
+    fn write_components(&self, into: &mut [f64]) {
+        into[0] = self.q;
+    }
+    fn from_components(components: &[f64]) -> Self {
+        State { q: components[0] }
+    }
+}
+
 impl Default for State {
     fn default() -> Self {
         State { q: 0.5 }
@@ -57,7 +71,8 @@ impl fmt::Display for State {
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Model {
     pub beta_0: f64,
     pub n: i32,
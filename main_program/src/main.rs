@@ -1,18 +1,46 @@
 use std::env;
 
-use calculation::{NodeSetup, SystemType, Tasks};
-use network::Network;
-
-use timeseries::SaveItems;
+use calculation::{Calculation, Tasks};
+use ensemble::{Ensemble, EnsembleMember};
+use global_parameter_map::GlobalParameterMap;
+use network_validation::NetworkError;
+use parameter::ArgumentError;
+use run_config::RunConfig;
 
 mod timer;
 
 mod calculation;
+mod checkpoint;
+mod command_line;
 mod composite_system;
+mod coupling_schedule;
+mod csr_history;
+mod delay_buffer;
+mod delay_coupling;
 mod dynamical_system;
+mod edge_selection;
+mod ensemble;
+mod global_parameter_map;
 mod history;
 mod integration_methods;
+mod layered_history;
+mod lyapunov;
+mod matrix;
 mod network;
+mod network_analysis;
+mod network_export;
+mod network_io;
+mod network_layers;
+mod network_validation;
+mod network_weights;
+mod parameter;
+mod parameter_sweep;
+mod plasticity;
+mod random_networks;
+mod regime_map;
+mod run_config;
+mod spatial_networks;
+mod synchronisation;
 
 mod fitzhugh_nagumo;
 mod hindmarsh_rose;
@@ -22,92 +50,144 @@ mod mackey_glass;
 // mod mdre;
 mod roessler;
 mod stuart_landau;
+mod var;
 
 use timer::Timer;
 
 fn main() {
     let args = env::args().collect::<Vec<String>>();
+    let mut parameters = command_line::parameter_map();
+    if args[1..]
+        .iter()
+        .any(|arg| arg == "-help" || arg == "--help" || arg == "-h")
+    {
+        print!("{}", parameters.help(&args[0]));
+        return;
+    }
+    if let Err(error) = parameters.set_from_args(&args[1..]) {
+        println!("{} (-help lists all options)", error);
+        return;
+    }
+    println!("{}", parameters);
 
-    let mut inv_dt = 512.0;
-    let mut buffer_time = 0.0;
-    let mut seg_length = 1024;
-    let mut segments = 25;
-    let mut epsilon = 0.005;
-    let mut tau = 10.0;
+    if let Err(error) = run(&parameters) {
+        println!("{}", error);
+    }
+}
 
-    for (i, pattern) in args.iter().enumerate() {
-        match pattern.as_str() {
-            "-idt" => {
-                if args.len() - i >= 1 {
-                    println!("{} {}", pattern, args[i + 1]);
-                    inv_dt = args[i + 1].parse().unwrap()
-                }
-            }
-            "-buft" => {
-                if args.len() - i >= 1 {
-                    println!("{} {}", pattern, args[i + 1]);
-                    buffer_time = args[i + 1].parse().unwrap()
-                }
-            }
-            "-segl" => {
-                if args.len() - i >= 1 {
-                    println!("{} {}", pattern, args[i + 1]);
-                    seg_length = args[i + 1].parse().unwrap()
-                }
-            }
-            "-segs" => {
-                if args.len() - i >= 1 {
-                    println!("{} {}", pattern, args[i + 1]);
-                    segments = args[i + 1].parse().unwrap()
-                }
-            }
-            "-epsilon" => {
-                if args.len() - i >= 1 {
-                    println!("{} {}", pattern, args[i + 1]);
-                    epsilon = args[i + 1].parse().unwrap()
-                }
-            }
-            "-tau" => {
-                if args.len() - i >= 1 {
-                    println!("{} {}", pattern, args[i + 1]);
-                    tau = args[i + 1].parse().unwrap()
-                }
-            }
+fn run(parameters: &GlobalParameterMap) -> Result<(), String> {
+    let to_string = |error: ArgumentError| error.to_string();
 
-            _ => {}
-        }
+    let ensemble_members = parameters.value::<u32>("tasks", "ensemble") as usize;
+    if ensemble_members > 0 {
+        return run_ensemble(parameters, ensemble_members).map_err(to_string);
     }
 
-    let mut network = Network::new(1, 0.1, 0.1, 100.0, 0, 1.0 / inv_dt);
-    network.put_edge(0, 0, 0.0, 0.5, tau);
-    // network.put_ring(0.125, 0.5, tau * 0.321);
-
-    let task_sequence = vec![
-        Tasks::IntegrateUntilTimeNoSave { time: buffer_time },
-        Tasks::IntegrateSegmentsAndSave {
-            segments: segments,
-            epsilon: epsilon,
-        },
-        Tasks::PrintTechnicalDetails,
-    ];
+    // -config file: the whole run (system, network, tasks) from a json file, see ./configs
+    let config = match command_line::file(parameters, "tasks", "config") {
+        Some(file) => Some(RunConfig::load(file).map_err(|error| format!("{}: {}", file, error))?),
+        None => None,
+    };
+    let network = match &config {
+        Some(config) => config.build_network().map_err(|error| error.to_string())?,
+        None => command_line::network(parameters).map_err(to_string)?,
+    };
+    let default_tasks = command_line::tasks(parameters);
+    let task_sequence = config
+        .as_ref()
+        .map_or(&default_tasks, |config| &config.tasks);
+    let data_directory = config
+        .as_ref()
+        .map_or(parameters.word("output", "data"), |config| {
+            config.data_directory.as_str()
+        });
 
-    let mut calculation = calculation::Calculation::examples(
-        1.0 / inv_dt,
-        &network,
-        seg_length,
-        NodeSetup::Identical,
-        SystemType::Lorenz,
-        &task_sequence,
-        SaveItems::ParametricCurve2d {
-            variable_pairs: vec![[0, 2]],
-        },
-    );
+    // -resume file: continues a run that was started with -checkpoint file (and the same
+    // network and tasks), the curves in the data directory are continued
+    let mut calculation = if let Some(file) = command_line::file(parameters, "output", "resume") {
+        let calculation =
+            Calculation::resume_in_directory(file, data_directory, &network, task_sequence)
+                .map_err(|error| format!("cannot resume: {}", error))?;
+        println!("resuming after {} steps", calculation.total_steps);
+        calculation
+    } else if let Some(config) = &config {
+        config
+            .calculation(&network)
+            .map_err(|error| error.to_string())?
+    } else {
+        let mut calculation = Calculation::examples_in_directory(
+            data_directory,
+            command_line::dt(parameters),
+            &network,
+            command_line::segment_length(parameters).map_err(to_string)?,
+            command_line::node_setup(parameters).map_err(to_string)?,
+            command_line::system_type(parameters).map_err(to_string)?,
+            command_line::coupling_layout(parameters).map_err(to_string)?,
+            task_sequence,
+            command_line::save_items(parameters).map_err(to_string)?,
+        )
+        .map_err(network_errors)?;
+        calculation
+            .system
+            .set_model_parameters(&command_line::model_parameters(parameters))
+            .map_err(|error| format!("invalid model parameters: {}", error))?;
+        calculation
+    };
+    if let Some(file) = command_line::file(parameters, "output", "checkpoint") {
+        let every = parameters.value::<u32>("output", "checkpoint_every") as usize;
+        calculation.checkpoint_every(file, every);
+    }
 
-    let mut timer = Timer::new();
-    calculation.perform_tasks();
+    let timer = Timer::new();
+    let result = calculation.perform_tasks();
     println!(
         "integrated {} steps in {} ms",
         calculation.total_steps,
         timer.get_nanoseconds() as f64 / 1000000.0
     );
+    result.map_err(|error| format!("task failed: {}", error))
+}
+
+// -ensemble n: n runs from perturbed initial conditions, each in <data>/ensemble/member_i
+fn run_ensemble(parameters: &GlobalParameterMap, members: usize) -> Result<(), ArgumentError> {
+    if !parameters.sets("system", "parameter").is_empty() {
+        return Err(ArgumentError::ConflictingOptions(
+            "-ensemble".to_string(),
+            "-param".to_string(),
+        ));
+    }
+    let ensemble = Ensemble {
+        directory: format!("{}/ensemble", parameters.word("output", "data")),
+        dt: command_line::dt(parameters),
+        segment_length: command_line::segment_length(parameters)?,
+        node_setup: command_line::node_setup(parameters)?,
+        system_type: command_line::system_type(parameters)?,
+        coupling_layout: command_line::coupling_layout(parameters)?,
+        save_items: command_line::save_items(parameters)?,
+    };
+    let network = command_line::network(parameters)?;
+    let summary = ensemble.run(members, |i| {
+        let mut tasks = vec![Tasks::PerturbStates {
+            amount: 0.1,
+            seed: i as u64,
+        }];
+        tasks.extend(command_line::tasks(parameters));
+        EnsembleMember {
+            network: network.clone(),
+            tasks,
+        }
+    });
+    print!("{}", summary);
+    if let Err(error) = summary.save() {
+        println!("could not save the ensemble summary: {}", error);
+    }
+    Ok(())
+}
+
+fn network_errors(errors: Vec<NetworkError>) -> String {
+    let mut message = "the network cannot be integrated:".to_string();
+    for error in errors {
+        message += &format!("\n    {}", error);
+    }
+    message
 }
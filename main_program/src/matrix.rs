@@ -0,0 +1,205 @@
+use num_complex::Complex;
+
+// dense complex matrix, `matrix[row][column]`
+pub type ComplexMatrix = Vec<Vec<Complex<f64>>>;
+
+const EPSILON: f64 = f64::EPSILON;
+const MAX_ITERATIONS_PER_EIGENVALUE: usize = 60;
+
+pub fn zeros(n: usize) -> ComplexMatrix {
+    vec![vec![Complex::new(0.0, 0.0); n]; n]
+}
+
+// all eigenvalues of a square matrix.
+// the matrix is reduced to upper hessenberg form, then shifted qr steps (with givens rotations)
+// deflate one eigenvalue after another from the bottom right corner.
+pub fn eigenvalues(mut a: ComplexMatrix) -> Vec<Complex<f64>> {
+    let n = a.len();
+    reduce_to_hessenberg(&mut a);
+
+    let scale = a
+        .iter()
+        .flatten()
+        .map(|x| x.norm())
+        .fold(0.0f64, f64::max)
+        .max(f64::MIN_POSITIVE);
+
+    let mut eigenvalues = Vec::with_capacity(n);
+    let mut hi = n; // the active block is a[lo..hi][lo..hi]
+    let mut iterations = 0;
+    while hi > 0 {
+        let mut lo = hi - 1;
+        while lo > 0 {
+            let neighbourhood = (a[lo - 1][lo - 1].norm() + a[lo][lo].norm()).max(scale * EPSILON);
+            if a[lo][lo - 1].norm() <= EPSILON * neighbourhood {
+                a[lo][lo - 1] = Complex::new(0.0, 0.0);
+                break;
+            }
+            lo -= 1;
+        }
+        if lo == hi - 1 || iterations > MAX_ITERATIONS_PER_EIGENVALUE {
+            // converged (or given up, then the diagonal element is the best estimate)
+            eigenvalues.push(a[hi - 1][hi - 1]);
+            hi -= 1;
+            iterations = 0;
+            continue;
+        }
+        iterations += 1;
+
+        let shift = if iterations % 10 == 0 {
+            // exceptional shift to break up cycles of the shifted qr iteration
+            a[hi - 1][hi - 1] + a[hi - 1][hi - 2].norm() * 1.5
+        } else {
+            wilkinson_shift(
+                a[hi - 2][hi - 2],
+                a[hi - 2][hi - 1],
+                a[hi - 1][hi - 2],
+                a[hi - 1][hi - 1],
+            )
+        };
+        qr_step(&mut a, lo, hi, shift);
+    }
+    eigenvalues
+}
+
+// eigenvalue of [[a, b], [c, d]] closest to `d`
+fn wilkinson_shift(
+    a: Complex<f64>,
+    b: Complex<f64>,
+    c: Complex<f64>,
+    d: Complex<f64>,
+) -> Complex<f64> {
+    let half_difference = (a - d) * 0.5;
+    let root = (half_difference * half_difference + b * c).sqrt();
+    let mean = (a + d) * 0.5;
+    let (first, second) = (mean + root, mean - root);
+    if (first - d).norm() < (second - d).norm() {
+        first
+    } else {
+        second
+    }
+}
+
+// (h - shift) = q r  ->  h = r q + shift, restricted to the active block
+#[allow(clippy::needless_range_loop)]
+fn qr_step(a: &mut ComplexMatrix, lo: usize, hi: usize, shift: Complex<f64>) {
+    for k in lo..hi {
+        a[k][k] -= shift;
+    }
+    let mut rotations = Vec::with_capacity(hi - lo);
+    for k in lo..hi - 1 {
+        let (c, s) = givens(a[k][k], a[k + 1][k]);
+        for j in k..hi {
+            let (x, y) = (a[k][j], a[k + 1][j]);
+            a[k][j] = x * c + s * y;
+            a[k + 1][j] = -s.conj() * x + y * c;
+        }
+        rotations.push((c, s));
+    }
+    for (k, (c, s)) in (lo..hi - 1).zip(rotations) {
+        for row in a.iter_mut().take((k + 2).min(hi)).skip(lo) {
+            let (x, y) = (row[k], row[k + 1]);
+            row[k] = x * c + y * s.conj();
+            row[k + 1] = -x * s + y * c;
+        }
+    }
+    for k in lo..hi {
+        a[k][k] += shift;
+    }
+}
+
+// unitary rotation [[c, s], [-conj(s), c]] that maps (x, y) onto (r, 0)
+fn givens(x: Complex<f64>, y: Complex<f64>) -> (f64, Complex<f64>) {
+    let r = (x.norm_sqr() + y.norm_sqr()).sqrt();
+    if r == 0.0 {
+        (1.0, Complex::new(0.0, 0.0))
+    } else if x.norm() == 0.0 {
+        (0.0, y.conj() / y.norm())
+    } else {
+        (x.norm() / r, x / x.norm() * y.conj() / r)
+    }
+}
+
+// householder reflections, zeroes everything below the first subdiagonal
+#[allow(clippy::needless_range_loop)]
+fn reduce_to_hessenberg(a: &mut ComplexMatrix) {
+    let n = a.len();
+    for k in 0..n.saturating_sub(2) {
+        let mut v: Vec<Complex<f64>> = (k + 1..n).map(|i| a[i][k]).collect();
+        let column_norm = v.iter().map(|x| x.norm_sqr()).sum::<f64>().sqrt();
+        if column_norm == 0.0 {
+            continue;
+        }
+        let phase = if v[0].norm() == 0.0 {
+            Complex::new(1.0, 0.0)
+        } else {
+            v[0] / v[0].norm()
+        };
+        v[0] += phase * column_norm;
+        let v_norm = v.iter().map(|x| x.norm_sqr()).sum::<f64>().sqrt();
+        for x in v.iter_mut() {
+            *x /= v_norm;
+        }
+        // a <- (1 - 2 v v^h) a
+        for j in 0..n {
+            let projection: Complex<f64> =
+                (k + 1..n).zip(&v).map(|(i, x)| x.conj() * a[i][j]).sum();
+            for (i, x) in (k + 1..n).zip(&v) {
+                a[i][j] -= *x * projection * 2.0;
+            }
+        }
+        // a <- a (1 - 2 v v^h)
+        for row in a.iter_mut() {
+            let projection: Complex<f64> = (k + 1..n).zip(&v).map(|(j, x)| row[j] * x).sum();
+            for (j, x) in (k + 1..n).zip(&v) {
+                row[j] -= projection * x.conj() * 2.0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    #[test]
+    fn ring_eigenvalues_are_roots_of_unity() {
+        for n in [1, 2, 3, 5, 8, 50] {
+            let mut a = zeros(n);
+            for i in 0..n {
+                a[(i + 1) % n][i] += Complex::new(0.3, 0.0);
+            }
+            let lambdas = eigenvalues(a);
+            assert_eq!(lambdas.len(), n);
+            for lambda in lambdas {
+                assert!((lambda.norm() - 0.3).abs() < 1e-9, "{} {}", n, lambda);
+                assert!((lambda.powu(n as u32) - 0.3f64.powi(n as i32)).norm() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn eigenvalues_match_traces() {
+        // tr(a) = sum(lambda), tr(a^2) = sum(lambda^2)
+        let mut rng = SmallRng::seed_from_u64(3);
+        let n = 30;
+        let a: ComplexMatrix = (0..n)
+            .map(|_| {
+                (0..n)
+                    .map(|_| Complex::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)))
+                    .collect()
+            })
+            .collect();
+        let trace: Complex<f64> = (0..n).map(|i| a[i][i]).sum();
+        let trace_squared: Complex<f64> = (0..n)
+            .flat_map(|i| (0..n).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j] * a[j][i])
+            .sum();
+        let lambdas = eigenvalues(a);
+        assert!((lambdas.iter().sum::<Complex<f64>>() - trace).norm() < 1e-9);
+        assert!(
+            (lambdas.iter().map(|l| l * l).sum::<Complex<f64>>() - trace_squared).norm() < 1e-8
+        );
+    }
+}
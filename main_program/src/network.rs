@@ -1,9 +1,16 @@
+use crate::dynamical_system::CombinedWeight;
+use crate::edge_selection::EdgeSelection;
 use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 
 // const DOMAIN: &'static str = "network";
 
+// plain group selection, e.g. for schedules and learning rules. network operations take the
+// more general `EdgeSelection` (which every `SelectGroup` converts into).
 #[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum SelectGroup {
     // #[default]
     AllGroups,
@@ -12,9 +19,43 @@ pub enum SelectGroup {
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone)]
+impl SelectGroup {
+    pub fn contains(&self, group: usize) -> bool {
+        match self {
+            SelectGroup::AllGroups => true,
+            SelectGroup::SingleGroup(which) => group == *which,
+            SelectGroup::NotGroup(which) => group != *which,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Default, Debug)]
+pub struct SimplificationReport {
+    pub edges_before: usize,
+    pub edges_after: usize,
+    pub combined: Vec<CombinedEdges>, // one entry per edge that replaced several edges
+    pub removed: usize,               // edges with strength 0 (after combining)
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct CombinedEdges {
+    pub into: usize,
+    pub from: usize,
+    pub delay_steps: usize,
+    pub merged: usize,
+    pub groups: Vec<usize>, // groups of the merged edges, the combined edge is in the first one
+    pub strength: f64,
+    pub turn: f64,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Edge {
     pub group: usize, // assigning edges into groups in order to manipulate them by group
+    #[serde(default)]
+    pub layer: usize, // multiplex networks: which coupling variable of the system is used
     pub into: usize,
     pub from: usize,
     pub strength: f64,
@@ -26,6 +67,7 @@ impl Default for Edge {
     fn default() -> Self {
         Edge {
             group: 0,
+            layer: 0,
             into: 0,
             from: 0,
             strength: 0.05,
@@ -36,13 +78,19 @@ impl Default for Edge {
 }
 
 #[allow(dead_code)]
+#[derive(Clone)]
 pub struct Network {
     pub nodes: usize,
     pub edges: Vec<Edge>,
     pub edge_groups: Vec<String>,
+    pub layers: Vec<String>, // names of the coupling layers, can be empty for a single layer
     pub default_strength: f64,
     pub default_turn: f64,
     pub default_delay: f64,
+    pub positions: Vec<[f64; 3]>, // spatial coordinates of the nodes, empty without geometry
+    // the cell vectors of a periodic lattice, distances are measured to the nearest image
+    pub periodic_cell: Option<[[f64; 3]; 3]>,
+    pub seed: u64,
     pub rng: SmallRng,
     pub dt: f64,
 }
@@ -53,9 +101,13 @@ impl Default for Network {
             nodes: 1,
             edges: Vec::new(),
             edge_groups: Vec::new(),
+            layers: Vec::new(),
             default_strength: 0.05,
             default_turn: 0.0,
             default_delay: 100.0,
+            positions: Vec::new(),
+            periodic_cell: None,
+            seed: 0,
             rng: SmallRng::seed_from_u64(0),
             dt: 1.0 / 64.0,
         }
@@ -77,10 +129,14 @@ impl Network {
             nodes,
             edges: Vec::new(),
             edge_groups: Vec::new(),
+            layers: Vec::new(),
             default_strength,
             default_turn,
             default_delay,
+            positions: Vec::new(),
+            periodic_cell: None,
             //
+            seed,
             rng: SmallRng::seed_from_u64(seed),
             //
             dt,
@@ -329,6 +385,7 @@ impl Network {
     ) -> Edge {
         Edge {
             group,
+            layer: 0,
             into,
             from,
             strength,
@@ -463,139 +520,113 @@ impl Network {
     }
 
     // modify network
-    pub fn turn_angles(&mut self, turn: f64, selection: SelectGroup) {
-        match selection {
-            SelectGroup::SingleGroup(which_group) => {
-                if which_group < self.edge_groups.len() {
-                    for edge in &mut self.edges {
-                        if edge.group == which_group {
-                            edge.turn = ((edge.turn + turn) % 1.0 + 1.0) % 1.0
-                        }
-                    }
-                }
-            }
-            SelectGroup::NotGroup(which_group) => {
-                if which_group < self.edge_groups.len() {
-                    for edge in &mut self.edges {
-                        if edge.group != which_group {
-                            edge.turn = ((edge.turn + turn) % 1.0 + 1.0) % 1.0
-                        }
-                    }
-                }
-            }
-            SelectGroup::AllGroups => {
-                for edge in &mut self.edges {
-                    edge.turn = ((edge.turn + turn) % 1.0 + 1.0) % 1.0
-                }
-            }
-        }
+    pub fn turn_angles(&mut self, turn: f64, selection: impl Into<EdgeSelection>) {
+        self.modify_edges(selection, |edge| {
+            edge.turn = ((edge.turn + turn) % 1.0 + 1.0) % 1.0
+        });
     }
 
-    pub fn randomize_strength(&mut self, amount: f64, selection: SelectGroup) {
-        match selection {
-            SelectGroup::SingleGroup(which) => {
-                if which < self.edges.len() {
-                    for edge in &mut self.edges {
-                        if edge.group == which {
-                            edge.strength += self.rng.gen_range(-1.0..1.0) * amount;
-                            edge.strength = edge.strength.abs();
-                        }
-                    }
-                }
-            }
-            SelectGroup::NotGroup(which) => {
-                if which < self.edges.len() {
-                    for edge in &mut self.edges {
-                        edge.strength += self.rng.gen_range(-1.0..1.0) * amount;
-                        edge.strength = edge.strength.abs();
-                    }
-                }
-            }
-            SelectGroup::AllGroups => {
-                for edge in &mut self.edges {
-                    edge.strength += self.rng.gen_range(-1.0..1.0) * amount;
-                    edge.strength = edge.strength.abs();
-                }
-            }
+    pub fn randomize_strength(&mut self, amount: f64, selection: impl Into<EdgeSelection>) {
+        for i in self.select_edges(selection) {
+            let edge = &mut self.edges[i];
+            edge.strength += self.rng.gen_range(-1.0..1.0) * amount;
+            edge.strength = edge.strength.abs();
         }
     }
 
-    pub fn randomize_angle(&mut self, amount: f64, selection: SelectGroup) {
-        match selection {
-            SelectGroup::SingleGroup(which) => {
-                if which < self.edges.len() {
-                    for edge in &mut self.edges {
-                        if edge.group == which {
-                            edge.turn =
-                                ((edge.turn + self.rng.gen_range(-1.0..1.0) * amount) % 1.0 + 1.0)
-                                    % 1.0;
-                        }
-                    }
-                }
-            }
-            SelectGroup::NotGroup(which) => {
-                if which < self.edges.len() {
-                    for edge in &mut self.edges {
-                        edge.turn = ((edge.turn + self.rng.gen_range(-1.0..1.0) * amount) % 1.0
-                            + 1.0)
-                            % 1.0;
-                    }
-                }
-            }
-            SelectGroup::AllGroups => {
-                for edge in &mut self.edges {
-                    edge.turn =
-                        ((edge.turn + self.rng.gen_range(-1.0..1.0) * amount) % 1.0 + 1.0) % 1.0;
-                }
-            }
+    pub fn randomize_angle(&mut self, amount: f64, selection: impl Into<EdgeSelection>) {
+        for i in self.select_edges(selection) {
+            let edge = &mut self.edges[i];
+            edge.turn = ((edge.turn + self.rng.gen_range(-1.0..1.0) * amount) % 1.0 + 1.0) % 1.0;
         }
     }
 
-    pub fn randomize_delay_relative(&mut self, rel_amount: f64, selection: SelectGroup) {
-        if rel_amount < 1.0 {
-            match selection {
-                SelectGroup::SingleGroup(which) => {
-                    if which < self.edges.len() {
-                        for edge in &mut self.edges {
-                            if edge.group == which {
-                                edge.delay *= 1.0 + self.rng.gen_range(-1.0..1.0) * rel_amount;
-                            }
-                        }
-                    }
-                }
-                SelectGroup::NotGroup(which) => {
-                    if which < self.edges.len() {
-                        for edge in &mut self.edges {
-                            edge.delay *= 1.0 + self.rng.gen_range(-1.0..1.0) * rel_amount;
-                        }
-                    }
-                }
-                SelectGroup::AllGroups => {
-                    for edge in &mut self.edges {
-                        edge.delay *= 1.0 + self.rng.gen_range(-1.0..1.0) * rel_amount;
-                    }
-                }
-            }
-        } else {
-            panic!("amount of relative randomness cannot be larger than 1.0.")
+    // `rel_amount` has to be below 1.0, otherwise delays could become negative
+    pub fn randomize_delay_relative(
+        &mut self,
+        rel_amount: f64,
+        selection: impl Into<EdgeSelection>,
+    ) -> Result<(), String> {
+        if !(0.0..1.0).contains(&rel_amount) {
+            return Err(format!(
+                "amount of relative randomness has to be in [0.0, 1.0), got {}",
+                rel_amount
+            ));
+        }
+        for i in self.select_edges(selection) {
+            self.edges[i].delay *= 1.0 + self.rng.gen_range(-1.0..1.0) * rel_amount;
         }
+        Ok(())
     }
 
-    // convert into different formats ?
     // maintainance
-    // if two edges share the same values for {from, into, delay} complex coupling strengths can be added.
-    pub fn simplify_network(&mut self) {
-        self.combine_edges(); // two (or more) edges with the same values for (from, into, delay) can be combined into one.
-        self.remove_irrelevant_edges(); // edges with strength = 0 shouldn't be computed
+    // edges with the same (into, from, delay step, layer) read the same value from the history,
+    // their weights (`WeightT` of the system: real strengths, or complex `strength * e^(2 pi i
+    // turn)`) can be summed into one edge. the combined edge belongs to the group of the first
+    // edge, the report lists every group that was merged (selections of the others miss it).
+    pub fn simplify_network<WeightT: CombinedWeight>(&mut self) -> SimplificationReport {
+        let mut report = SimplificationReport {
+            edges_before: self.edges.len(),
+            ..Default::default()
+        };
+        self.combine_edges::<WeightT>(&mut report);
+        self.remove_irrelevant_edges(&mut report); // edges with strength = 0 shouldn't be computed
+        report.edges_after = self.edges.len();
+        report
     }
-    fn combine_edges(&mut self) {
-        println!("\"combine_edges()\" - not yet implemented.");
-        // if (into, from, delay) are the same for different edges the (complex) coupling strengths can be summed and only one edge has to remain
-        // maybe only use to convert edges from polar coordinates (strength, angle) to complex values `kappa=(re, im)`
+
+    // the combined edge keeps the delay (and for real weights the turn) of the first edge
+    fn combine_edges<WeightT: CombinedWeight>(&mut self, report: &mut SimplificationReport) {
+        let mut first_of = HashMap::<(usize, usize, usize, usize), usize>::new();
+        let mut merged: Vec<Vec<Edge>> = Vec::new();
+        for e in &self.edges {
+            let key = (e.into, e.from, delay_steps(e.delay, self.dt), e.layer);
+            let i = *first_of.entry(key).or_insert_with(|| {
+                merged.push(Vec::new());
+                merged.len() - 1
+            });
+            merged[i].push(*e);
+        }
+
+        self.edges = merged
+            .into_iter()
+            .map(|es| {
+                let mut combined = es[0];
+                if es.len() == 1 {
+                    return combined;
+                }
+                let sum: WeightT = es.iter().map(WeightT::from_edge).sum();
+                let magnitudes: f64 = es.iter().map(|e| e.strength.abs()).sum();
+                if sum.magnitude() <= 4.0 * f64::EPSILON * magnitudes {
+                    // cancelled out, rounding errors shouldn't leave a tiny edge behind
+                    combined.strength = 0.0;
+                    combined.turn = 0.0;
+                } else {
+                    sum.write_to_edge(&mut combined);
+                }
+                report.combined.push(CombinedEdges {
+                    into: combined.into,
+                    from: combined.from,
+                    delay_steps: delay_steps(combined.delay, self.dt),
+                    merged: es.len(),
+                    groups: es.iter().fold(Vec::new(), |mut groups, e| {
+                        if !groups.contains(&e.group) {
+                            groups.push(e.group);
+                        }
+                        groups
+                    }),
+                    strength: combined.strength,
+                    turn: combined.turn,
+                });
+                combined
+            })
+            .collect();
     }
-    fn remove_irrelevant_edges(&mut self) {
-        // make me private use as in "simplify network"
-        self.edges.retain(|&e| e.strength > 0.0);
+
+    fn remove_irrelevant_edges(&mut self, report: &mut SimplificationReport) {
+        let before = self.edges.len();
+        self.edges.retain(|&e| e.strength != 0.0);
+        report.removed = before - self.edges.len();
     }
 
     pub fn get_nodes(&self) -> usize {
@@ -630,6 +661,18 @@ impl fmt::Display for Network {
             self.edge_groups.len()
         )
         .unwrap();
+        if self.number_of_layers() > 1 {
+            for l in 0..self.number_of_layers() {
+                writeln!(
+                    f,
+                    "layer [{}] \"{}\": {} edges",
+                    l,
+                    self.layer_name(l),
+                    self.edges.iter().filter(|e| e.layer == l).count()
+                )
+                .unwrap();
+            }
+        }
         for (g, groupname) in self.edge_groups.iter().enumerate() {
             writeln!(f, "edge-group [{}] \"{}\":", g, groupname).unwrap();
             for n in 0..self.nodes {
@@ -637,8 +680,8 @@ impl fmt::Display for Network {
                     if edge.group == g && edge.into == n {
                         writeln!(
 							f,
-							"\t{:2} <<< {:2}: kappa = {:.3}, angle = {:.3} * 2Pi, tau = {:.2} (steps: {})",
-							n, edge.from, edge.strength, edge.turn, edge.delay, (edge.delay / self.dt) as usize
+							"\t{:2} <<< {:2}: kappa = {:.3}, angle = {:.3} * 2Pi, tau = {:.2} (steps: {}), layer {}",
+							n, edge.from, edge.strength, edge.turn, edge.delay, delay_steps(edge.delay, self.dt), edge.layer
 						)
                         .unwrap();
                     }
@@ -649,6 +692,114 @@ impl fmt::Display for Network {
     }
 }
 
-fn on_ring(a: isize, b: usize) -> usize {
+impl fmt::Display for SimplificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "simplified network: {} edges -> {} edges ({} combined into {}, {} removed)",
+            self.edges_before,
+            self.edges_after,
+            self.combined.iter().map(|c| c.merged).sum::<usize>(),
+            self.combined.len(),
+            self.removed
+        )?;
+        for c in &self.combined {
+            write!(
+                f,
+                "\t{:2} <<< {:2} (steps: {}): {} edges -> kappa = {:.3}, angle = {:.3} * 2Pi",
+                c.into, c.from, c.delay_steps, c.merged, c.strength, c.turn
+            )?;
+            if c.groups.len() > 1 {
+                write!(
+                    f,
+                    ", edge-groups {:?} merged into [{}]",
+                    c.groups, c.groups[0]
+                )?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// the number of steps of length `dt` a delay reaches back. rounded: a delay computed from a
+// distance can be a few ulps short of a whole number of steps, truncating would read it a step
+// too early. every backend and the validation count steps with this
+pub fn delay_steps(delay: f64, dt: f64) -> usize {
+    (delay / dt).round() as usize
+}
+
+pub fn on_ring(a: isize, b: usize) -> usize {
     (((a % b as isize) + b as isize) % b as isize) as usize
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamical_system::{WeightComplex, WeightReal};
+
+    #[test]
+    fn combined_edges_respect_turns() {
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_edge(0, 1, 1.0, 0.0, 1.0);
+        network.put_edge(0, 1, 1.0, 0.25, 1.0 + 0.1 / 64.0); // same delay step
+        network.put_edge(0, 1, 1.0, 0.0, 2.0); // different delay step
+        network.put_edge(1, 0, -0.5, 0.0, 1.0);
+        network.put_edge(1, 0, -0.25, 0.0, 1.0);
+        let report = network.simplify_network::<WeightComplex>();
+
+        assert_eq!((report.edges_before, report.edges_after), (5, 3));
+        assert_eq!(report.combined.len(), 2);
+        let quarter = network.edges[0];
+        assert!((quarter.strength - 2.0f64.sqrt()).abs() < 1e-12);
+        assert!((quarter.turn - 0.125).abs() < 1e-12);
+        // real sums keep their sign
+        assert!(network
+            .edges
+            .iter()
+            .any(|e| e.into == 1 && e.strength == -0.75));
+    }
+
+    #[test]
+    fn cancelling_edges_are_removed() {
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_edge(0, 1, 0.3, 0.1, 1.0);
+        network.put_edge(0, 1, 0.3, 0.6, 1.0);
+        network.put_edge(1, 0, -0.2, 0.0, 1.0);
+        let report = network.simplify_network::<WeightComplex>();
+        assert_eq!(report.removed, 1);
+        assert_eq!(network.edges.len(), 1);
+        assert_eq!(network.edges[0].strength, -0.2);
+    }
+
+    #[test]
+    fn real_weights_ignore_turns() {
+        // the same edges as above: for Mackey-Glass, Lorenz, ... the turns don't matter
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_edge(0, 1, 0.3, 0.1, 1.0);
+        network.put_edge(0, 1, 0.3, 0.6, 1.0);
+        network.put_edge(1, 0, 0.2, 0.0, 1.0);
+        network.put_edge(1, 0, -0.2, 0.5, 1.0);
+        let report = network.simplify_network::<WeightReal>();
+        assert_eq!(report.removed, 1);
+        assert_eq!(network.edges.len(), 1);
+        assert!((network.edges[0].strength - 0.6).abs() < 1e-15);
+        assert_eq!(network.edges[0].turn, 0.1);
+    }
+
+    #[test]
+    fn merged_groups_are_reported() {
+        let mut network = Network::new(3, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_ring(0.1, 0.0, 1.0);
+        network.put_edge(1, 0, 0.2, 0.0, 1.0);
+        let report = network.simplify_network::<WeightReal>();
+        assert_eq!(report.combined.len(), 1);
+        assert_eq!(report.combined[0].groups, vec![0, 1]);
+        let combined = network.edges.iter().find(|e| e.into == 1).unwrap();
+        assert_eq!(combined.group, 0);
+        assert!((combined.strength - 0.3).abs() < 1e-15);
+        assert!(report
+            .to_string()
+            .contains("edge-groups [0, 1] merged into [0]"));
+    }
+}
@@ -0,0 +1,287 @@
+use crate::dynamical_system::WeightFromEdge;
+use crate::matrix::{self, ComplexMatrix};
+use crate::network::{delay_steps, Network};
+use num_complex::Complex;
+use std::fmt;
+
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct Cycle {
+    pub nodes: Vec<usize>, // starts at the smallest node, follows the signal: nodes[0] -> nodes[1] -> ..
+    pub edges: Vec<usize>, // index into `Network::edges`, parallel edges give different cycles
+    pub loop_delay: f64,   // sum of the delays along the cycle
+}
+
+// structural properties of the coupling graph. edges point from `from` to `into`.
+#[allow(dead_code)]
+impl Network {
+    pub fn in_degrees(&self) -> Vec<usize> {
+        let mut degrees = vec![0; self.nodes];
+        for e in &self.edges {
+            degrees[e.into] += 1;
+        }
+        degrees
+    }
+
+    pub fn out_degrees(&self) -> Vec<usize> {
+        let mut degrees = vec![0; self.nodes];
+        for e in &self.edges {
+            degrees[e.from] += 1;
+        }
+        degrees
+    }
+
+    // `distribution[k]` is the number of nodes with k incoming edges
+    pub fn in_degree_distribution(&self) -> Vec<usize> {
+        degree_distribution(&self.in_degrees())
+    }
+
+    pub fn out_degree_distribution(&self) -> Vec<usize> {
+        degree_distribution(&self.out_degrees())
+    }
+
+    // tarjan's algorithm (without recursion, so large networks don't overflow the stack).
+    // components are sorted internally, in reverse topological order of the condensed graph.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        let successors = self.successors();
+        let mut index = vec![usize::MAX; self.nodes];
+        let mut low_link = vec![0; self.nodes];
+        let mut on_stack = vec![false; self.nodes];
+        let mut stack = Vec::new();
+        let mut components = Vec::new();
+        let mut next_index = 0;
+
+        for root in 0..self.nodes {
+            if index[root] != usize::MAX {
+                continue;
+            }
+            // (node, next successor to look at)
+            let mut call_stack = vec![(root, 0)];
+            index[root] = next_index;
+            low_link[root] = next_index;
+            next_index += 1;
+            stack.push(root);
+            on_stack[root] = true;
+
+            while let Some(&mut (node, ref mut next)) = call_stack.last_mut() {
+                if let Some(&successor) = successors[node].get(*next) {
+                    *next += 1;
+                    if index[successor] == usize::MAX {
+                        index[successor] = next_index;
+                        low_link[successor] = next_index;
+                        next_index += 1;
+                        stack.push(successor);
+                        on_stack[successor] = true;
+                        call_stack.push((successor, 0));
+                    } else if on_stack[successor] {
+                        low_link[node] = low_link[node].min(index[successor]);
+                    }
+                    continue;
+                }
+                call_stack.pop();
+                if let Some(&(parent, _)) = call_stack.last() {
+                    low_link[parent] = low_link[parent].min(low_link[node]);
+                }
+                if low_link[node] == index[node] {
+                    let mut component = Vec::new();
+                    while let Some(member) = stack.pop() {
+                        on_stack[member] = false;
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    component.sort_unstable();
+                    components.push(component);
+                }
+            }
+        }
+        components
+    }
+
+    pub fn is_strongly_connected(&self) -> bool {
+        self.strongly_connected_components().len() == 1
+    }
+
+    // all elementary cycles with at most `max_length` edges (the number of cycles grows
+    // exponentially with the length in dense networks). self loops are cycles of length 1.
+    pub fn cycles(&self, max_length: usize) -> Vec<Cycle> {
+        let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); self.nodes];
+        for (i, e) in self.edges.iter().enumerate() {
+            outgoing[e.from].push(i);
+        }
+        let mut cycles = Vec::new();
+        let mut on_path = vec![false; self.nodes];
+        for start in 0..self.nodes {
+            // each cycle is found from its smallest node only
+            let mut path_nodes = vec![start];
+            let mut path_edges = Vec::new();
+            on_path[start] = true;
+            self.extend_cycles(
+                start,
+                max_length,
+                &outgoing,
+                &mut on_path,
+                &mut path_nodes,
+                &mut path_edges,
+                &mut cycles,
+            );
+            on_path[start] = false;
+        }
+        cycles
+    }
+
+    // distinct loop delays (rounded to whole steps of `dt`) of all cycles up to `max_length`
+    pub fn loop_delays(&self, max_length: usize) -> Vec<f64> {
+        let mut steps: Vec<usize> = self
+            .cycles(max_length)
+            .iter()
+            .map(|c| delay_steps(c.loop_delay, self.dt))
+            .collect();
+        steps.sort_unstable();
+        steps.dedup();
+        steps.iter().map(|&s| s as f64 * self.dt).collect()
+    }
+
+    // l = d - a with the weighted in-degree d_ii = sum_j a_ij, so each row sums up to 0.
+    // the eigenvalues of l are the input for a master stability function analysis.
+    pub fn laplacian_matrix<WeightT>(&self) -> ComplexMatrix
+    where
+        WeightT: WeightFromEdge + Into<Complex<f64>>,
+    {
+        let mut laplacian = self.weighted_adjacency_matrix::<WeightT>();
+        for (i, row) in laplacian.iter_mut().enumerate() {
+            let degree: Complex<f64> = row.iter().sum();
+            for a in row.iter_mut() {
+                *a = -*a;
+            }
+            row[i] += degree;
+        }
+        laplacian
+    }
+
+    pub fn laplacian_eigenvalues<WeightT>(&self) -> Vec<Complex<f64>>
+    where
+        WeightT: WeightFromEdge + Into<Complex<f64>>,
+    {
+        matrix::eigenvalues(self.laplacian_matrix::<WeightT>())
+    }
+
+    fn successors(&self) -> Vec<Vec<usize>> {
+        let mut successors = vec![Vec::new(); self.nodes];
+        for e in &self.edges {
+            successors[e.from].push(e.into);
+        }
+        for s in successors.iter_mut() {
+            s.sort_unstable();
+            s.dedup();
+        }
+        successors
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn extend_cycles(
+        &self,
+        start: usize,
+        max_length: usize,
+        outgoing: &[Vec<usize>],
+        on_path: &mut [bool],
+        path_nodes: &mut Vec<usize>,
+        path_edges: &mut Vec<usize>,
+        cycles: &mut Vec<Cycle>,
+    ) {
+        let node = *path_nodes.last().unwrap();
+        for &edge in &outgoing[node] {
+            let next = self.edges[edge].into;
+            if next == start {
+                path_edges.push(edge);
+                cycles.push(Cycle {
+                    nodes: path_nodes.clone(),
+                    edges: path_edges.clone(),
+                    loop_delay: path_edges.iter().map(|&e| self.edges[e].delay).sum(),
+                });
+                path_edges.pop();
+            } else if next > start && !on_path[next] && path_edges.len() + 1 < max_length {
+                on_path[next] = true;
+                path_nodes.push(next);
+                path_edges.push(edge);
+                self.extend_cycles(
+                    start, max_length, outgoing, on_path, path_nodes, path_edges, cycles,
+                );
+                path_edges.pop();
+                path_nodes.pop();
+                on_path[next] = false;
+            }
+        }
+    }
+}
+
+fn degree_distribution(degrees: &[usize]) -> Vec<usize> {
+    let mut distribution = vec![0; degrees.iter().max().map_or(0, |&d| d + 1)];
+    for &d in degrees {
+        distribution[d] += 1;
+    }
+    distribution
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for n in &self.nodes {
+            write!(f, "{} -> ", n)?;
+        }
+        write!(f, "{} (loop delay: {})", self.nodes[0], self.loop_delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamical_system::WeightReal;
+
+    #[test]
+    fn components_and_degrees() {
+        // ring 0..3, chain 3 -> 4 -> 5, self loop on 5
+        let mut network = Network::new(6, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_chain(0, 2, 0.1, 0.0, 1.0);
+        network.put_edge(0, 2, 0.1, 0.0, 1.0);
+        network.put_edge(3, 2, 0.1, 0.0, 1.0);
+        network.put_chain(3, 5, 0.1, 0.0, 1.0);
+        network.put_edge(5, 5, 0.1, 0.0, 1.0);
+
+        assert_eq!(network.in_degrees(), vec![1, 1, 1, 1, 1, 2]);
+        assert_eq!(network.out_degree_distribution(), vec![0, 5, 1]);
+        let mut components = network.strongly_connected_components();
+        components.sort();
+        assert_eq!(components, vec![vec![0, 1, 2], vec![3], vec![4], vec![5]]);
+        assert!(!network.is_strongly_connected());
+    }
+
+    #[test]
+    fn cycles_sum_up_delays() {
+        let mut network = Network::new(3, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_ring(0.1, 0.0, 1.0);
+        network.put_edge(0, 1, 0.1, 0.0, 2.0); // back and forth between 0 and 1
+        network.put_edge(2, 2, 0.1, 0.0, 0.5);
+
+        let cycles = network.cycles(10);
+        assert_eq!(cycles.len(), 3);
+        assert_eq!(network.loop_delays(10), vec![0.5, 3.0]);
+        let short_cycles = network.cycles(2);
+        assert_eq!(short_cycles.len(), 2);
+        assert!(short_cycles.iter().any(|c| c.nodes == [0, 1]));
+    }
+
+    #[test]
+    fn laplacian_rows_sum_to_zero() {
+        let mut network = Network::new(5, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_ring(0.3, 0.0, 1.0);
+        network.put_edge(2, 0, 0.2, 0.0, 1.0);
+        let laplacian = network.laplacian_matrix::<WeightReal>();
+        for row in &laplacian {
+            assert!(row.iter().sum::<Complex<f64>>().norm() < 1e-12);
+        }
+        let eigenvalues = network.laplacian_eigenvalues::<WeightReal>();
+        assert!(eigenvalues.iter().any(|l| l.norm() < 1e-9));
+        assert!(eigenvalues.iter().all(|l| l.re > -1e-9));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::network::Network;
+use crate::network_io::NetworkFileError;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+// colours of the edge groups, repeated if there are more groups
+const GROUP_COLOURS: [&str; 10] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+    "#bcbd22", "#17becf",
+];
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub enum MatrixImage {
+    Strength, // sum of |strength| of all edges between two nodes
+    Delay,    // shortest delay between two nodes
+}
+
+// `matrix[into][from]`, zero where there is no edge. drawn with the same orientation:
+// row = receiving node, column = sending node.
+#[allow(dead_code)]
+impl Network {
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph network {\n");
+        writeln!(dot, "    node [shape=circle];").unwrap();
+        for n in 0..self.nodes {
+            match self.positions.get(n) {
+                // neato and fdp use fixed positions ("!"), dot ignores them
+                Some(p) => writeln!(dot, "    {} [pos=\"{},{}!\"];", n, p[0], p[1]).unwrap(),
+                None => writeln!(dot, "    {};", n).unwrap(),
+            }
+        }
+        for e in &self.edges {
+            writeln!(
+                dot,
+                "    {} -> {} [color=\"{}\", label=\"κ={:.3} φ={:.3} τ={}\"];",
+                e.from,
+                e.into,
+                GROUP_COLOURS[e.group % GROUP_COLOURS.len()],
+                e.strength,
+                e.turn,
+                e.delay
+            )
+            .unwrap();
+        }
+        // legend
+        writeln!(dot, "    subgraph cluster_groups {{").unwrap();
+        writeln!(dot, "        label=\"edge groups\";").unwrap();
+        for (g, name) in self.edge_groups.iter().enumerate() {
+            writeln!(
+                dot,
+                "        group_{} [shape=box, color=\"{}\", label=\"[{}] {}\"];",
+                g,
+                GROUP_COLOURS[g % GROUP_COLOURS.len()],
+                g,
+                name.replace('"', "\\\"")
+            )
+            .unwrap();
+        }
+        writeln!(dot, "    }}").unwrap();
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn save_dot(&self, path: impl AsRef<Path>) -> Result<(), NetworkFileError> {
+        fs::write(path, self.to_dot())?;
+        Ok(())
+    }
+
+    pub fn matrix_values(&self, image: MatrixImage) -> Vec<Vec<f64>> {
+        let mut values = vec![vec![0.0; self.nodes]; self.nodes];
+        for e in &self.edges {
+            let value = &mut values[e.into][e.from];
+            match image {
+                MatrixImage::Strength => *value += e.strength.abs(),
+                MatrixImage::Delay if *value == 0.0 => *value = e.delay,
+                MatrixImage::Delay => *value = value.min(e.delay),
+            }
+        }
+        values
+    }
+
+    // binary greyscale image (black = no edge / 0, white = largest value), each matrix entry
+    // becomes a square of `pixels_per_node` pixels
+    pub fn save_matrix_pgm(
+        &self,
+        image: MatrixImage,
+        pixels_per_node: usize,
+        path: impl AsRef<Path>,
+    ) -> Result<(), NetworkFileError> {
+        let size = self.nodes * pixels_per_node;
+        let mut pgm = format!("P5\n{} {}\n255\n", size, size).into_bytes();
+        pgm.extend(self.matrix_pixels(image, pixels_per_node));
+        fs::write(path, pgm)?;
+        Ok(())
+    }
+
+    pub fn save_matrix_png(
+        &self,
+        image: MatrixImage,
+        pixels_per_node: usize,
+        path: impl AsRef<Path>,
+    ) -> Result<(), NetworkFileError> {
+        let size = (self.nodes * pixels_per_node) as u32;
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), size, size);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let rgb: Vec<u8> = self
+            .matrix_pixels(image, pixels_per_node)
+            .iter()
+            .flat_map(|&grey| heat_colour(grey))
+            .collect();
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&rgb))
+            .map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    fn matrix_pixels(&self, image: MatrixImage, pixels_per_node: usize) -> Vec<u8> {
+        let values = self.matrix_values(image);
+        let largest = values.iter().flatten().fold(0.0f64, |m, &v| m.max(v));
+        let scale = if largest > 0.0 { 255.0 / largest } else { 0.0 };
+        values
+            .iter()
+            .flat_map(|row| {
+                let pixel_row: Vec<u8> = row
+                    .iter()
+                    .flat_map(|&v| std::iter::repeat_n((v * scale).round() as u8, pixels_per_node))
+                    .collect();
+                std::iter::repeat_n(pixel_row, pixels_per_node).flatten()
+            })
+            .collect()
+    }
+}
+
+// black -> red -> yellow -> white
+fn heat_colour(value: u8) -> [u8; 3] {
+    let x = value as f64 / 255.0 * 3.0;
+    [
+        (x.min(1.0) * 255.0) as u8,
+        ((x - 1.0).clamp(0.0, 1.0) * 255.0) as u8,
+        ((x - 2.0).clamp(0.0, 1.0) * 255.0) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dot_and_matrix_images() {
+        let mut network = Network::new(4, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_ring(0.5, 0.0, 2.0);
+        network.put_jumps(0, 2, 2, 0.25, 0.5, 1.0);
+
+        let dot = network.to_dot();
+        assert!(dot.starts_with("digraph network {"));
+        assert_eq!(dot.matches(" -> ").count(), network.edges.len());
+        assert!(dot.contains(&format!("color=\"{}\"", GROUP_COLOURS[1])));
+
+        let delays = network.matrix_values(MatrixImage::Delay);
+        assert_eq!(delays[1][0], 2.0);
+        assert_eq!(delays[0][2], 1.0);
+        assert_eq!(delays[0][0], 0.0);
+
+        let path = std::env::temp_dir().join("network_export_test.pgm");
+        network
+            .save_matrix_pgm(MatrixImage::Strength, 3, &path)
+            .unwrap();
+        let pgm = fs::read(&path).unwrap();
+        let header = b"P5\n12 12\n255\n";
+        assert_eq!(&pgm[..header.len()], header);
+        assert_eq!(pgm.len(), header.len() + 12 * 12);
+        // ring edge 0 -> 1 is the strongest: row 1, column 0
+        assert_eq!(pgm[header.len() + 3 * 12], 255);
+
+        let path = std::env::temp_dir().join("network_export_test.png");
+        network
+            .save_matrix_png(MatrixImage::Delay, 3, &path)
+            .unwrap();
+        assert_eq!(&fs::read(&path).unwrap()[1..4], b"PNG");
+    }
+}
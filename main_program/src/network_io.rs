@@ -0,0 +1,528 @@
+use crate::network::{Edge, Network};
+use rand::{rngs::SmallRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// everything needed to rebuild a `Network`. the rng itself is not stored, only its seed.
+#[derive(Serialize, Deserialize)]
+struct NetworkFile {
+    nodes: usize,
+    edges: Vec<Edge>,
+    edge_groups: Vec<String>,
+    #[serde(default)]
+    layers: Vec<String>,
+    default_strength: f64,
+    default_turn: f64,
+    default_delay: f64,
+    #[serde(default)]
+    positions: Vec<[f64; 3]>,
+    #[serde(default)]
+    periodic_cell: Option<[[f64; 3]; 3]>,
+    seed: u64,
+    dt: f64,
+}
+
+impl From<&Network> for NetworkFile {
+    fn from(network: &Network) -> Self {
+        NetworkFile {
+            nodes: network.nodes,
+            edges: network.edges.clone(),
+            edge_groups: network.edge_groups.clone(),
+            layers: network.layers.clone(),
+            default_strength: network.default_strength,
+            default_turn: network.default_turn,
+            default_delay: network.default_delay,
+            positions: network.positions.clone(),
+            periodic_cell: network.periodic_cell,
+            seed: network.seed,
+            dt: network.dt,
+        }
+    }
+}
+
+impl From<NetworkFile> for Network {
+    fn from(file: NetworkFile) -> Self {
+        Network {
+            nodes: file.nodes,
+            edges: file.edges,
+            edge_groups: file.edge_groups,
+            layers: file.layers,
+            default_strength: file.default_strength,
+            default_turn: file.default_turn,
+            default_delay: file.default_delay,
+            positions: file.positions,
+            periodic_cell: file.periodic_cell,
+            seed: file.seed,
+            rng: SmallRng::seed_from_u64(file.seed),
+            dt: file.dt,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NetworkFileError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    EdgeList { line: usize, message: String },
+    GraphMl(String),
+}
+
+impl fmt::Display for NetworkFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkFileError::Io(error) => write!(f, "could not access network file: {}", error),
+            NetworkFileError::Json(error) => write!(f, "invalid network json: {}", error),
+            NetworkFileError::EdgeList { line, message } => {
+                write!(f, "invalid edge list in line {}: {}", line, message)
+            }
+            NetworkFileError::GraphMl(message) => write!(f, "invalid graphml: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for NetworkFileError {}
+
+impl From<io::Error> for NetworkFileError {
+    fn from(error: io::Error) -> Self {
+        NetworkFileError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for NetworkFileError {
+    fn from(error: serde_json::Error) -> Self {
+        NetworkFileError::Json(error)
+    }
+}
+
+const EDGE_LIST_HEADER: &str = "group,into,from,strength,turn,delay,layer";
+const EDGE_LIST_HEADER_SINGLE_LAYER: &str = "group,into,from,strength,turn,delay"; // `layer` = 0
+
+#[allow(dead_code)]
+impl Network {
+    // json
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&NetworkFile::from(self)).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, NetworkFileError> {
+        let file: NetworkFile = serde_json::from_str(json)?;
+        Ok(file.into())
+    }
+
+    pub fn save_json(&self, path: impl AsRef<Path>) -> Result<(), NetworkFileError> {
+        fs::write(path, self.to_json())?;
+        Ok(())
+    }
+
+    pub fn load_json(path: impl AsRef<Path>) -> Result<Self, NetworkFileError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    // edge list
+    // network-wide values and group names go into `#`-comments above the csv table:
+    //   # nodes: 4
+    //   # group 0: ring --> (clockwise)
+    //   # layer 0: electrical
+    //   # position 0: 0 0 0
+    //   group,into,from,strength,turn,delay,layer
+    //   0,1,0,0.1,0,100,0
+    // the `layer` column is optional when reading.
+    pub fn to_edge_list(&self) -> String {
+        let mut csv = String::new();
+        csv.push_str(&format!("# nodes: {}\n", self.nodes));
+        csv.push_str(&format!("# default_strength: {}\n", self.default_strength));
+        csv.push_str(&format!("# default_turn: {}\n", self.default_turn));
+        csv.push_str(&format!("# default_delay: {}\n", self.default_delay));
+        csv.push_str(&format!("# seed: {}\n", self.seed));
+        csv.push_str(&format!("# dt: {}\n", self.dt));
+        for (g, group_name) in self.edge_groups.iter().enumerate() {
+            csv.push_str(&format!("# group {}: {}\n", g, group_name));
+        }
+        for (l, layer_name) in self.layers.iter().enumerate() {
+            csv.push_str(&format!("# layer {}: {}\n", l, layer_name));
+        }
+        for (n, [x, y, z]) in self.positions.iter().enumerate() {
+            csv.push_str(&format!("# position {}: {} {} {}\n", n, x, y, z));
+        }
+        csv.push_str(EDGE_LIST_HEADER);
+        csv.push('\n');
+        for e in &self.edges {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                e.group, e.into, e.from, e.strength, e.turn, e.delay, e.layer
+            ));
+        }
+        csv
+    }
+
+    // missing `#`-values fall back to `Network::default()`, missing node count and
+    // group names are derived from the edges.
+    pub fn from_edge_list(csv: &str) -> Result<Self, NetworkFileError> {
+        let mut network = Network::default();
+        let mut nodes: Option<usize> = None;
+        let mut group_names = HashMap::<usize, String>::new();
+        let mut layer_names = HashMap::<usize, String>::new();
+
+        for (i, raw_line) in csv.lines().enumerate() {
+            let line_number = i + 1;
+            let line = raw_line.trim();
+            if line.is_empty() || line == EDGE_LIST_HEADER || line == EDGE_LIST_HEADER_SINGLE_LAYER
+            {
+                continue;
+            }
+            let parse_error = |message: String| NetworkFileError::EdgeList {
+                line: line_number,
+                message,
+            };
+
+            if let Some(comment) = line.strip_prefix('#') {
+                let Some((key, value)) = comment.split_once(':') else {
+                    continue;
+                };
+                let (key, value) = (key.trim(), value.trim());
+                let parse_f64 = |v: &str| {
+                    v.parse::<f64>()
+                        .map_err(|_| parse_error(format!("`{}` is not a number", v)))
+                };
+                match key {
+                    "nodes" => {
+                        nodes =
+                            Some(value.parse::<usize>().map_err(|_| {
+                                parse_error(format!("`{}` is not a node count", value))
+                            })?)
+                    }
+                    "default_strength" => network.default_strength = parse_f64(value)?,
+                    "default_turn" => network.default_turn = parse_f64(value)?,
+                    "default_delay" => network.default_delay = parse_f64(value)?,
+                    "dt" => network.dt = parse_f64(value)?,
+                    "seed" => {
+                        network.seed = value
+                            .parse::<u64>()
+                            .map_err(|_| parse_error(format!("`{}` is not a seed", value)))?
+                    }
+                    _ => {
+                        if let Some(group) = key.strip_prefix("group ") {
+                            let group = group.trim().parse::<usize>().map_err(|_| {
+                                parse_error(format!("`{}` is not a group index", group))
+                            })?;
+                            group_names.insert(group, value.to_string());
+                        } else if let Some(layer) = key.strip_prefix("layer ") {
+                            let layer = layer.trim().parse::<usize>().map_err(|_| {
+                                parse_error(format!("`{}` is not a layer index", layer))
+                            })?;
+                            layer_names.insert(layer, value.to_string());
+                        } else if let Some(node) = key.strip_prefix("position ") {
+                            let node = node.trim().parse::<usize>().map_err(|_| {
+                                parse_error(format!("`{}` is not a node index", node))
+                            })?;
+                            let coordinates = value
+                                .split_whitespace()
+                                .map(parse_f64)
+                                .collect::<Result<Vec<f64>, _>>()?;
+                            if coordinates.len() != 3 {
+                                return Err(parse_error(format!(
+                                    "expected 3 coordinates, found {}",
+                                    coordinates.len()
+                                )));
+                            }
+                            if network.positions.len() <= node {
+                                network.positions.resize(node + 1, [0.0; 3]);
+                            }
+                            network.positions[node] =
+                                [coordinates[0], coordinates[1], coordinates[2]];
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+            if fields.len() != 6 && fields.len() != 7 {
+                return Err(parse_error(format!(
+                    "expected 6 or 7 fields `{}`, found {}",
+                    EDGE_LIST_HEADER,
+                    fields.len()
+                )));
+            }
+            let parse_usize = |v: &str| {
+                v.parse::<usize>()
+                    .map_err(|_| parse_error(format!("`{}` is not an index", v)))
+            };
+            let parse_f64 = |v: &str| {
+                v.parse::<f64>()
+                    .map_err(|_| parse_error(format!("`{}` is not a number", v)))
+            };
+            network.edges.push(Edge {
+                group: parse_usize(fields[0])?,
+                into: parse_usize(fields[1])?,
+                from: parse_usize(fields[2])?,
+                strength: parse_f64(fields[3])?,
+                turn: parse_f64(fields[4])?,
+                delay: parse_f64(fields[5])?,
+                layer: fields.get(6).map_or(Ok(0), |l| parse_usize(l))?,
+            });
+        }
+
+        network.nodes = nodes.unwrap_or_else(|| {
+            network
+                .edges
+                .iter()
+                .map(|e| e.into.max(e.from) + 1)
+                .max()
+                .unwrap_or(1)
+        });
+        let groups = network
+            .edges
+            .iter()
+            .map(|e| e.group + 1)
+            .chain(group_names.keys().map(|g| g + 1))
+            .max()
+            .unwrap_or(0);
+        network.edge_groups = (0..groups)
+            .map(|g| {
+                group_names
+                    .remove(&g)
+                    .unwrap_or_else(|| format!("edge list group {}", g))
+            })
+            .collect();
+        if let Some(layers) = layer_names.keys().max() {
+            network.layers = (0..=*layers)
+                .map(|l| {
+                    layer_names
+                        .remove(&l)
+                        .unwrap_or_else(|| format!("edge list layer {}", l))
+                })
+                .collect();
+        }
+        network.rng = SmallRng::seed_from_u64(network.seed);
+        Ok(network)
+    }
+
+    pub fn save_edge_list(&self, path: impl AsRef<Path>) -> Result<(), NetworkFileError> {
+        fs::write(path, self.to_edge_list())?;
+        Ok(())
+    }
+
+    pub fn load_edge_list(path: impl AsRef<Path>) -> Result<Self, NetworkFileError> {
+        Self::from_edge_list(&fs::read_to_string(path)?)
+    }
+
+    // graphml (as written by networkx or gephi)
+    // edge attributes named `strength` (or `weight`), `turn` and `delay` are used when present,
+    // otherwise the network's defaults are taken. all edges end up in a single edge group.
+    // undirected graphs get one edge in each direction.
+    pub fn from_graphml(
+        xml: &str,
+        default_strength: f64,
+        default_turn: f64,
+        default_delay: f64,
+        seed: u64,
+        dt: f64,
+    ) -> Result<Self, NetworkFileError> {
+        let document = roxmltree::Document::parse(xml)
+            .map_err(|e| NetworkFileError::GraphMl(e.to_string()))?;
+
+        // <key id="d0" for="edge" attr.name="weight" attr.type="double"/>
+        let mut edge_keys = HashMap::<&str, &str>::new();
+        for key in document.descendants().filter(|n| n.has_tag_name("key")) {
+            if matches!(key.attribute("for"), Some("edge") | Some("all")) {
+                if let (Some(id), Some(name)) = (key.attribute("id"), key.attribute("attr.name")) {
+                    edge_keys.insert(id, name);
+                }
+            }
+        }
+
+        let graph = document
+            .descendants()
+            .find(|n| n.has_tag_name("graph"))
+            .ok_or_else(|| NetworkFileError::GraphMl("no <graph> element".to_string()))?;
+        let undirected = graph.attribute("edgedefault") == Some("undirected");
+
+        let mut node_indices = HashMap::<&str, usize>::new();
+        for node in graph.children().filter(|n| n.has_tag_name("node")) {
+            let id = node
+                .attribute("id")
+                .ok_or_else(|| NetworkFileError::GraphMl("<node> without id".to_string()))?;
+            let index = node_indices.len();
+            node_indices.entry(id).or_insert(index);
+        }
+
+        let mut network = Network::new(
+            node_indices.len().max(1),
+            default_strength,
+            default_turn,
+            default_delay,
+            seed,
+            dt,
+        );
+
+        for edge in graph.children().filter(|n| n.has_tag_name("edge")) {
+            let endpoint = |attribute: &str| -> Result<usize, NetworkFileError> {
+                let id = edge.attribute(attribute).ok_or_else(|| {
+                    NetworkFileError::GraphMl(format!("<edge> without {}", attribute))
+                })?;
+                node_indices.get(id).copied().ok_or_else(|| {
+                    NetworkFileError::GraphMl(format!("<edge> refers to unknown node `{}`", id))
+                })
+            };
+            let from = endpoint("source")?;
+            let into = endpoint("target")?;
+
+            let mut strength = default_strength;
+            let mut turn = default_turn;
+            let mut delay = default_delay;
+            for data in edge.children().filter(|n| n.has_tag_name("data")) {
+                let Some(name) = data.attribute("key").and_then(|k| edge_keys.get(k)) else {
+                    continue;
+                };
+                let text = data.text().unwrap_or("").trim();
+                let value = || {
+                    text.parse::<f64>().map_err(|_| {
+                        NetworkFileError::GraphMl(format!("`{}` is not a number", text))
+                    })
+                };
+                match *name {
+                    "strength" | "weight" => strength = value()?,
+                    "turn" => turn = value()?,
+                    "delay" => delay = value()?,
+                    _ => {}
+                }
+            }
+
+            let directed = match edge.attribute("directed") {
+                Some(directed) => directed == "true",
+                None => !undirected,
+            };
+            network.edges.push(Edge {
+                group: 0,
+                layer: 0,
+                into,
+                from,
+                strength,
+                turn,
+                delay,
+            });
+            if !directed && into != from {
+                network.edges.push(Edge {
+                    group: 0,
+                    layer: 0,
+                    into: from,
+                    from: into,
+                    strength,
+                    turn,
+                    delay,
+                });
+            }
+        }
+        network.edge_groups.push("graphml import".to_string());
+        Ok(network)
+    }
+
+    pub fn load_graphml(
+        path: impl AsRef<Path>,
+        default_strength: f64,
+        default_turn: f64,
+        default_delay: f64,
+        seed: u64,
+        dt: f64,
+    ) -> Result<Self, NetworkFileError> {
+        Self::from_graphml(
+            &fs::read_to_string(path)?,
+            default_strength,
+            default_turn,
+            default_delay,
+            seed,
+            dt,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::SelectGroup;
+
+    fn example_network() -> Network {
+        let mut network = Network::new(5, 0.1, 0.25, 10.0, 42, 1.0 / 128.0);
+        network.put_ring(0.125, 0.3, 7.5);
+        network.put_edge(0, 3, 0.01, 0.0, 1.0 / 3.0);
+        network.put_jumps(1, 2, 2, 0.2, 0.75, 12.0);
+        network.positions = (0..5).map(|n| [n as f64 * 0.5, 1.0 / 3.0, -2.0]).collect();
+        let chemical = network.add_layer("chemical");
+        network.move_to_layer(SelectGroup::SingleGroup(1), chemical);
+        network
+    }
+
+    fn assert_same_network(a: &Network, b: &Network) {
+        assert_eq!(a.nodes, b.nodes);
+        assert_eq!(a.edges, b.edges);
+        assert_eq!(a.edge_groups, b.edge_groups);
+        assert_eq!(a.layers, b.layers);
+        assert_eq!(a.default_strength, b.default_strength);
+        assert_eq!(a.default_turn, b.default_turn);
+        assert_eq!(a.default_delay, b.default_delay);
+        assert_eq!(a.positions, b.positions);
+        assert_eq!(a.seed, b.seed);
+        assert_eq!(a.dt, b.dt);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let network = example_network();
+        let restored = Network::from_json(&network.to_json()).unwrap();
+        assert_same_network(&network, &restored);
+    }
+
+    #[test]
+    fn edge_list_round_trip() {
+        let network = example_network();
+        let restored = Network::from_edge_list(&network.to_edge_list()).unwrap();
+        assert_same_network(&network, &restored);
+    }
+
+    #[test]
+    fn edge_list_without_metadata() {
+        let csv = "group,into,from,strength,turn,delay\n0,1,0,0.5,0,3\n1,2,1,0.5,0.5,4\n";
+        let network = Network::from_edge_list(csv).unwrap();
+        assert_eq!(network.nodes, 3);
+        assert_eq!(network.edges.len(), 2);
+        assert_eq!(network.edge_groups.len(), 2);
+    }
+
+    #[test]
+    fn edge_list_reports_line() {
+        let csv = "# nodes: 2\n0,1,0,0.5,0\n";
+        match Network::from_edge_list(csv) {
+            Err(NetworkFileError::EdgeList { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("malformed edge list was accepted"),
+        }
+    }
+
+    #[test]
+    fn graphml_import() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="d0" for="edge" attr.name="weight" attr.type="double"/>
+  <key id="d1" for="edge" attr.name="delay" attr.type="double"/>
+  <graph edgedefault="undirected">
+    <node id="a"/>
+    <node id="b"/>
+    <node id="c"/>
+    <edge source="a" target="b"><data key="d0">0.5</data></edge>
+    <edge source="b" target="c" directed="true"><data key="d1">20.0</data></edge>
+  </graph>
+</graphml>"#;
+        let network = Network::from_graphml(xml, 0.1, 0.0, 10.0, 0, 1.0 / 64.0).unwrap();
+        assert_eq!(network.nodes, 3);
+        assert_eq!(network.edges.len(), 3);
+        assert_eq!(network.get_edges_into_node(0)[0].strength, 0.5);
+        let into_c = network.get_edges_into_node(2);
+        assert_eq!(into_c.len(), 1);
+        assert_eq!((into_c[0].from, into_c[0].delay), (1, 20.0));
+        assert_eq!(into_c[0].strength, 0.1);
+    }
+}
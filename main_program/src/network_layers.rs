@@ -0,0 +1,117 @@
+use crate::edge_selection::EdgeSelection;
+use crate::network::Network;
+
+// multiplex networks: every layer couples through its own feedback variable of the system
+// (`Feedback::get_coupling`), e.g. electrical coupling via `x` and chemical coupling via a
+// synaptic variable. each layer has its own edges and therefore its own delays.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub enum SelectLayer {
+    AllLayers,
+    SingleLayer(usize),
+    NotLayer(usize),
+}
+
+#[allow(dead_code)]
+impl SelectLayer {
+    pub fn contains(&self, layer: usize) -> bool {
+        match self {
+            SelectLayer::AllLayers => true,
+            SelectLayer::SingleLayer(which) => layer == *which,
+            SelectLayer::NotLayer(which) => layer != *which,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Network {
+    // returns the index of the new layer, edges are put into it with `move_to_layer`
+    pub fn add_layer(&mut self, name: &str) -> usize {
+        // unnamed layers that are already used by edges keep their index
+        while self.layers.len() < self.number_of_layers() {
+            self.layers.push(format!("layer {}", self.layers.len()));
+        }
+        if self.layers.is_empty() {
+            self.layers.push("layer 0".to_string());
+        }
+        self.layers.push(name.to_string());
+        self.layers.len() - 1
+    }
+
+    // at least 1, also counts layers used by edges but without a name
+    pub fn number_of_layers(&self) -> usize {
+        self.edges
+            .iter()
+            .map(|e| e.layer + 1)
+            .fold(self.layers.len().max(1), usize::max)
+    }
+
+    pub fn layer_name(&self, layer: usize) -> String {
+        self.layers
+            .get(layer)
+            .cloned()
+            .unwrap_or_else(|| format!("layer {}", layer))
+    }
+
+    pub fn move_to_layer(&mut self, selection: impl Into<EdgeSelection>, layer: usize) {
+        self.modify_edges(selection, |e| e.layer = layer);
+    }
+
+    // indices of the edges in the selected layers, in network order
+    pub fn edges_in_layers(&self, selection: SelectLayer) -> Vec<usize> {
+        (0..self.edges.len())
+            .filter(|&i| selection.contains(self.edges[i].layer))
+            .collect()
+    }
+
+    // the same nodes with only the edges of the selected layers
+    pub fn select_layers(&self, selection: SelectLayer) -> Network {
+        Network {
+            nodes: self.nodes,
+            edges: self
+                .edges
+                .iter()
+                .filter(|e| selection.contains(e.layer))
+                .copied()
+                .collect(),
+            edge_groups: self.edge_groups.clone(),
+            layers: self.layers.clone(),
+            default_strength: self.default_strength,
+            default_turn: self.default_turn,
+            default_delay: self.default_delay,
+            positions: self.positions.clone(),
+            periodic_cell: self.periodic_cell,
+            seed: self.seed,
+            rng: self.rng.clone(),
+            dt: self.dt,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::SelectGroup;
+
+    #[test]
+    fn layers_split_the_edges() {
+        let mut network = Network::new(3, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_ring(0.1, 0.0, 1.0);
+        network.put_ring_reverse(0.2, 0.0, 2.0);
+        assert_eq!(network.number_of_layers(), 1);
+
+        let chemical = network.add_layer("chemical");
+        assert_eq!(chemical, 1);
+        assert_eq!(network.layers, vec!["layer 0", "chemical"]);
+        network.move_to_layer(SelectGroup::SingleGroup(1), chemical);
+        assert_eq!(network.number_of_layers(), 2);
+
+        let electrical = network.select_layers(SelectLayer::SingleLayer(0));
+        assert_eq!(electrical.edges.len(), 3);
+        assert!(electrical.edges.iter().all(|e| e.delay == 1.0));
+        assert_eq!(
+            network.edges_in_layers(SelectLayer::NotLayer(0)),
+            vec![3, 4, 5]
+        );
+    }
+}
@@ -0,0 +1,357 @@
+use crate::integration_methods::MAX_LAYERS;
+use crate::network::{delay_steps, Edge, Network};
+use std::fmt;
+
+// runge-kutta 4 reads the history at `delay` and half a step later, which needs two steps
+const MIN_DELAY_STEPS: usize = 2;
+const REPRESENTABLE_TOLERANCE: f64 = 1e-9; // relative to dt
+
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetworkError {
+    NodeOutOfRange {
+        edge: usize,
+        node: usize,
+        nodes: usize,
+    },
+    DelayTooShort {
+        edge: usize,
+        delay: f64,
+        steps: usize,
+    },
+    // the delay is not a multiple of `dt` (it is rounded to the nearest step, `delay_steps`) or not finite
+    DelayNotRepresentable {
+        edge: usize,
+        delay: f64,
+        dt: f64,
+    },
+    IsolatedNode {
+        node: usize,
+    },
+    NanStrength {
+        edge: usize,
+    },
+    // the system has no coupling variable for the edge's layer
+    LayerOutOfRange {
+        edge: usize,
+        layer: usize,
+        layers: usize,
+    },
+    // more (named) layers than the system couples through, or than a step can hold
+    TooManyLayers {
+        layers: usize,
+        max: usize,
+    },
+    // a learning rule and a coupling schedule would both write the edge's weight
+    PlasticAndScheduled {
+        edge: usize,
+    },
+    // updated edges change strength, turn and delay, not how many edges there are
+    EdgeCountChanged {
+        edges: usize,
+        expected: usize,
+    },
+    // or which nodes (and layer) an edge connects
+    EndpointsChanged {
+        edge: usize,
+    },
+}
+
+#[allow(dead_code)]
+impl NetworkError {
+    // non fatal problems still give a well defined integration
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            NetworkError::NodeOutOfRange { .. }
+            | NetworkError::DelayTooShort { .. }
+            | NetworkError::NanStrength { .. }
+            | NetworkError::LayerOutOfRange { .. }
+            | NetworkError::TooManyLayers { .. }
+            | NetworkError::PlasticAndScheduled { .. }
+            | NetworkError::EdgeCountChanged { .. }
+            | NetworkError::EndpointsChanged { .. } => true,
+            NetworkError::DelayNotRepresentable { delay, .. } => !delay.is_finite(),
+            NetworkError::IsolatedNode { .. } => false,
+        }
+    }
+
+    // `edges[i]` is the network's index of edge i, for errors found in a part of the network
+    pub fn renumber_edge(self, edges: &[usize]) -> Self {
+        match self {
+            NetworkError::NodeOutOfRange { edge, node, nodes } => NetworkError::NodeOutOfRange {
+                edge: edges[edge],
+                node,
+                nodes,
+            },
+            NetworkError::DelayTooShort { edge, delay, steps } => NetworkError::DelayTooShort {
+                edge: edges[edge],
+                delay,
+                steps,
+            },
+            NetworkError::DelayNotRepresentable { edge, delay, dt } => {
+                NetworkError::DelayNotRepresentable {
+                    edge: edges[edge],
+                    delay,
+                    dt,
+                }
+            }
+            NetworkError::NanStrength { edge } => NetworkError::NanStrength { edge: edges[edge] },
+            NetworkError::LayerOutOfRange {
+                edge,
+                layer,
+                layers,
+            } => NetworkError::LayerOutOfRange {
+                edge: edges[edge],
+                layer,
+                layers,
+            },
+            NetworkError::IsolatedNode { node } => NetworkError::IsolatedNode { node },
+            NetworkError::TooManyLayers { layers, max } => {
+                NetworkError::TooManyLayers { layers, max }
+            }
+            NetworkError::PlasticAndScheduled { edge } => {
+                NetworkError::PlasticAndScheduled { edge: edges[edge] }
+            }
+            NetworkError::EdgeCountChanged { edges, expected } => {
+                NetworkError::EdgeCountChanged { edges, expected }
+            }
+            NetworkError::EndpointsChanged { edge } => {
+                NetworkError::EndpointsChanged { edge: edges[edge] }
+            }
+        }
+    }
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkError::NodeOutOfRange { edge, node, nodes } => write!(
+                f,
+                "edge {} connects node {}, but the network only has {} nodes",
+                edge, node, nodes
+            ),
+            NetworkError::DelayTooShort { edge, delay, steps } => write!(
+                f,
+                "edge {} has delay {} ({} steps), runge kutta 4 needs at least {} steps",
+                edge, delay, steps, MIN_DELAY_STEPS
+            ),
+            NetworkError::DelayNotRepresentable { edge, delay, dt } => write!(
+                f,
+                "edge {} has delay {}, which is not a multiple of dt = {}",
+                edge, delay, dt
+            ),
+            NetworkError::IsolatedNode { node } => {
+                write!(f, "node {} has neither incoming nor outgoing edges", node)
+            }
+            NetworkError::NanStrength { edge } => write!(f, "edge {} has strength NaN", edge),
+            NetworkError::LayerOutOfRange {
+                edge,
+                layer,
+                layers,
+            } => write!(
+                f,
+                "edge {} is in layer {}, but the system only couples through {} variables",
+                edge, layer, layers
+            ),
+            NetworkError::TooManyLayers { layers, max } => write!(
+                f,
+                "the network has {} layers, the system couples through at most {}",
+                layers, max
+            ),
+            NetworkError::PlasticAndScheduled { edge } => write!(
+                f,
+                "edge {} is plastic and modulated by the coupling schedule",
+                edge
+            ),
+            NetworkError::EdgeCountChanged { edges, expected } => write!(
+                f,
+                "{} edges were given to update the {} edges of the network",
+                edges, expected
+            ),
+            NetworkError::EndpointsChanged { edge } => write!(
+                f,
+                "edge {} connects other nodes or another layer than before, an update only \
+                 changes strength, turn and delay",
+                edge
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+// new values for `current` edges may change strength, turn and delay, but not the edges
+pub fn check_same_edges(current: &[Edge], edges: &[Edge]) -> Result<(), NetworkError> {
+    if edges.len() != current.len() {
+        return Err(NetworkError::EdgeCountChanged {
+            edges: edges.len(),
+            expected: current.len(),
+        });
+    }
+    match current
+        .iter()
+        .zip(edges)
+        .position(|(c, e)| (c.into, c.from, c.layer) != (e.into, e.from, e.layer))
+    {
+        Some(edge) => Err(NetworkError::EndpointsChanged { edge }),
+        None => Ok(()),
+    }
+}
+
+#[allow(dead_code)]
+impl Network {
+    pub fn validate(&self) -> Result<(), Vec<NetworkError>> {
+        self.validate_for_dt(self.dt)
+    }
+
+    // collects every problem instead of stopping at the first one
+    pub fn validate_for_dt(&self, dt: f64) -> Result<(), Vec<NetworkError>> {
+        let mut errors = Vec::new();
+        let mut connected = vec![false; self.nodes];
+        for (i, e) in self.edges.iter().enumerate() {
+            for node in [e.into, e.from] {
+                if node < self.nodes {
+                    connected[node] = true;
+                } else {
+                    errors.push(NetworkError::NodeOutOfRange {
+                        edge: i,
+                        node,
+                        nodes: self.nodes,
+                    });
+                }
+            }
+            let steps = e.delay / dt;
+            if !steps.is_finite()
+                || (steps - steps.round()).abs() > REPRESENTABLE_TOLERANCE * steps.max(1.0)
+            {
+                errors.push(NetworkError::DelayNotRepresentable {
+                    edge: i,
+                    delay: e.delay,
+                    dt,
+                });
+            }
+            if steps.is_finite() && delay_steps(e.delay, dt) < MIN_DELAY_STEPS {
+                errors.push(NetworkError::DelayTooShort {
+                    edge: i,
+                    delay: e.delay,
+                    steps: delay_steps(e.delay, dt),
+                });
+            }
+            if e.strength.is_nan() {
+                errors.push(NetworkError::NanStrength { edge: i });
+            }
+        }
+        for (node, _) in connected.iter().enumerate().filter(|(_, &c)| !c) {
+            errors.push(NetworkError::IsolatedNode { node });
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // `coupling_layers` is the number of coupling variables of the system
+    // (`Feedback::coupling_names`)
+    pub fn validate_layers(&self, coupling_layers: usize) -> Result<(), Vec<NetworkError>> {
+        let mut errors: Vec<NetworkError> = self
+            .edges
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.layer >= coupling_layers)
+            .map(|(i, e)| NetworkError::LayerOutOfRange {
+                edge: i,
+                layer: e.layer,
+                layers: coupling_layers,
+            })
+            .collect();
+        // layers without edges still take part in every step
+        let max = coupling_layers.min(MAX_LAYERS);
+        if errors.is_empty() && self.number_of_layers() > max {
+            errors.push(NetworkError::TooManyLayers {
+                layers: self.number_of_layers(),
+                max,
+            });
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_problem_is_reported() {
+        let mut network = Network::new(4, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_ring(0.1, 0.0, 1.0);
+        assert_eq!(network.validate(), Ok(()));
+        network.edges.clear();
+        network.put_edge(0, 1, 0.1, 0.0, 1.0);
+
+        network.put_edge(1, 0, f64::NAN, 0.0, 1.0 / 64.0);
+        network.put_edge(2, 0, 0.1, 0.0, 1.0 + 0.3 / 64.0);
+        network.edges[2].into = 7;
+        let errors = network.validate().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                NetworkError::DelayTooShort {
+                    edge: 1,
+                    delay: 1.0 / 64.0,
+                    steps: 1
+                },
+                NetworkError::NanStrength { edge: 1 },
+                NetworkError::NodeOutOfRange {
+                    edge: 2,
+                    node: 7,
+                    nodes: 4
+                },
+                NetworkError::DelayNotRepresentable {
+                    edge: 2,
+                    delay: 1.0 + 0.3 / 64.0,
+                    dt: 1.0 / 64.0
+                },
+                NetworkError::IsolatedNode { node: 2 },
+                NetworkError::IsolatedNode { node: 3 },
+            ]
+        );
+        assert_eq!(errors.iter().filter(|e| e.is_fatal()).count(), 3);
+    }
+    #[test]
+    fn layers_fit_the_system() {
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_bi_ring(0.1, 0.0, 1.0);
+        network.add_layer("synapse"); // layers 0 and 1
+        assert_eq!(network.validate_layers(2), Ok(()));
+        network.add_layer("gap junction");
+        assert_eq!(
+            network.validate_layers(2),
+            Err(vec![NetworkError::TooManyLayers { layers: 3, max: 2 }])
+        );
+        for l in 3..10 {
+            network.add_layer(&format!("layer {}", l));
+        }
+        assert_eq!(
+            network.validate_layers(16),
+            Err(vec![NetworkError::TooManyLayers {
+                layers: 10,
+                max: MAX_LAYERS
+            }])
+        );
+        // an edge outside the system's layers is reported by itself
+        network.edges[0].layer = 9;
+        assert_eq!(
+            network.validate_layers(2),
+            Err(vec![NetworkError::LayerOutOfRange {
+                edge: 0,
+                layer: 9,
+                layers: 2
+            }])
+        );
+    }
+}
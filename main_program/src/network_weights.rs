@@ -0,0 +1,158 @@
+use crate::dynamical_system::WeightFromEdge;
+use crate::matrix::{self, ComplexMatrix};
+use crate::network::Network;
+use num_complex::Complex;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub enum Normalization {
+    InDegree,         // divide each edge's strength by the number of edges into its target
+    OutDegree,        // divide each edge's strength by the number of edges out of its source
+    RowStochastic,    // strengths into each node sum up to 1
+    ColumnStochastic, // strengths out of each node sum up to 1 ("node's total output")
+}
+
+#[allow(dead_code)]
+impl Network {
+    pub fn normalize_weights(&mut self, normalization: Normalization) {
+        let mut totals = vec![0.0; self.nodes];
+        for e in &self.edges {
+            let node = match normalization {
+                Normalization::InDegree | Normalization::RowStochastic => e.into,
+                Normalization::OutDegree | Normalization::ColumnStochastic => e.from,
+            };
+            totals[node] += match normalization {
+                Normalization::InDegree | Normalization::OutDegree => 1.0,
+                Normalization::RowStochastic | Normalization::ColumnStochastic => e.strength.abs(),
+            };
+        }
+        for e in &mut self.edges {
+            let total = match normalization {
+                Normalization::InDegree | Normalization::RowStochastic => totals[e.into],
+                Normalization::OutDegree | Normalization::ColumnStochastic => totals[e.from],
+            };
+            if total > 0.0 {
+                e.strength /= total;
+            }
+        }
+    }
+
+    // `matrix[into][from]` is the sum of the weights of all edges between the two nodes.
+    // the weight type decides whether `turn` is part of the coupling, e.g.
+    // `network.weighted_adjacency_matrix::<<lorenz::System as Feedback>::WeightT>()`
+    pub fn weighted_adjacency_matrix<WeightT>(&self) -> ComplexMatrix
+    where
+        WeightT: WeightFromEdge + Into<Complex<f64>>,
+    {
+        let mut adjacency = matrix::zeros(self.nodes);
+        for e in &self.edges {
+            adjacency[e.into][e.from] += WeightT::from_edge(e).into();
+        }
+        adjacency
+    }
+
+    pub fn adjacency_eigenvalues<WeightT>(&self) -> Vec<Complex<f64>>
+    where
+        WeightT: WeightFromEdge + Into<Complex<f64>>,
+    {
+        matrix::eigenvalues(self.weighted_adjacency_matrix::<WeightT>())
+    }
+
+    pub fn spectral_radius<WeightT>(&self) -> f64
+    where
+        WeightT: WeightFromEdge + Into<Complex<f64>>,
+    {
+        self.adjacency_eigenvalues::<WeightT>()
+            .iter()
+            .map(|lambda| lambda.norm())
+            .fold(0.0, f64::max)
+    }
+
+    // rescale all strengths so that the weighted adjacency matrix has the spectral radius
+    // `target` (echo state network preparation). returns the spectral radius before scaling.
+    pub fn scale_to_spectral_radius<WeightT>(&mut self, target: f64) -> Result<f64, String>
+    where
+        WeightT: WeightFromEdge + Into<Complex<f64>>,
+    {
+        let radius = self.spectral_radius::<WeightT>();
+        if radius <= f64::EPSILON {
+            return Err(format!(
+                "spectral radius {:e} is zero (acyclic network?), cannot rescale it to {}",
+                radius, target
+            ));
+        }
+        for e in &mut self.edges {
+            e.strength *= target / radius;
+        }
+        Ok(radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamical_system::{WeightComplex, WeightReal};
+    use crate::random_networks::{EdgeValues, ValueDistribution};
+
+    #[test]
+    fn stochastic_normalization() {
+        let mut network = Network::new(6, 0.1, 0.0, 10.0, 2, 1.0 / 64.0);
+        network.put_erdos_renyi(
+            0.6,
+            EdgeValues {
+                strength: ValueDistribution::Uniform {
+                    low: 0.1,
+                    high: 1.0,
+                },
+                turn: ValueDistribution::Constant(0.0),
+                delay: ValueDistribution::Constant(10.0),
+            },
+        );
+        network.normalize_weights(Normalization::RowStochastic);
+        for n in 0..network.nodes {
+            let incoming: Vec<f64> = network
+                .edges
+                .iter()
+                .filter(|e| e.into == n)
+                .map(|e| e.strength)
+                .collect();
+            if !incoming.is_empty() {
+                assert!((incoming.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+            }
+        }
+        network.normalize_weights(Normalization::ColumnStochastic);
+        for n in 0..network.nodes {
+            let outgoing: Vec<f64> = network
+                .edges
+                .iter()
+                .filter(|e| e.from == n)
+                .map(|e| e.strength)
+                .collect();
+            if !outgoing.is_empty() {
+                assert!((outgoing.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn spectral_radius_scaling() {
+        let mut ring = Network::new(8, 0.1, 0.0, 10.0, 0, 1.0 / 64.0);
+        ring.put_ring(0.4, 0.25, 10.0);
+        assert!((ring.spectral_radius::<WeightComplex>() - 0.4).abs() < 1e-9);
+
+        // opposite phases cancel for complex weights, not for real ones
+        let mut two_edges = Network::new(1, 0.1, 0.0, 10.0, 0, 1.0 / 64.0);
+        two_edges.put_edge(0, 0, 0.5, 0.0, 10.0);
+        two_edges.put_edge(0, 0, 0.5, 0.5, 10.0);
+        assert!(two_edges.spectral_radius::<WeightComplex>() < 1e-12);
+        assert!((two_edges.spectral_radius::<WeightReal>() - 1.0).abs() < 1e-12);
+        assert!(two_edges
+            .scale_to_spectral_radius::<WeightComplex>(0.9)
+            .is_err());
+
+        let mut network = Network::new(40, 0.1, 0.0, 10.0, 9, 1.0 / 64.0);
+        network.put_erdos_renyi(0.1, EdgeValues::constant(0.3, 0.0, 10.0));
+        network.scale_to_spectral_radius::<WeightReal>(0.9).unwrap();
+        assert!((network.spectral_radius::<WeightReal>() - 0.9).abs() < 1e-9);
+    }
+}
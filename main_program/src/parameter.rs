@@ -1,6 +1,3 @@
-// use colored::Colorize;
-// use itertools::Itertools;
-// use std::collections::HashMap;
 use std::fmt;
 
 use crate::var::Var;
@@ -17,6 +14,102 @@ pub enum ParameterMultiplicity {
     CanReoccur,
 }
 
+// what can go wrong on the command line, `option` as it was written (e.g. "-idt")
+#[derive(Debug, PartialEq)]
+pub enum ArgumentError {
+    UnknownOption(String),
+    AmbiguousOption {
+        option: String,
+        domains: Vec<&'static str>,
+    },
+    UnexpectedArgument(String), // neither an option, nor a value, nor a domain
+    MissingValues {
+        option: String,
+        expected: usize,
+        found: usize,
+    },
+    InvalidValue {
+        option: String,
+        value: String,
+        expected: &'static str,
+    },
+    RepeatedOption(String), // a unique parameter was given twice
+    ConflictingOptions(String, String),
+    InvalidChoice {
+        option: String,
+        value: String,
+        choices: Vec<&'static str>,
+    },
+    OutOfRange {
+        option: String,
+        value: String,
+        expected: String,
+    },
+}
+
+impl fmt::Display for ArgumentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgumentError::UnknownOption(option) => write!(f, "unknown option `{}`", option),
+            ArgumentError::AmbiguousOption { option, domains } => write!(
+                f,
+                "`{}` exists in the domains {}, put the domain in front of it",
+                option,
+                domains.join(", ")
+            ),
+            ArgumentError::UnexpectedArgument(argument) => {
+                write!(f, "unexpected argument `{}`", argument)
+            }
+            ArgumentError::MissingValues {
+                option,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` needs {} values, only {} given",
+                option, expected, found
+            ),
+            ArgumentError::InvalidValue {
+                option,
+                value,
+                expected,
+            } => write!(
+                f,
+                "`{} {}`: expected a value of type {}",
+                option, value, expected
+            ),
+            ArgumentError::RepeatedOption(option) => {
+                write!(f, "`{}` can only be given once", option)
+            }
+            ArgumentError::ConflictingOptions(first, second) => {
+                write!(f, "`{}` and `{}` cannot be combined", first, second)
+            }
+            ArgumentError::InvalidChoice {
+                option,
+                value,
+                choices,
+            } => write!(
+                f,
+                "`{} {}`: expected one of {}",
+                option,
+                value,
+                choices.join(", ")
+            ),
+            ArgumentError::OutOfRange {
+                option,
+                value,
+                expected,
+            } => write!(
+                f,
+                "`{} {}` is out of range, expected {}",
+                option, value, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArgumentError {}
+
 #[allow(dead_code)]
 // #[derive(Debug)]
 pub struct Parameter<'a> {
@@ -33,87 +126,81 @@ impl<'a> Parameter<'a> {
     pub fn parameter_length(&self) -> usize {
         self.parameter.len()
     }
-}
-
-impl<'a> Parameter<'a> {
-    pub fn parse_parameter(&mut self, args: &'a Vec<String>, keywords_index: usize) {
-        // let minimum_args = if self.parameter.len() == 1 {
-        //     match self.parameter[0] {
-        //         Var::Switch(_) => 0,
-        //         _ => 1,
-        //     }
-        // } else {
-        //     1
-        // };
-
-        let _minimum_args = 1;
-
-        if keywords_index + self.parameter.len() <= args.len() {
-            //
-            // case 1: is_unique {
-            //
-            // }
-            // case 2:
-            // if !self.was_set {
-            //     println!("stop doing it.")
-            // } else {
-            //     if self.is_unique {}
-            // }
 
-            // if self.was_set && !self.is_unique {
-            //     println!("try find another instance of letter.");
-            // }
-
-            // if self.was_set && self.is_unique {
-            //     println!("stop this.");
-            // }
-
-            // if !self.was_set {
-            //
-            let mut parameter_set_counter = 1;
-            'args_loop: for i in 0..args.len() {
-                'set_s_loop: for set_s in &self.set_by {
-                    let compare_str = format!("-{}", *set_s);
-
-                    if compare_str == args[i].as_str() {
-                        // println!("length of parameter {}: {}", set_s, self.parameter.len());
+    // a single `Switch` is set by the option alone, without a value
+    pub fn is_switch(&self) -> bool {
+        matches!(self.parameter[0][..], [Var::Switch(_)])
+    }
 
-                        if !self.is_unique && parameter_set_counter > 1 {
-                            let parameter_copy = self.parameter.first().unwrap().clone();
-                            self.parameter.push(parameter_copy);
-                            // println!("length of parameter {}: {}", set_s, self.parameter.len());
-                        }
-                        parameter_set_counter = parameter_set_counter + 1;
-                        // println!("{parameter_set_counter}");
+    // the values given on the command line, nothing for a repeatable parameter that was not
+    // given (its defaults are only the template for the types)
+    pub fn sets(&self) -> &[Vec<Var<'a>>] {
+        if self.is_unique || self.was_set {
+            &self.parameter
+        } else {
+            &[]
+        }
+    }
 
-                        // println!("length of parameter {}: {}", set_s, self.parameter.len());
-                        //
-                        // println!("found `set_by`: {}", compare_str);
+    // one occurrence of the option, `values` are the arguments following it. returns how many
+    // of them belong to the option. the first occurrence replaces the defaults, further ones
+    // of a repeatable parameter are appended.
+    pub fn parse_parameter(
+        &mut self,
+        option: &str,
+        values: &'a [String],
+    ) -> Result<usize, ArgumentError> {
+        if self.is_switch() {
+            self.parameter[0][0] = Var::Switch(true);
+            self.was_set = true;
+            return Ok(0);
+        }
+        if self.was_set && self.is_unique {
+            return Err(ArgumentError::RepeatedOption(option.to_string()));
+        }
+        let expected = self.parameter[0].len();
+        if values.len() < expected {
+            return Err(ArgumentError::MissingValues {
+                option: option.to_string(),
+                expected,
+                found: values.len(),
+            });
+        }
+        let mut set = self.parameter[0].clone();
+        for (part, value) in set.iter_mut().zip(values) {
+            part.try_parse(value)
+                .map_err(|expected| ArgumentError::InvalidValue {
+                    option: option.to_string(),
+                    value: value.clone(),
+                    expected,
+                })?;
+        }
+        if self.was_set {
+            self.parameter.push(set);
+        } else {
+            self.parameter[0] = set;
+        }
+        self.was_set = true;
+        Ok(expected)
+    }
 
-                        for (j, part) in &mut self
-                            .parameter
-                            .last_mut()
-                            .expect("here is the problem")
-                            // .unwrap()
-                            .iter_mut()
-                            .enumerate()
-                        {
-                            if i + j + 1 < args.len() {
-                                part.try_parse(args[i + j + 1].as_str());
-                            }
-                        }
-                        self.was_set = true;
-                        if self.is_unique {
-                            break 'args_loop;
-                        }
-                        break 'set_s_loop;
-                    }
-                }
+    // e.g. "-edge <int> <int> <float> ... (repeatable)"
+    pub fn usage(&self) -> String {
+        let mut usage = self
+            .set_by
+            .iter()
+            .map(|s| format!("-{}", s))
+            .collect::<Vec<String>>()
+            .join(" | ");
+        if !self.is_switch() {
+            for part in &self.parameter[0] {
+                usage += &format!(" <{}>", part.kind());
             }
-            // } else {
-            //     if !self.is_unique {}
-            // }
         }
+        if !self.is_unique {
+            usage += " (repeatable)";
+        }
+        usage
     }
 }
 
@@ -134,29 +221,24 @@ impl<'a> fmt::Debug for Parameter<'a> {
 impl<'a> fmt::Display for Parameter<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if self.is_unique {
-            write!(f, "[ ").unwrap();
+            write!(f, "[ ")?;
             for parameter_element in &self.parameter[0] {
-                write!(f, "{} ", &parameter_element).unwrap();
+                write!(f, "{} ", &parameter_element)?;
             }
         } else {
-            write!(f, "[").unwrap();
+            write!(f, "[")?;
             for (i, parameter_set) in self.parameter.iter().enumerate() {
-                write!(f, " ({}): [ ", i).unwrap();
+                write!(f, " ({}): [ ", i)?;
                 for parameter_element in parameter_set {
-                    write!(f, "{} ", parameter_element).unwrap();
+                    write!(f, "{} ", parameter_element)?;
                 }
-                if &parameter_set != &self.parameter.iter().last().unwrap() {
-                    write!(f, "],").unwrap();
+                if i + 1 < self.parameter.len() {
+                    write!(f, "],")?;
                 } else {
-                    write!(f, "]").unwrap();
+                    write!(f, "]")?;
                 }
             }
         }
-        // if self.was_set {
-        //     write!(f, "] [active]")
-        // } else {
-        //     write!(f, "]")
-        // }
         write!(f, "]")
     }
 }
@@ -0,0 +1,388 @@
+use serde::Deserialize;
+
+use crate::coupling_schedule::{self, EdgeParameter};
+use crate::edge_selection::EdgeSelection;
+use crate::integration_methods::IntegrationMethods;
+use crate::network::Edge;
+
+// a bifurcation diagram: the parameter is stepped from `from` to `to` (`values` values, both
+// ends included, `to` < `from` sweeps downwards). at every value `transient` time units are
+// discarded, then the `points` of `variable` are recorded for `record` time units and written
+// to `file` in the data directory, one line "value node point" per point.
+// `continuation`: every value starts from the attractor of the previous one, which follows a
+// branch through hysteresis. otherwise every value starts from the state before the sweep.
+#[allow(dead_code)]
+#[derive(Clone, Deserialize)]
+pub struct ParameterSweep {
+    pub parameter: SweepParameter,
+    pub from: f64,
+    pub to: f64,
+    pub values: usize,
+    pub continuation: bool,
+    pub transient: f64,
+    pub record: f64,
+    pub variable: usize, // index into the saved variables (`timeseries_curve_names`)
+    pub points: SweepPoints,
+    pub file: String,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Deserialize)]
+pub enum SweepParameter {
+    Model(String), // a model parameter of every node, e.g. "beta_0"
+    Edges {
+        selection: EdgeSelection,
+        parameter: EdgeParameter,
+    },
+}
+
+#[allow(dead_code)]
+impl SweepParameter {
+    pub fn name(&self) -> String {
+        match self {
+            SweepParameter::Model(name) => name.clone(),
+            SweepParameter::Edges { parameter, .. } => format!("edge {:?}", parameter),
+        }
+    }
+
+    // `edges` are the current ones, `edge_groups` the network's group names
+    pub fn resolve(&self, edges: &[Edge], edge_groups: &[String]) -> ResolvedParameter {
+        match self {
+            SweepParameter::Model(name) => ResolvedParameter::Model(name.clone()),
+            SweepParameter::Edges {
+                selection,
+                parameter,
+            } => ResolvedParameter::Edges {
+                edges: (0..edges.len())
+                    .filter(|&i| selection.matches(&edges[i], edge_groups))
+                    .collect(),
+                parameter: *parameter,
+            },
+        }
+    }
+}
+
+// a `SweepParameter` with the selection replaced by the indices of the selected edges, it can
+// be handed to other threads
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum ResolvedParameter {
+    Model(String),
+    Edges {
+        edges: Vec<usize>,
+        parameter: EdgeParameter,
+    },
+}
+
+#[allow(dead_code)]
+impl ResolvedParameter {
+    pub fn set(&self, system: &mut dyn IntegrationMethods, value: f64) -> Result<(), String> {
+        match self {
+            ResolvedParameter::Model(name) => {
+                system.set_model_parameters(&serde_json::json!({ name: model_value(value) }))
+            }
+            ResolvedParameter::Edges { edges, parameter } => {
+                // starts from the current edges, so learned strengths are kept
+                let mut current = system.current_edges();
+                for &i in edges {
+                    *coupling_schedule::parameter_of(&mut current[i], *parameter) = value;
+                }
+                system.update_edges(&current).map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum SweepPoints {
+    Maxima,
+    Minima,
+    Extrema,
+    // the value of `variable` where the variable `section` crosses `level` upwards
+    Poincare { section: usize, level: f64 },
+}
+
+#[allow(dead_code)]
+impl ParameterSweep {
+    pub fn parameter_values(&self) -> Vec<f64> {
+        values_between(self.from, self.to, self.values)
+    }
+
+    // for the header of the output file
+    pub fn parameter_name(&self) -> String {
+        self.parameter.name()
+    }
+}
+
+// `values` values from `from` to `to`, both included
+pub fn values_between(from: f64, to: f64, values: usize) -> Vec<f64> {
+    if values < 2 {
+        return vec![from; values];
+    }
+    let last = (values - 1) as f64;
+    (0..values)
+        .map(|i| from + (to - from) * i as f64 / last)
+        .collect()
+}
+
+// a model parameter's value as json. whole numbers also fit integer parameters, e.g. the
+// exponent of mackey-glass
+pub fn model_value(value: f64) -> serde_json::Value {
+    match value.fract() == 0.0 && value.abs() < 1e15 {
+        true => serde_json::json!(value as i64),
+        false => serde_json::json!(value),
+    }
+}
+
+// finds the points of one variable of every node in consecutive states. extrema are refined
+// by a parabola through the last three samples, poincare points interpolated linearly.
+pub struct PointRecorder {
+    points: SweepPoints,
+    variable: usize,
+    variables: usize,
+    // per node the last two samples of (variable, section variable)
+    previous: Vec<[(f64, f64); 2]>,
+    samples: usize,
+    pub found: Vec<(usize, f64)>, // (node, point)
+}
+
+#[allow(dead_code)]
+impl PointRecorder {
+    // `variables` per node in the states given to `push`
+    pub fn new(points: SweepPoints, variable: usize, nodes: usize, variables: usize) -> Self {
+        PointRecorder {
+            points,
+            variable,
+            variables,
+            previous: vec![[(0.0, 0.0); 2]; nodes],
+            samples: 0,
+            found: Vec::new(),
+        }
+    }
+
+    // a state of all nodes, `[node * variables + variable]` like `keep_state`
+    pub fn push(&mut self, state: &[f64]) {
+        let section = match self.points {
+            SweepPoints::Poincare { section, .. } => section,
+            _ => self.variable,
+        };
+        for (node, previous) in self.previous.iter_mut().enumerate() {
+            let y2 = state[node * self.variables + self.variable];
+            let s2 = state[node * self.variables + section];
+            let [(y0, _), (y1, s1)] = *previous;
+            if self.samples >= 2 {
+                let point = match self.points {
+                    SweepPoints::Maxima => maximum(y0, y1, y2),
+                    SweepPoints::Minima => minimum(y0, y1, y2),
+                    SweepPoints::Extrema => maximum(y0, y1, y2).or(minimum(y0, y1, y2)),
+                    SweepPoints::Poincare { level, .. } => (s1 < level && s2 >= level)
+                        .then(|| y1 + (level - s1) / (s2 - s1) * (y2 - y1)),
+                };
+                if let Some(point) = point {
+                    self.found.push((node, point));
+                }
+            }
+            *previous = [(y1, s1), (y2, s2)];
+        }
+        self.samples += 1;
+    }
+}
+
+fn maximum(y0: f64, y1: f64, y2: f64) -> Option<f64> {
+    (y1 > y0 && y1 >= y2).then(|| parabola_vertex(y0, y1, y2))
+}
+
+fn minimum(y0: f64, y1: f64, y2: f64) -> Option<f64> {
+    (y1 < y0 && y1 <= y2).then(|| parabola_vertex(y0, y1, y2))
+}
+
+// of the parabola through (-1, y0), (0, y1), (1, y2)
+fn parabola_vertex(y0: f64, y1: f64, y2: f64) -> f64 {
+    let curvature = y0 - 2.0 * y1 + y2;
+    if curvature == 0.0 {
+        y1
+    } else {
+        y1 - (y2 - y0).powi(2) / (8.0 * curvature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculation::{Calculation, CouplingLayout, NodeSetup, SystemType, Tasks};
+    use crate::network::Network;
+    use std::fs;
+    use timeseries::SaveItems;
+
+    // (parameter value, point) of every line of a sweep's file
+    fn sweep_lorenz(name: &str, sweep: ParameterSweep) -> Vec<(f64, f64)> {
+        let dt = 1.0 / 256.0;
+        let directory = std::env::temp_dir().join(name);
+        let mut network = Network::new(1, 0.0, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 0, 0.0, 0.0, 1.0);
+        let tasks = vec![Tasks::ParameterSweep(sweep)];
+        let mut calculation = Calculation::examples_in_directory(
+            &directory.to_string_lossy(),
+            dt,
+            &network,
+            256,
+            NodeSetup::Identical,
+            SystemType::Lorenz,
+            CouplingLayout::RingBuffers,
+            &tasks,
+            SaveItems::Timeseries,
+        )
+        .unwrap();
+        calculation.perform_tasks().unwrap();
+        let lines = fs::read_to_string(directory.join("sweep.txt")).unwrap();
+        fs::remove_dir_all(directory).unwrap();
+        assert_eq!(lines.lines().next(), Some("# rho\tnode\tz (Maxima)"));
+        lines
+            .lines()
+            .skip(1)
+            .map(|line| {
+                let columns: Vec<f64> = line.split('\t').map(|c| c.parse().unwrap()).collect();
+                assert_eq!(columns[1], 0.0);
+                (columns[0], columns[2])
+            })
+            .collect()
+    }
+
+    fn rho_sweep(from: f64, to: f64, continuation: bool) -> ParameterSweep {
+        ParameterSweep {
+            parameter: SweepParameter::Model("rho".to_string()),
+            from,
+            to,
+            values: 2,
+            continuation,
+            transient: 50.0,
+            record: 20.0,
+            variable: 2,
+            points: SweepPoints::Maxima,
+            file: "sweep.txt".to_string(),
+        }
+    }
+
+    #[test]
+    fn lorenz_sweep_finds_fixed_point_and_chaos() {
+        let points = sweep_lorenz("sweep_lorenz_rho", rho_sweep(10.0, 28.0, true));
+        // rho = 10: the oscillations decay towards z = rho - 1
+        let fixed_point: Vec<f64> = points.iter().filter(|p| p.0 == 10.0).map(|p| p.1).collect();
+        assert!(
+            fixed_point.iter().all(|z| (z - 9.0).abs() < 1e-3),
+            "{:?}",
+            fixed_point
+        );
+        // rho = 28: the maxima of the chaotic attractor spread over a wide range
+        let chaos: Vec<f64> = points.iter().filter(|p| p.0 == 28.0).map(|p| p.1).collect();
+        assert!(chaos.len() > 10);
+        let spread = chaos.iter().cloned().fold(f64::MIN, f64::max)
+            - chaos.iter().cloned().fold(f64::MAX, f64::min);
+        assert!(spread > 5.0, "{:?}", chaos);
+
+        // without continuation every value starts from the same state
+        let points = sweep_lorenz("sweep_lorenz_restart", rho_sweep(28.0, 28.0, false));
+        let half = points.len() / 2;
+        assert!(half > 0);
+        assert_eq!(points[..half], points[half..]);
+        let points = sweep_lorenz("sweep_lorenz_continued", rho_sweep(28.0, 28.0, true));
+        assert_ne!(points[..points.len() / 2], points[points.len() / 2..]);
+    }
+
+    #[test]
+    fn integer_parameters_can_be_swept() {
+        let dt = 1.0 / 64.0;
+        let directory = std::env::temp_dir().join("sweep_mackey_glass_exponent");
+        let mut network = Network::new(1, 0.0, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 0, 1.0, 0.0, 17.0);
+        let tasks = vec![Tasks::ParameterSweep(ParameterSweep {
+            parameter: SweepParameter::Model("n".to_string()),
+            from: 8.0,
+            to: 10.0,
+            values: 3,
+            continuation: true,
+            transient: 100.0,
+            record: 100.0,
+            variable: 0,
+            points: SweepPoints::Maxima,
+            file: "sweep.txt".to_string(),
+        })];
+        let mut calculation = Calculation::examples_in_directory(
+            &directory.to_string_lossy(),
+            dt,
+            &network,
+            256,
+            NodeSetup::Identical,
+            SystemType::MackeyGlass,
+            CouplingLayout::RingBuffers,
+            &tasks,
+            SaveItems::Timeseries,
+        )
+        .unwrap();
+        calculation.perform_tasks().unwrap();
+        let lines = fs::read_to_string(directory.join("sweep.txt")).unwrap();
+        fs::remove_dir_all(directory).unwrap();
+        for n in ["8", "9", "10"] {
+            assert!(lines
+                .lines()
+                .any(|line| line.starts_with(&format!("{}\t", n))));
+        }
+        assert_eq!(model_value(10.0), serde_json::json!(10));
+        assert_eq!(model_value(0.25), serde_json::json!(0.25));
+    }
+
+    #[test]
+    fn points_of_oscillations_are_found() {
+        // node 0: (sin, cos), node 1: (2 sin, cos) at 100 samples per period
+        let record = |points| {
+            let mut recorder = PointRecorder::new(points, 0, 2, 2);
+            for i in 0..1000 {
+                let phase = i as f64 * std::f64::consts::TAU / 100.0;
+                recorder.push(&[phase.sin(), phase.cos(), 2.0 * phase.sin(), phase.cos()]);
+            }
+            recorder.found
+        };
+
+        let maxima = record(SweepPoints::Maxima);
+        assert_eq!(maxima.len(), 20);
+        for (node, point) in maxima {
+            assert!((point - (node + 1) as f64).abs() < 1e-5, "{}", point);
+        }
+        let extrema = record(SweepPoints::Extrema);
+        assert_eq!(extrema.len(), 40);
+        assert!(extrema.iter().any(|&(_, point)| (point + 2.0).abs() < 1e-4));
+
+        // sin where cos crosses 0 upwards: at 3/4 of the period, sin = -1
+        let section = record(SweepPoints::Poincare {
+            section: 1,
+            level: 0.0,
+        });
+        assert_eq!(section.len(), 20);
+        for (node, point) in section {
+            assert!((point + (node + 1) as f64).abs() < 1e-3, "{}", point);
+        }
+    }
+
+    #[test]
+    fn values_include_both_ends() {
+        let sweep = |from, to, values| ParameterSweep {
+            parameter: SweepParameter::Model("gamma".to_string()),
+            from,
+            to,
+            values,
+            continuation: true,
+            transient: 0.0,
+            record: 1.0,
+            variable: 0,
+            points: SweepPoints::Maxima,
+            file: String::new(),
+        };
+        assert_eq!(
+            sweep(0.0, 1.0, 5).parameter_values(),
+            [0.0, 0.25, 0.5, 0.75, 1.0]
+        );
+        assert_eq!(sweep(1.0, 0.0, 3).parameter_values(), [1.0, 0.5, 0.0]);
+        assert_eq!(sweep(0.5, 1.0, 1).parameter_values(), [0.5]);
+    }
+}
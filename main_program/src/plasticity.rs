@@ -0,0 +1,328 @@
+use crate::coupling_schedule::CouplingSchedule;
+use crate::edge_selection::EdgeSelection;
+use crate::integration_methods::RungeKuttaDelay;
+use crate::network::Edge;
+use crate::network_validation::NetworkError;
+use num_complex::Complex;
+use serde::{Deserialize, Serialize};
+
+// what a learning rule sees of a node's feedback value
+pub trait Activity {
+    fn activity(&self) -> f64;
+    fn phase(&self) -> f64;
+}
+
+// real feedback: the value itself, used as phase for phase oscillators
+impl Activity for f64 {
+    fn activity(&self) -> f64 {
+        *self
+    }
+    fn phase(&self) -> f64 {
+        *self
+    }
+}
+
+// complex fields: amplitude and phase
+impl Activity for Complex<f64> {
+    fn activity(&self) -> f64 {
+        self.norm()
+    }
+    fn phase(&self) -> f64 {
+        self.arg()
+    }
+}
+
+impl<T> Activity for RungeKuttaDelay<T>
+where
+    T: Activity
+        + Sized
+        + Clone
+        + Copy
+        + Default
+        + core::iter::Sum
+        + std::ops::Add<Output = T>
+        + std::ops::AddAssign,
+{
+    fn activity(&self) -> f64 {
+        self.state.activity()
+    }
+    fn phase(&self) -> f64 {
+        self.state.phase()
+    }
+}
+
+// "pre" is the source's activity arriving through the edge (i.e. delayed), "post" the
+// target's current activity. the rules change `strength`, `turn` stays fixed.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum PlasticityRule {
+    // d strength/dt = rate * (pre * post - decay * strength)
+    Hebbian {
+        rate: f64,
+        decay: f64,
+    },
+    // pair based spike timing dependent plasticity with exponentially decaying traces.
+    // a spike is an upward crossing of `threshold` by the activity.
+    Stdp {
+        potentiation: f64, // added per post spike, times the pre trace
+        depression: f64,   // subtracted per pre spike, times the post trace
+        tau_plus: f64,
+        tau_minus: f64,
+        threshold: f64,
+    },
+    // adaptive phase coupling (aoki, aihara):
+    // d strength/dt = -rate * (strength + sin(phase_post - phase_pre + phase_lag))
+    AdaptiveKuramoto {
+        rate: f64,
+        phase_lag: f64,
+    },
+}
+
+#[allow(dead_code)]
+impl PlasticityRule {
+    // hebbian and adaptive kuramoto change the strength continuously, stdp jumps at spikes
+    pub fn is_smooth(&self) -> bool {
+        !matches!(self, PlasticityRule::Stdp { .. })
+    }
+
+    // d strength/dt of a smooth rule, 0 for stdp
+    pub fn rate_of_change(&self, strength: f64, pre: &impl Activity, post: &impl Activity) -> f64 {
+        match *self {
+            PlasticityRule::Hebbian { rate, decay } => {
+                rate * (pre.activity() * post.activity() - decay * strength)
+            }
+            PlasticityRule::AdaptiveKuramoto { rate, phase_lag } => {
+                -rate * (strength + (post.phase() - pre.phase() + phase_lag).sin())
+            }
+            PlasticityRule::Stdp { .. } => 0.0,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Plasticity {
+    pub rule: PlasticityRule,
+    pub selection: EdgeSelection, // `GroupNamed` is resolved with `resolve_group_names`
+    pub min_strength: f64,
+    pub max_strength: f64,
+}
+
+#[allow(dead_code)]
+impl Plasticity {
+    pub fn resolve_group_names(&self, edge_groups: &[String]) -> Plasticity {
+        Plasticity {
+            selection: self.selection.resolve_group_names(edge_groups),
+            ..self.clone()
+        }
+    }
+}
+
+// the state of all learning rules of a network, per edge
+#[allow(dead_code)]
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PlasticEdges {
+    rules: Vec<Plasticity>,
+    rule_of_edge: Vec<Option<usize>>, // the last rule selecting the edge's group
+    pre_trace: Vec<f64>,
+    post_trace: Vec<f64>,
+    previous_pre: Vec<f64>,
+    previous_post: Vec<f64>,
+}
+
+#[allow(dead_code)]
+impl PlasticEdges {
+    pub fn new(rules: Vec<Plasticity>, edges: &[Edge]) -> Self {
+        let rule_of_edge = edges
+            .iter()
+            .map(|e| rules.iter().rposition(|p| p.selection.matches(e, &[])))
+            .collect();
+        PlasticEdges {
+            rules,
+            rule_of_edge,
+            pre_trace: vec![0.0; edges.len()],
+            post_trace: vec![0.0; edges.len()],
+            previous_pre: vec![f64::INFINITY; edges.len()], // no spike in the first step
+            previous_post: vec![f64::INFINITY; edges.len()],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn is_plastic(&self, edge: usize) -> bool {
+        self.rule_of_edge[edge].is_some()
+    }
+
+    // the schedule recomputes the weights from the base edges and the rules write them back,
+    // one would silently undo the other
+    pub fn check_schedule(
+        &self,
+        schedule: &CouplingSchedule,
+        edges: &[Edge],
+    ) -> Result<(), NetworkError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        match (0..edges.len()).find(|&i| self.is_plastic(i) && schedule.modulates(&edges[i])) {
+            Some(edge) => Err(NetworkError::PlasticAndScheduled { edge }),
+            None => Ok(()),
+        }
+    }
+
+    // whether edge i changes smoothly, in the runge-kutta stages of its target node
+    pub fn is_smooth(&self, edge: usize) -> bool {
+        self.rule_of_edge[edge].is_some_and(|r| self.rules[r].rule.is_smooth())
+    }
+
+    // what the target of edge i needs to integrate a smooth rule along with its state, `None`
+    // for fixed and stdp edges. `arriving` are the delayed values at the start and the end of
+    // the step, `unit_weight` is the weight of the edge at strength 1.
+    pub fn input<T, W>(
+        &self,
+        i: usize,
+        edge: &Edge,
+        layer: usize,
+        arriving: [T; 2],
+        unit_weight: W,
+    ) -> Option<PlasticInput<T, W>> {
+        let plasticity = &self.rules[self.rule_of_edge[i]?];
+        if !plasticity.rule.is_smooth() {
+            return None;
+        }
+        Some(PlasticInput {
+            layer,
+            edge: i,
+            rule: plasticity.rule,
+            min_strength: plasticity.min_strength,
+            max_strength: plasticity.max_strength,
+            arriving,
+            unit_weight,
+            strength: edge.strength,
+            stage_strength: edge.strength,
+            stage_slope: 0.0,
+            change: 0.0,
+        })
+    }
+
+    // the event driven stdp changes of one step of length `dt`, after the nodes made their
+    // step: a spike is a jump the runge-kutta stages could not resolve. the smooth rules are
+    // integrated in the stages (`integration_methods::rk4_delay_layers`).
+    // `ends(i, edge)` gives the activity of the arriving value and of the target of edge i.
+    pub fn step<F>(&mut self, edges: &mut [Edge], dt: f64, ends: F)
+    where
+        F: Fn(usize, &Edge) -> (f64, f64),
+    {
+        for (i, e) in edges.iter_mut().enumerate() {
+            let Some(r) = self.rule_of_edge[i] else {
+                continue;
+            };
+            let plasticity = &self.rules[r];
+            let PlasticityRule::Stdp {
+                potentiation,
+                depression,
+                tau_plus,
+                tau_minus,
+                threshold,
+            } = plasticity.rule
+            else {
+                continue;
+            };
+            let (pre_activity, post_activity) = ends(i, e);
+            self.pre_trace[i] *= (-dt / tau_plus).exp();
+            self.post_trace[i] *= (-dt / tau_minus).exp();
+            let pre_spike = self.previous_pre[i] < threshold && pre_activity >= threshold;
+            let post_spike = self.previous_post[i] < threshold && post_activity >= threshold;
+            let mut change = 0.0;
+            if pre_spike {
+                self.pre_trace[i] += 1.0;
+                change -= depression * self.post_trace[i];
+            }
+            if post_spike {
+                self.post_trace[i] += 1.0;
+                change += potentiation * self.pre_trace[i];
+            }
+            self.previous_pre[i] = pre_activity;
+            self.previous_post[i] = post_activity;
+            e.strength =
+                (e.strength + change).clamp(plasticity.min_strength, plasticity.max_strength);
+        }
+    }
+}
+
+// a plastic edge with a smooth rule as its target node sees it during one runge-kutta step.
+// the node's stepping writes the new strength, the history takes it over afterwards.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub struct PlasticInput<T, W> {
+    pub layer: usize,
+    pub edge: usize, // index among the edges of the layer
+    pub rule: PlasticityRule,
+    pub min_strength: f64,
+    pub max_strength: f64,
+    pub arriving: [T; 2],
+    pub unit_weight: W,
+    pub strength: f64, // at the start of the step, after it the new one
+    // scratch of the stages
+    pub stage_strength: f64,
+    pub stage_slope: f64,
+    pub change: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Network;
+
+    fn two_nodes() -> Network {
+        let mut network = Network::new(2, 0.1, 0.0, 1.0, 0, 0.01);
+        network.put_edge(1, 0, 0.1, 0.0, 1.0);
+        network
+    }
+
+    #[test]
+    fn hebbian_strength_approaches_correlation() {
+        let hebbian = PlasticityRule::Hebbian {
+            rate: 1.0,
+            decay: 2.0,
+        };
+        let rate = |strength| hebbian.rate_of_change(strength, &1.0, &1.5);
+        assert_eq!(rate(0.75), 0.0);
+        assert!(rate(0.5) > 0.0 && rate(1.0) < 0.0);
+    }
+
+    #[test]
+    fn stdp_potentiates_causal_spikes() {
+        let stdp = Plasticity {
+            rule: PlasticityRule::Stdp {
+                potentiation: 0.1,
+                depression: 0.1,
+                tau_plus: 0.2,
+                tau_minus: 0.2,
+                threshold: 0.5,
+            },
+            selection: EdgeSelection::All,
+            min_strength: 0.0,
+            max_strength: 1.0,
+        };
+        // spikes of `first` 5 steps before the spikes of `second`
+        let run = |pre_first: bool| {
+            let mut network = two_nodes();
+            let mut plastic = PlasticEdges::new(vec![stdp.clone()], &network.edges);
+            for step in 0..100 {
+                let first = if step % 50 == 10 { 1.0 } else { 0.0 };
+                let second = if step % 50 == 15 { 1.0 } else { 0.0 };
+                let (pre, post) = if pre_first {
+                    (first, second)
+                } else {
+                    (second, first)
+                };
+                plastic.step(&mut network.edges, 0.01, |_, _| (pre, post));
+            }
+            network.edges[0].strength
+        };
+        assert!(run(true) > 0.1);
+        assert!(run(false) < 0.1);
+    }
+}
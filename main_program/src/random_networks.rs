@@ -0,0 +1,418 @@
+use crate::network::{Edge, Network};
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+use rand_distr::{Distribution, LogNormal, Normal};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// distribution that edge strengths, turns and delays of random networks are drawn from
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum ValueDistribution {
+    Constant(f64),
+    Uniform { low: f64, high: f64 },
+    Normal { mean: f64, std_dev: f64 },
+    LogNormal { mu: f64, sigma: f64 },
+}
+
+impl ValueDistribution {
+    pub fn sample(&self, rng: &mut SmallRng) -> f64 {
+        match *self {
+            ValueDistribution::Constant(value) => value,
+            ValueDistribution::Uniform { low, high } => {
+                if low < high {
+                    rng.gen_range(low..high)
+                } else {
+                    low
+                }
+            }
+            ValueDistribution::Normal { mean, std_dev } => Normal::new(mean, std_dev.abs())
+                .map(|normal| normal.sample(rng))
+                .unwrap_or(mean),
+            ValueDistribution::LogNormal { mu, sigma } => LogNormal::new(mu, sigma.abs())
+                .map(|log_normal| log_normal.sample(rng))
+                .unwrap_or(mu.exp()),
+        }
+    }
+}
+
+impl fmt::Display for ValueDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueDistribution::Constant(value) => write!(f, "{}", value),
+            ValueDistribution::Uniform { low, high } => write!(f, "uniform({}, {})", low, high),
+            ValueDistribution::Normal { mean, std_dev } => {
+                write!(f, "normal({}, {})", mean, std_dev)
+            }
+            ValueDistribution::LogNormal { mu, sigma } => {
+                write!(f, "log-normal({}, {})", mu, sigma)
+            }
+        }
+    }
+}
+
+// how the parameters of every newly generated edge are drawn
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct EdgeValues {
+    pub strength: ValueDistribution,
+    pub turn: ValueDistribution,
+    pub delay: ValueDistribution,
+}
+
+#[allow(dead_code)]
+impl EdgeValues {
+    pub fn constant(strength: f64, turn: f64, delay: f64) -> Self {
+        EdgeValues {
+            strength: ValueDistribution::Constant(strength),
+            turn: ValueDistribution::Constant(turn),
+            delay: ValueDistribution::Constant(delay),
+        }
+    }
+}
+
+impl fmt::Display for EdgeValues {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "strength: {}, turn: {}, delay: {}",
+            self.strength, self.turn, self.delay
+        )
+    }
+}
+
+// random topologies. every generator puts its edges into a new edge group whose name records
+// the generator's parameters. all randomness comes from the network's `rng`, so a topology
+// can be reproduced from the network's seed.
+#[allow(dead_code)]
+impl Network {
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+
+    fn insert_random_edge(&mut self, into: usize, from: usize, values: &EdgeValues) {
+        let strength = values.strength.sample(&mut self.rng);
+        let turn = (values.turn.sample(&mut self.rng) % 1.0 + 1.0) % 1.0;
+        let delay = values.delay.sample(&mut self.rng);
+        self.edges.push(Edge {
+            group: self.edge_groups.len(),
+            layer: 0,
+            into,
+            from,
+            strength,
+            turn,
+            delay,
+        });
+    }
+
+    // every directed pair (into, from) with into != from is connected with probability `p`
+    pub fn put_erdos_renyi(&mut self, p: f64, values: EdgeValues) {
+        for into in 0..self.nodes {
+            for from in 0..self.nodes {
+                if into != from && self.rng.gen_bool(p.clamp(0.0, 1.0)) {
+                    self.insert_random_edge(into, from, &values);
+                }
+            }
+        }
+        self.edge_groups
+            .push(format!("erdos-renyi (p: {}, {})", p, values));
+    }
+
+    // ring where each node is connected to its `neighbours` nearest nodes on either side,
+    // then every connection is rewired to a random node with probability `beta`.
+    // connections are bidirectional.
+    pub fn put_watts_strogatz(
+        &mut self,
+        neighbours: usize,
+        beta: f64,
+        values: EdgeValues,
+    ) -> Result<(), String> {
+        if 2 * neighbours >= self.nodes {
+            return Err(format!(
+                "watts-strogatz needs more than {} nodes for {} neighbours on each side",
+                2 * neighbours,
+                neighbours
+            ));
+        }
+        let mut connected = HashSet::<(usize, usize)>::new();
+        for n in 0..self.nodes {
+            for k in 1..=neighbours {
+                connected.insert(undirected(n, (n + k) % self.nodes));
+            }
+        }
+        let mut pairs: Vec<(usize, usize)> = Vec::new();
+        for n in 0..self.nodes {
+            for k in 1..=neighbours {
+                let mut pair = undirected(n, (n + k) % self.nodes);
+                if self.rng.gen_bool(beta.clamp(0.0, 1.0)) {
+                    let free: Vec<usize> = (0..self.nodes)
+                        .filter(|&m| m != n && !connected.contains(&undirected(n, m)))
+                        .collect();
+                    if let Some(&m) = free.choose(&mut self.rng) {
+                        connected.remove(&pair);
+                        pair = undirected(n, m);
+                        connected.insert(pair);
+                    }
+                }
+                pairs.push(pair);
+            }
+        }
+        for (a, b) in pairs {
+            self.insert_random_edge(a, b, &values);
+            self.insert_random_edge(b, a, &values);
+        }
+        self.edge_groups.push(format!(
+            "watts-strogatz (neighbours: {}, beta: {}, {})",
+            neighbours, beta, values
+        ));
+        Ok(())
+    }
+
+    // preferential attachment: starts with `m + 1` fully connected nodes, every further node
+    // connects to `m` distinct existing nodes chosen proportional to their degree.
+    // connections are bidirectional.
+    pub fn put_barabasi_albert(&mut self, m: usize, values: EdgeValues) -> Result<(), String> {
+        if m == 0 || m >= self.nodes {
+            return Err(format!(
+                "barabasi-albert needs 0 < m < nodes, got m = {} for {} nodes",
+                m, self.nodes
+            ));
+        }
+        let mut pairs: Vec<(usize, usize)> = Vec::new();
+        // every node appears once per connection, so drawing from it is proportional to degree
+        let mut degree_list: Vec<usize> = Vec::new();
+        for a in 0..=m {
+            for b in a + 1..=m {
+                pairs.push((a, b));
+                degree_list.push(a);
+                degree_list.push(b);
+            }
+        }
+        for n in m + 1..self.nodes {
+            let mut targets = HashSet::<usize>::new();
+            while targets.len() < m {
+                targets.insert(*degree_list.choose(&mut self.rng).unwrap());
+            }
+            let mut targets: Vec<usize> = targets.into_iter().collect();
+            targets.sort_unstable();
+            for t in targets {
+                pairs.push((n, t));
+                degree_list.push(n);
+                degree_list.push(t);
+            }
+        }
+        for (a, b) in pairs {
+            self.insert_random_edge(a, b, &values);
+            self.insert_random_edge(b, a, &values);
+        }
+        self.edge_groups
+            .push(format!("barabasi-albert (m: {}, {})", m, values));
+        Ok(())
+    }
+
+    // every node gets exactly `k` bidirectional connections. the stubs are paired at random
+    // (pairing model), self loops and double connections are then removed by switching them
+    // with random other pairs: (a, b), (c, d) -> (a, c), (b, d), which keeps the degrees. a
+    // whole new draw would be needed about exp((k * k - 1) / 4) times, already 10^4 for k = 6
+    pub fn put_random_regular(&mut self, k: usize, values: EdgeValues) -> Result<(), String> {
+        if k >= self.nodes || !(k * self.nodes).is_multiple_of(2) {
+            return Err(format!(
+                "no {}-regular graph with {} nodes exists",
+                k, self.nodes
+            ));
+        }
+        let mut stubs: Vec<usize> = (0..self.nodes)
+            .flat_map(|n| std::iter::repeat_n(n, k))
+            .collect();
+        stubs.shuffle(&mut self.rng);
+        let mut pairs: Vec<(usize, usize)> = stubs
+            .chunks(2)
+            .map(|pair| undirected(pair[0], pair[1]))
+            .collect();
+        let mut count = HashMap::<(usize, usize), usize>::new();
+        for &pair in &pairs {
+            *count.entry(pair).or_default() += 1;
+        }
+
+        const MAX_SWITCHES_PER_PAIR: usize = 100;
+        let mut switches_left = MAX_SWITCHES_PER_PAIR * pairs.len();
+        while let Some(i) =
+            (0..pairs.len()).find(|&i| pairs[i].0 == pairs[i].1 || count[&pairs[i]] > 1)
+        {
+            if switches_left == 0 {
+                return Err(format!(
+                    "could not remove the double connections of a {}-regular graph with {} nodes",
+                    k, self.nodes
+                ));
+            }
+            switches_left -= 1;
+            let j = self.rng.gen_range(0..pairs.len());
+            let ((a, b), (mut c, mut d)) = (pairs[i], pairs[j]);
+            if self.rng.gen_bool(0.5) {
+                std::mem::swap(&mut c, &mut d);
+            }
+            let switched = [undirected(a, c), undirected(b, d)];
+            if i == j
+                || switched[0] == switched[1]
+                || switched
+                    .iter()
+                    .any(|&(x, y)| x == y || count.contains_key(&(x, y)))
+            {
+                continue;
+            }
+            for old in [pairs[i], pairs[j]] {
+                let n = count.get_mut(&old).unwrap();
+                *n -= 1;
+                if *n == 0 {
+                    count.remove(&old);
+                }
+            }
+            for new in switched {
+                *count.entry(new).or_default() += 1;
+            }
+            [pairs[i], pairs[j]] = switched;
+        }
+
+        pairs.sort_unstable();
+        for (a, b) in pairs {
+            self.insert_random_edge(a, b, &values);
+            self.insert_random_edge(b, a, &values);
+        }
+        self.edge_groups
+            .push(format!("random regular (k: {}, {})", k, values));
+        Ok(())
+    }
+
+    // nodes are assigned to consecutive blocks of `block_sizes`. a directed edge from a node in
+    // block `b` into a node in block `a` exists with probability `probabilities[a][b]`.
+    pub fn put_stochastic_block_model(
+        &mut self,
+        block_sizes: &[usize],
+        probabilities: &[Vec<f64>],
+        values: EdgeValues,
+    ) -> Result<(), String> {
+        if block_sizes.iter().sum::<usize>() != self.nodes {
+            return Err(format!(
+                "block sizes {:?} do not add up to {} nodes",
+                block_sizes, self.nodes
+            ));
+        }
+        if probabilities.len() != block_sizes.len()
+            || probabilities
+                .iter()
+                .any(|row| row.len() != block_sizes.len())
+        {
+            return Err(format!(
+                "probabilities must be a {0}x{0} matrix",
+                block_sizes.len()
+            ));
+        }
+        let block_of: Vec<usize> = block_sizes
+            .iter()
+            .enumerate()
+            .flat_map(|(b, &size)| std::iter::repeat_n(b, size))
+            .collect();
+        for into in 0..self.nodes {
+            for from in 0..self.nodes {
+                let p = probabilities[block_of[into]][block_of[from]];
+                if into != from && self.rng.gen_bool(p.clamp(0.0, 1.0)) {
+                    self.insert_random_edge(into, from, &values);
+                }
+            }
+        }
+        self.edge_groups.push(format!(
+            "stochastic block model (blocks: {:?}, p: {:?}, {})",
+            block_sizes, probabilities, values
+        ));
+        Ok(())
+    }
+}
+
+fn undirected(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> EdgeValues {
+        EdgeValues {
+            strength: ValueDistribution::Uniform {
+                low: 0.05,
+                high: 0.15,
+            },
+            turn: ValueDistribution::Constant(0.0),
+            delay: ValueDistribution::Normal {
+                mean: 50.0,
+                std_dev: 5.0,
+            },
+        }
+    }
+
+    #[test]
+    fn reproducible_from_seed() {
+        let mut a = Network::new(50, 0.1, 0.0, 10.0, 7, 1.0 / 64.0);
+        let mut b = Network::new(50, 0.1, 0.0, 10.0, 7, 1.0 / 64.0);
+        a.put_erdos_renyi(0.1, values());
+        b.put_erdos_renyi(0.1, values());
+        assert!(!a.edges.is_empty());
+        assert_eq!(a.edges, b.edges);
+
+        b.reseed(8);
+        b.edges.clear();
+        b.put_erdos_renyi(0.1, values());
+        assert_ne!(a.edges, b.edges);
+    }
+
+    #[test]
+    fn regular_degrees() {
+        let mut network = Network::new(20, 0.1, 0.0, 10.0, 1, 1.0 / 64.0);
+        network.put_random_regular(3, values()).unwrap();
+        for n in 0..network.nodes {
+            assert_eq!(network.get_edges_into_node(n).len(), 3);
+        }
+        assert!(network.put_random_regular(3, values()).is_ok());
+        let mut odd = Network::new(7, 0.1, 0.0, 10.0, 1, 1.0 / 64.0);
+        assert!(odd.put_random_regular(3, values()).is_err());
+    }
+
+    #[test]
+    fn regular_reservoir_degrees() {
+        for (nodes, k) in [(300, 8), (300, 20), (200, 10), (8, 7)] {
+            let mut network = Network::new(nodes, 0.1, 0.0, 10.0, 2, 1.0 / 64.0);
+            network.put_random_regular(k, values()).unwrap();
+            let mut connected = HashSet::new();
+            for e in &network.edges {
+                assert_ne!(e.into, e.from);
+                assert!(connected.insert((e.into, e.from)));
+            }
+            for n in 0..nodes {
+                assert_eq!(network.get_edges_into_node(n).len(), k);
+            }
+        }
+    }
+
+    #[test]
+    fn small_world_and_scale_free_edge_counts() {
+        let mut network = Network::new(30, 0.1, 0.0, 10.0, 3, 1.0 / 64.0);
+        network.put_watts_strogatz(2, 0.2, values()).unwrap();
+        assert_eq!(network.edges.len(), 30 * 2 * 2);
+
+        let mut network = Network::new(30, 0.1, 0.0, 10.0, 3, 1.0 / 64.0);
+        network.put_barabasi_albert(2, values()).unwrap();
+        // 3 initial connections, then 2 per added node, each in both directions
+        assert_eq!(network.edges.len(), 2 * (3 + 2 * 27));
+        assert!(network.edges.iter().all(|e| e.into != e.from));
+    }
+
+    #[test]
+    fn blocks_without_cross_coupling() {
+        let mut network = Network::new(10, 0.1, 0.0, 10.0, 5, 1.0 / 64.0);
+        network
+            .put_stochastic_block_model(&[4, 6], &[vec![1.0, 0.0], vec![0.0, 1.0]], values())
+            .unwrap();
+        assert_eq!(network.edges.len(), 4 * 3 + 6 * 5);
+        assert!(network.edges.iter().all(|e| (e.into < 4) == (e.from < 4)));
+    }
+}
@@ -0,0 +1,450 @@
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::integration_methods::IntegrationMethods;
+use crate::lyapunov;
+use crate::parameter_sweep::{self, PointRecorder, ResolvedParameter, SweepParameter, SweepPoints};
+
+// states beyond this are counted as divergent
+const DIVERGENCE: f64 = 1e10;
+// time the perturbation of the lyapunov exponent needs to turn into the most unstable direction
+const SETTLE: f64 = 100.0;
+const REGIME_CODES: &str =
+    "0 fixed point, n > 0 periodic with n maxima, -1 quasi-periodic, -2 chaotic, -3 divergent";
+
+#[allow(dead_code)]
+#[derive(Clone, Deserialize)]
+pub struct ParameterRange {
+    pub parameter: SweepParameter,
+    pub from: f64,
+    pub to: f64,
+    pub values: usize,
+}
+
+// a two parameter scan. every grid point starts from the state of the calculation, sets both
+// parameters, discards `transient` time units and then records `variable` for `record` time
+// units, while the largest lyapunov exponent is estimated over intervals of
+// `lyapunov_interval`. the grid points are computed in parallel (`threads`, 0: all cores).
+// `file` gets the regime of every grid point (`Regime::code`), a row per value of `rows` and
+// a column per value of `columns`, `exponent_file` the exponents in the same layout.
+#[allow(dead_code)]
+#[derive(Clone, Deserialize)]
+pub struct RegimeMap {
+    pub columns: ParameterRange,
+    pub rows: ParameterRange,
+    pub transient: f64,
+    pub record: f64,
+    pub variable: usize, // index into the saved variables (`timeseries_curve_names`)
+    #[serde(default = "default_lyapunov_interval")]
+    pub lyapunov_interval: f64,
+    // relative to the range of the variable: maxima closer than this are the same, a
+    // variable that moves less than this (relative to its size) sits on a fixed point
+    #[serde(default = "default_tolerance")]
+    pub tolerance: f64,
+    // more distinct maxima than this are quasi-periodic
+    #[serde(default = "default_max_period")]
+    pub max_period: usize,
+    // exponents above this are chaotic
+    #[serde(default = "default_chaos_threshold")]
+    pub chaos_threshold: f64,
+    #[serde(default)]
+    pub threads: usize,
+    pub file: String,
+    #[serde(default)]
+    pub exponent_file: Option<String>,
+}
+
+fn default_lyapunov_interval() -> f64 {
+    1.0
+}
+
+fn default_tolerance() -> f64 {
+    1e-3
+}
+
+fn default_max_period() -> usize {
+    8
+}
+
+fn default_chaos_threshold() -> f64 {
+    0.005
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Regime {
+    FixedPoint,
+    Periodic(usize), // distinct maxima per period
+    QuasiPeriodic,
+    Chaotic,
+    Divergent,
+}
+
+#[allow(dead_code)]
+impl Regime {
+    // see `REGIME_CODES`
+    pub fn code(&self) -> i64 {
+        match self {
+            Regime::FixedPoint => 0,
+            Regime::Periodic(maxima) => *maxima as i64,
+            Regime::QuasiPeriodic => -1,
+            Regime::Chaotic => -2,
+            Regime::Divergent => -3,
+        }
+    }
+}
+
+// what one grid point found
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub struct GridPoint {
+    pub regime: Regime,
+    pub exponent: f64,
+}
+
+#[allow(dead_code)]
+impl RegimeMap {
+    // `build` creates a system like the calculation's, one per thread. `start` is the saved
+    // state every grid point begins with, the parameters are resolved on the current edges.
+    // the grid points row by row.
+    pub fn scan(
+        &self,
+        build: impl Fn() -> Box<dyn IntegrationMethods> + Sync,
+        start: &[u8],
+        columns: &ResolvedParameter,
+        rows: &ResolvedParameter,
+        dt: f64,
+    ) -> Result<Vec<GridPoint>, String> {
+        let column_values = parameter_sweep::values_between(
+            self.columns.from,
+            self.columns.to,
+            self.columns.values,
+        );
+        let row_values =
+            parameter_sweep::values_between(self.rows.from, self.rows.to, self.rows.values);
+        let points = column_values.len() * row_values.len();
+        let threads = match self.threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            threads => threads,
+        }
+        .min(points);
+
+        // every thread takes the next grid point that nobody has taken yet
+        let classifier = self.classifier();
+        let next = AtomicUsize::new(0);
+        let found = Mutex::new(vec![None; points]);
+        let work = || -> Result<(), String> {
+            let mut system = build();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= points {
+                    return Ok(());
+                }
+                system.restore_state(start)?;
+                columns.set(system.as_mut(), column_values[i % column_values.len()])?;
+                rows.set(system.as_mut(), row_values[i / column_values.len()])?;
+                let point = classifier.grid_point(system.as_mut(), dt)?;
+                found.lock().unwrap()[i] = Some(point);
+            }
+        };
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads).map(|_| scope.spawn(work)).collect();
+            handles.into_iter().try_for_each(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err("a thread of the regime map panicked".to_string()))
+            })
+        })?;
+        Ok(found
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|point| point.expect("every grid point is computed"))
+            .collect())
+    }
+
+    pub fn classifier(&self) -> Classifier {
+        Classifier {
+            transient: self.transient,
+            record: self.record,
+            variable: self.variable,
+            lyapunov_interval: self.lyapunov_interval,
+            tolerance: self.tolerance,
+            max_period: self.max_period,
+            chaos_threshold: self.chaos_threshold,
+        }
+    }
+
+    // the parameter values are written in front as comments
+    pub fn write(&self, points: &[GridPoint], data_directory: &str) -> Result<(), String> {
+        let path = Path::new(data_directory).join(&self.file);
+        self.write_matrix(points, &path, REGIME_CODES, |p| p.regime.code().to_string())?;
+        match &self.exponent_file {
+            Some(file) => {
+                let path = Path::new(data_directory).join(file);
+                self.write_matrix(points, &path, "largest lyapunov exponents", |p| {
+                    p.exponent.to_string()
+                })
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn write_matrix(
+        &self,
+        points: &[GridPoint],
+        path: &Path,
+        legend: &str,
+        entry: impl Fn(&GridPoint) -> String,
+    ) -> Result<(), String> {
+        let write_error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+        let mut file = BufWriter::new(File::create(path).map_err(write_error)?);
+        let header = |range: &ParameterRange| {
+            parameter_sweep::values_between(range.from, range.to, range.values)
+                .iter()
+                .map(f64::to_string)
+                .collect::<Vec<String>>()
+                .join("\t")
+        };
+        writeln!(
+            file,
+            "# {}\n# columns: {}\t{}\n# rows: {}\t{}",
+            legend,
+            self.columns.parameter.name(),
+            header(&self.columns),
+            self.rows.parameter.name(),
+            header(&self.rows)
+        )
+        .map_err(write_error)?;
+        for row in points.chunks(self.columns.values.max(1)) {
+            let line: Vec<String> = row.iter().map(&entry).collect();
+            writeln!(file, "{}", line.join("\t")).map_err(write_error)?;
+        }
+        file.flush().map_err(write_error)
+    }
+}
+
+// the part of a `RegimeMap` that is needed at a grid point. the parameters stay behind, their
+// edge selections cannot be shared between threads.
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub struct Classifier {
+    transient: f64,
+    record: f64,
+    variable: usize,
+    lyapunov_interval: f64,
+    tolerance: f64,
+    max_period: usize,
+    chaos_threshold: f64,
+}
+
+#[allow(dead_code)]
+impl Classifier {
+    fn grid_point(
+        &self,
+        system: &mut dyn IntegrationMethods,
+        dt: f64,
+    ) -> Result<GridPoint, String> {
+        // the last part of the transient (at most `SETTLE`) aligns the perturbation
+        let transient = (self.transient / dt) as usize;
+        let steps = ((self.lyapunov_interval / dt) as usize).max(1);
+        let settle = (SETTLE / dt) as usize / steps;
+        let settle = settle.min(transient / steps);
+        system.n_steps_rk4(transient - settle * steps);
+        let variables = system.timeseries_row_len();
+        let nodes = system.keep_state().len() / variables;
+        let mut maxima = PointRecorder::new(SweepPoints::Maxima, self.variable, nodes, variables);
+        let mut bounds = vec![(f64::MAX, f64::MIN); nodes];
+        let mut diverged = false;
+
+        let intervals = ((self.record / dt) as usize / steps).max(1);
+        let exponent =
+            lyapunov::largest_lyapunov_exponent(system, dt, steps, settle, intervals, |s| {
+                let state = s.keep_state();
+                diverged |= state
+                    .iter()
+                    .any(|x| !x.is_finite() || x.abs() >= DIVERGENCE);
+                for (node, (min, max)) in bounds.iter_mut().enumerate() {
+                    let x = state[node * variables + self.variable];
+                    *min = min.min(x);
+                    *max = max.max(x);
+                }
+                maxima.push(&state);
+            })?;
+        let regime = if diverged || !exponent.is_finite() {
+            Regime::Divergent
+        } else {
+            self.classify(&bounds, &maxima.found, exponent)
+        };
+        Ok(GridPoint { regime, exponent })
+    }
+
+    // `bounds` (min, max) of the variable of every node, `maxima` (node, maximum)
+    pub fn classify(
+        &self,
+        bounds: &[(f64, f64)],
+        maxima: &[(usize, f64)],
+        exponent: f64,
+    ) -> Regime {
+        let resting = bounds
+            .iter()
+            .all(|(min, max)| max - min <= self.tolerance * max.abs().max(min.abs()).max(1.0));
+        if resting || maxima.is_empty() {
+            return Regime::FixedPoint;
+        }
+        if exponent > self.chaos_threshold {
+            return Regime::Chaotic;
+        }
+        // the most distinct maxima of one node, every one of them has to come back
+        let mut period = 0;
+        for (node, (min, max)) in bounds.iter().enumerate() {
+            let mut of_node: Vec<f64> = maxima
+                .iter()
+                .filter(|(n, _)| *n == node)
+                .map(|(_, maximum)| *maximum)
+                .collect();
+            of_node.sort_by(f64::total_cmp);
+            let distinct = 1 + of_node
+                .windows(2)
+                .filter(|pair| pair[1] - pair[0] > self.tolerance * (max - min))
+                .count();
+            if of_node.len() < 2 * distinct {
+                return Regime::QuasiPeriodic;
+            }
+            period = period.max(distinct);
+        }
+        match period {
+            period if period <= self.max_period => Regime::Periodic(period),
+            _ => Regime::QuasiPeriodic,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculation::{Calculation, CouplingLayout, NodeSetup, SystemType, Tasks};
+    use crate::coupling_schedule::EdgeParameter;
+    use crate::edge_selection::EdgeSelection;
+    use crate::network::Network;
+    use std::fs;
+    use timeseries::SaveItems;
+
+    // mackey-glass over its delay (columns), the rows repeat the same values
+    fn delay_map() -> RegimeMap {
+        RegimeMap {
+            columns: ParameterRange {
+                parameter: SweepParameter::Edges {
+                    selection: EdgeSelection::All,
+                    parameter: EdgeParameter::Delay,
+                },
+                from: 2.0,
+                to: 17.0,
+                values: 6,
+            },
+            rows: ParameterRange {
+                parameter: SweepParameter::Model("n".to_string()),
+                from: 10.0,
+                to: 10.0,
+                values: 2,
+            },
+            transient: 500.0,
+            record: 500.0,
+            variable: 0,
+            lyapunov_interval: default_lyapunov_interval(),
+            tolerance: default_tolerance(),
+            max_period: default_max_period(),
+            chaos_threshold: default_chaos_threshold(),
+            threads: 3,
+            file: "regimes.txt".to_string(),
+            exponent_file: Some("exponents.txt".to_string()),
+        }
+    }
+
+    // the numbers of a matrix file
+    fn matrix(path: &Path) -> Vec<Vec<f64>> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.split('\t').map(|x| x.parse().unwrap()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn mackey_glass_regimes_over_the_delay() {
+        let dt = 1.0 / 64.0;
+        let directory = std::env::temp_dir().join("regime_map_mackey_glass");
+        let mut network = Network::new(1, 0.0, 0.0, 1.0, 0, dt);
+        network.put_edge(0, 0, 1.0, 0.0, 17.0);
+        let tasks = vec![Tasks::RegimeMap(delay_map())];
+        let mut calculation = Calculation::examples_in_directory(
+            &directory.to_string_lossy(),
+            dt,
+            &network,
+            256,
+            NodeSetup::Identical,
+            SystemType::MackeyGlass,
+            CouplingLayout::RingBuffers,
+            &tasks,
+            SaveItems::Timeseries,
+        )
+        .unwrap();
+        let before = calculation.system.save_state().unwrap();
+        calculation.perform_tasks().unwrap();
+        assert_eq!(calculation.system.save_state().unwrap(), before);
+
+        let regimes = matrix(&directory.join("regimes.txt"));
+        let exponents = matrix(&directory.join("exponents.txt"));
+        fs::remove_dir_all(directory).unwrap();
+        // delay 2: fixed point, 5 to 11: limit cycle, 14: period doubled, 17: chaos
+        let expected = [0.0, 1.0, 1.0, 1.0, 2.0, -2.0];
+        assert_eq!(regimes, [expected, expected]);
+        assert_eq!(exponents[0], exponents[1]);
+        assert!(exponents[0][0] < -0.1, "{:?}", exponents[0]);
+        for cycle in &exponents[0][1..5] {
+            assert!(cycle.abs() < 2e-3, "{:?}", exponents[0]);
+        }
+        // about 0.007 from the literature
+        assert!(
+            (0.004..0.01).contains(&exponents[0][5]),
+            "{:?}",
+            exponents[0]
+        );
+    }
+
+    #[test]
+    fn regimes_from_extrema() {
+        let classifier = delay_map().classifier();
+        let bounds = [(0.0, 1.0), (0.0, 1.0)];
+        let cycle = |values: &[f64]| -> Vec<(usize, f64)> {
+            (0..2)
+                .flat_map(|node| (0..30).map(move |i| (node, values[i % values.len()])))
+                .collect()
+        };
+        assert_eq!(
+            classifier.classify(&bounds, &cycle(&[1.0, 0.7, 0.9]), 0.0),
+            Regime::Periodic(3)
+        );
+        assert_eq!(
+            classifier.classify(&bounds, &cycle(&[1.0, 0.7, 0.9]), 0.1),
+            Regime::Chaotic
+        );
+        let drifting: Vec<f64> = (0..30).map(|i| 0.5 + i as f64 / 60.0).collect();
+        assert_eq!(
+            classifier.classify(&bounds, &cycle(&drifting), 0.0),
+            Regime::QuasiPeriodic
+        );
+        assert_eq!(
+            classifier.classify(&[(1.0, 1.0 + 1e-6)], &[(0, 1.0)], -0.1),
+            Regime::FixedPoint
+        );
+        assert_eq!(Regime::Periodic(3).code(), 3);
+        assert_eq!(Regime::Divergent.code(), -3);
+    }
+}
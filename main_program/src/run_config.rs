@@ -0,0 +1,762 @@
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use timeseries::SaveItems;
+
+use crate::calculation::{Calculation, CouplingLayout, NodeSetup, SystemType, Tasks};
+use crate::edge_selection::EdgeSelection;
+use crate::network::Network;
+use crate::network_validation::NetworkError;
+use crate::random_networks::EdgeValues;
+use crate::spatial_networks::{Boundary, LatticeDelay};
+use crate::{
+    fitzhugh_nagumo, hindmarsh_rose, lang_kobayashi, lorenz, mackey_glass, roessler, stuart_landau,
+};
+
+// a whole run in one json file, see the examples in ./configs. enums are written like in
+// rust (`{"MackeyGlass": {...}}`, `"Identical"`), only the network steps are named after the
+// `Network` methods they call (`{"put_ring": {...}}`).
+#[derive(Deserialize)]
+pub struct RunConfig {
+    pub system: SystemConfig,
+    #[serde(default)]
+    pub node_setup: NodeSetup,
+    #[serde(default)]
+    pub coupling_layout: CouplingLayout,
+    pub dt: f64,
+    pub segment_length: usize,
+    #[serde(default = "default_save_items")]
+    pub save_items: SaveItems,
+    #[serde(default = "default_data_directory")]
+    pub data_directory: String,
+    pub network: NetworkConfig,
+    pub tasks: Vec<Tasks>,
+}
+
+fn default_save_items() -> SaveItems {
+    SaveItems::Timeseries
+}
+
+fn default_data_directory() -> String {
+    "./data".to_string()
+}
+
+// the system and its model parameters, missing parameters keep their default values
+#[derive(Deserialize)]
+pub enum SystemConfig {
+    LangKobayashi(lang_kobayashi::Model),
+    Lorenz(lorenz::Model),
+    MackeyGlass(mackey_glass::Model),
+    HindmarshRose(hindmarsh_rose::Model),
+    StuartLandau(stuart_landau::Model),
+    FitzHughNagumo(fitzhugh_nagumo::Model),
+    Roessler(roessler::Model),
+}
+
+#[derive(Deserialize)]
+pub struct NetworkConfig {
+    pub nodes: usize,
+    #[serde(default)]
+    pub seed: u64,
+    // performed in this order on the empty network
+    #[serde(default)]
+    pub steps: Vec<NetworkStep>,
+    // combine parallel edges after the steps, see `Network::simplify_network`
+    #[serde(default)]
+    pub simplify: bool,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkStep {
+    PutEdge {
+        into: isize,
+        from: isize,
+        strength: f64,
+        turn: f64,
+        delay: f64,
+    },
+    PutDiag {
+        offset: i16,
+        strength: f64,
+        turn: f64,
+        delay: f64,
+    },
+    PutRing {
+        strength: f64,
+        turn: f64,
+        delay: f64,
+    },
+    PutRingReverse {
+        strength: f64,
+        turn: f64,
+        delay: f64,
+    },
+    PutBiRing {
+        strength: f64,
+        turn: f64,
+        delay: f64,
+    },
+    PutJumps {
+        offset: isize,
+        jump_separation: usize,
+        jump_length: isize,
+        strength: f64,
+        turn: f64,
+        delay: f64,
+    },
+    PutChain {
+        start: isize,
+        end: isize,
+        strength: f64,
+        turn: f64,
+        delay: f64,
+    },
+    PutErdosRenyi {
+        p: f64,
+        values: EdgeValues,
+    },
+    PutWattsStrogatz {
+        neighbours: usize,
+        beta: f64,
+        values: EdgeValues,
+    },
+    PutBarabasiAlbert {
+        m: usize,
+        values: EdgeValues,
+    },
+    PutRandomRegular {
+        k: usize,
+        values: EdgeValues,
+    },
+    PutStochasticBlockModel {
+        block_sizes: Vec<usize>,
+        probabilities: Vec<Vec<f64>>,
+        values: EdgeValues,
+    },
+    PutSquareLattice {
+        size: [usize; 2],
+        boundary: Boundary,
+        strength: f64,
+        turn: f64,
+        // a constant `delay`, or delays from the distance over `speed`
+        #[serde(default)]
+        delay: Option<f64>,
+        #[serde(default)]
+        speed: Option<f64>,
+    },
+    PutHexagonalLattice {
+        size: [usize; 2],
+        boundary: Boundary,
+        strength: f64,
+        turn: f64,
+        // a constant `delay`, or delays from the distance over `speed`
+        #[serde(default)]
+        delay: Option<f64>,
+        #[serde(default)]
+        speed: Option<f64>,
+    },
+    PutCubicLattice {
+        size: [usize; 3],
+        boundary: Boundary,
+        strength: f64,
+        turn: f64,
+        // a constant `delay`, or delays from the distance over `speed`
+        #[serde(default)]
+        delay: Option<f64>,
+        #[serde(default)]
+        speed: Option<f64>,
+    },
+    PutRandomGeometric {
+        radius: f64,
+        speed: f64,
+        dimensions: usize,
+        strength: f64,
+        turn: f64,
+    },
+    TurnAngles {
+        turn: f64,
+        #[serde(default)]
+        selection: EdgeSelection,
+    },
+    RandomizeStrength {
+        amount: f64,
+        #[serde(default)]
+        selection: EdgeSelection,
+    },
+    RandomizeAngle {
+        amount: f64,
+        #[serde(default)]
+        selection: EdgeSelection,
+    },
+    RandomizeDelayRelative {
+        amount: f64,
+        #[serde(default)]
+        selection: EdgeSelection,
+    },
+    Reseed {
+        seed: u64,
+    },
+    AddLayer {
+        name: String,
+    },
+    MoveToLayer {
+        selection: EdgeSelection,
+        layer: usize,
+    },
+}
+
+// a key the configuration does not know, e.g. a misspelled parameter
+#[derive(Debug, PartialEq)]
+pub struct UnknownKey {
+    pub line: Option<usize>,
+    pub path: String, // keys and array indices, separated by "."
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Json(serde_json::Error), // syntax errors, wrong types, missing keys, with line and column
+    UnknownKeys(Vec<UnknownKey>),
+    Network(String),
+    Setup(Vec<NetworkError>),
+    Model(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "could not read configuration: {}", error),
+            ConfigError::Json(error) => write!(f, "invalid configuration: {}", error),
+            ConfigError::UnknownKeys(keys) => {
+                write!(f, "unknown keys in configuration:")?;
+                for key in keys {
+                    match key.line {
+                        Some(line) => write!(f, "\n    line {}: {}", line, key.path)?,
+                        None => write!(f, "\n    {}", key.path)?,
+                    }
+                }
+                Ok(())
+            }
+            ConfigError::Network(message) => write!(f, "cannot build the network: {}", message),
+            ConfigError::Setup(errors) => {
+                write!(f, "the network cannot be integrated:")?;
+                for error in errors {
+                    write!(f, " {};", error)?;
+                }
+                Ok(())
+            }
+            ConfigError::Model(message) => write!(f, "invalid model parameters: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(error: serde_json::Error) -> Self {
+        ConfigError::Json(error)
+    }
+}
+
+#[allow(dead_code)]
+impl RunConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    // unknown keys are not ignored: all of them are reported, with their lines
+    pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+        let mut unknown = Vec::new();
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let config: RunConfig = serde_ignored::deserialize(&mut deserializer, |path| {
+            unknown.push(path_segments(&path))
+        })?;
+        deserializer.end()?;
+        if unknown.is_empty() {
+            return Ok(config);
+        }
+        let keys = key_lines(json);
+        Err(ConfigError::UnknownKeys(
+            unknown
+                .into_iter()
+                .map(|path| UnknownKey {
+                    line: line_of(&path, &keys),
+                    path: path.join("."),
+                })
+                .collect(),
+        ))
+    }
+
+    pub fn build_network(&self) -> Result<Network, ConfigError> {
+        let defaults = Network::default();
+        let mut network = Network::new(
+            self.network.nodes,
+            defaults.default_strength,
+            defaults.default_turn,
+            defaults.default_delay,
+            self.network.seed,
+            self.dt,
+        );
+        for (i, step) in self.network.steps.iter().enumerate() {
+            step.apply(&mut network)
+                .map_err(|message| ConfigError::Network(format!("step {}: {}", i, message)))?;
+        }
+        if self.network.simplify {
+            print!(
+                "{}",
+                self.system.system_type().simplify_network(&mut network)
+            );
+        }
+        Ok(network)
+    }
+
+    // the calculation borrows the network and the tasks, build the network with `build_network`
+    pub fn calculation<'a>(
+        &'a self,
+        network: &'a Network,
+    ) -> Result<Calculation<'a, 'a>, ConfigError> {
+        let mut calculation = Calculation::examples_in_directory(
+            &self.data_directory,
+            self.dt,
+            network,
+            self.segment_length,
+            self.node_setup,
+            self.system.system_type(),
+            self.coupling_layout,
+            &self.tasks,
+            self.save_items.clone(),
+        )
+        .map_err(ConfigError::Setup)?;
+        calculation
+            .system
+            .set_model_parameters(&self.system.parameters())
+            .map_err(ConfigError::Model)?;
+        Ok(calculation)
+    }
+}
+
+impl SystemConfig {
+    pub fn system_type(&self) -> SystemType {
+        match self {
+            SystemConfig::LangKobayashi(_) => SystemType::LangKobayashi,
+            SystemConfig::Lorenz(_) => SystemType::Lorenz,
+            SystemConfig::MackeyGlass(_) => SystemType::MackeyGlass,
+            SystemConfig::HindmarshRose(_) => SystemType::HindmarshRose,
+            SystemConfig::StuartLandau(_) => SystemType::StuartLandau,
+            SystemConfig::FitzHughNagumo(_) => SystemType::FitzHughNagumo,
+            SystemConfig::Roessler(_) => SystemType::Roessler,
+        }
+    }
+
+    // all parameters of the model, for `IntegrationMethods::set_model_parameters`
+    pub fn parameters(&self) -> serde_json::Value {
+        let value = match self {
+            SystemConfig::LangKobayashi(model) => serde_json::to_value(model),
+            SystemConfig::Lorenz(model) => serde_json::to_value(model),
+            SystemConfig::MackeyGlass(model) => serde_json::to_value(model),
+            SystemConfig::HindmarshRose(model) => serde_json::to_value(model),
+            SystemConfig::StuartLandau(model) => serde_json::to_value(model),
+            SystemConfig::FitzHughNagumo(model) => serde_json::to_value(model),
+            SystemConfig::Roessler(model) => serde_json::to_value(model),
+        };
+        value.expect("models consist of numbers")
+    }
+}
+
+impl NetworkStep {
+    pub fn apply(&self, network: &mut Network) -> Result<(), String> {
+        match self {
+            NetworkStep::PutEdge {
+                into,
+                from,
+                strength,
+                turn,
+                delay,
+            } => network.put_edge(*into, *from, *strength, *turn, *delay),
+            NetworkStep::PutDiag {
+                offset,
+                strength,
+                turn,
+                delay,
+            } => network.put_diag(*offset, *strength, *turn, *delay),
+            NetworkStep::PutRing {
+                strength,
+                turn,
+                delay,
+            } => network.put_ring(*strength, *turn, *delay),
+            NetworkStep::PutRingReverse {
+                strength,
+                turn,
+                delay,
+            } => network.put_ring_reverse(*strength, *turn, *delay),
+            NetworkStep::PutBiRing {
+                strength,
+                turn,
+                delay,
+            } => network.put_bi_ring(*strength, *turn, *delay),
+            NetworkStep::PutJumps {
+                offset,
+                jump_separation,
+                jump_length,
+                strength,
+                turn,
+                delay,
+            } => network.put_jumps(
+                *offset,
+                *jump_separation,
+                *jump_length,
+                *strength,
+                *turn,
+                *delay,
+            ),
+            NetworkStep::PutChain {
+                start,
+                end,
+                strength,
+                turn,
+                delay,
+            } => network.put_chain(*start, *end, *strength, *turn, *delay),
+            NetworkStep::PutErdosRenyi { p, values } => network.put_erdos_renyi(*p, *values),
+            NetworkStep::PutWattsStrogatz {
+                neighbours,
+                beta,
+                values,
+            } => network.put_watts_strogatz(*neighbours, *beta, *values)?,
+            NetworkStep::PutBarabasiAlbert { m, values } => {
+                network.put_barabasi_albert(*m, *values)?
+            }
+            NetworkStep::PutRandomRegular { k, values } => {
+                network.put_random_regular(*k, *values)?
+            }
+            NetworkStep::PutStochasticBlockModel {
+                block_sizes,
+                probabilities,
+                values,
+            } => network.put_stochastic_block_model(block_sizes, probabilities, *values)?,
+            NetworkStep::PutSquareLattice {
+                size,
+                boundary,
+                strength,
+                turn,
+                delay,
+                speed,
+            } => network.put_square_lattice(
+                *size,
+                *boundary,
+                *strength,
+                *turn,
+                lattice_delay(*delay, *speed)?,
+            )?,
+            NetworkStep::PutHexagonalLattice {
+                size,
+                boundary,
+                strength,
+                turn,
+                delay,
+                speed,
+            } => network.put_hexagonal_lattice(
+                *size,
+                *boundary,
+                *strength,
+                *turn,
+                lattice_delay(*delay, *speed)?,
+            )?,
+            NetworkStep::PutCubicLattice {
+                size,
+                boundary,
+                strength,
+                turn,
+                delay,
+                speed,
+            } => network.put_cubic_lattice(
+                *size,
+                *boundary,
+                *strength,
+                *turn,
+                lattice_delay(*delay, *speed)?,
+            )?,
+            NetworkStep::PutRandomGeometric {
+                radius,
+                speed,
+                dimensions,
+                strength,
+                turn,
+            } => network.put_random_geometric(*radius, *speed, *dimensions, *strength, *turn)?,
+            NetworkStep::TurnAngles { turn, selection } => {
+                network.turn_angles(*turn, selection.clone())
+            }
+            NetworkStep::RandomizeStrength { amount, selection } => {
+                network.randomize_strength(*amount, selection.clone())
+            }
+            NetworkStep::RandomizeAngle { amount, selection } => {
+                network.randomize_angle(*amount, selection.clone())
+            }
+            NetworkStep::RandomizeDelayRelative { amount, selection } => {
+                network.randomize_delay_relative(*amount, selection.clone())?
+            }
+            NetworkStep::Reseed { seed } => network.reseed(*seed),
+            NetworkStep::AddLayer { name } => {
+                network.add_layer(name);
+            }
+            NetworkStep::MoveToLayer { selection, layer } => {
+                network.move_to_layer(selection.clone(), *layer)
+            }
+        }
+        Ok(())
+    }
+}
+
+// lattices take either a constant delay or a propagation speed
+fn lattice_delay(delay: Option<f64>, speed: Option<f64>) -> Result<LatticeDelay, String> {
+    match (delay, speed) {
+        (Some(delay), None) => Ok(LatticeDelay::Constant(delay)),
+        (None, Some(speed)) => Ok(LatticeDelay::Speed(speed)),
+        _ => Err("a lattice needs either a `delay` or a propagation `speed`".to_string()),
+    }
+}
+
+// serde_ignored's path without the levels that do not appear as keys in json
+fn path_segments(path: &serde_ignored::Path) -> Vec<String> {
+    match path {
+        serde_ignored::Path::Root => Vec::new(),
+        serde_ignored::Path::Seq { parent, index } => {
+            let mut segments = path_segments(parent);
+            segments.push(index.to_string());
+            segments
+        }
+        serde_ignored::Path::Map { parent, key } => {
+            let mut segments = path_segments(parent);
+            segments.push(key.clone());
+            segments
+        }
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => path_segments(parent),
+    }
+}
+
+// the path (keys and array indices) and line of every key in a json text. the text has
+// already been parsed, so it is known to be valid.
+fn key_lines(json: &str) -> Vec<(Vec<String>, usize)> {
+    enum Level {
+        Object(Option<String>), // the current key
+        Array(usize),           // the current index
+    }
+    let mut levels: Vec<Level> = Vec::new();
+    let mut keys = Vec::new();
+    let mut line = 1;
+    let mut expect_key = false;
+    let mut chars = json.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            '{' => {
+                levels.push(Level::Object(None));
+                expect_key = true;
+            }
+            '[' => levels.push(Level::Array(0)),
+            '}' | ']' => {
+                levels.pop();
+                expect_key = false;
+            }
+            ',' => match levels.last_mut() {
+                Some(Level::Object(_)) => expect_key = true,
+                Some(Level::Array(index)) => *index += 1,
+                None => {}
+            },
+            '"' => {
+                let mut string = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => string.extend(chars.next()),
+                        c => string.push(c),
+                    }
+                }
+                if expect_key {
+                    expect_key = false;
+                    let mut path: Vec<String> = levels[..levels.len() - 1]
+                        .iter()
+                        .filter_map(|level| match level {
+                            Level::Object(key) => key.clone(),
+                            Level::Array(index) => Some(index.to_string()),
+                        })
+                        .collect();
+                    path.push(string.clone());
+                    keys.push((path, line));
+                    if let Some(Level::Object(key)) = levels.last_mut() {
+                        *key = Some(string);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    keys
+}
+
+// json has the keys of enum variants (`{"put_ring": {...}}`) which are missing in `path`:
+// the shortest key path that ends with the same key and contains `path` in order
+fn line_of(path: &[String], keys: &[(Vec<String>, usize)]) -> Option<usize> {
+    let contains_in_order = |key_path: &[String]| {
+        let mut segments = key_path.iter();
+        path.iter().all(|p| segments.any(|s| s == p))
+    };
+    keys.iter()
+        .filter(|(key_path, _)| key_path.last() == path.last() && contains_in_order(key_path))
+        .min_by_key(|(key_path, _)| key_path.len())
+        .map(|&(_, line)| line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIGS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/configs");
+
+    #[test]
+    fn example_configs_are_valid() {
+        let mut systems = Vec::new();
+        for entry in fs::read_dir(CONFIGS).unwrap() {
+            let path = entry.unwrap().path();
+            let mut config = RunConfig::load(&path)
+                .unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+            let directory = std::env::temp_dir().join(format!(
+                "run_config_example_{}",
+                path.file_stem().unwrap().to_string_lossy()
+            ));
+            config.data_directory = directory.to_string_lossy().to_string();
+            let network = config.build_network().unwrap();
+            let mut calculation = config
+                .calculation(&network)
+                .unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+            calculation.n_steps_rk4(16);
+            assert!(calculation
+                .system
+                .keep_state()
+                .iter()
+                .all(|x| x.is_finite()));
+            systems.push(config.system.system_type());
+            fs::remove_dir_all(directory).unwrap();
+        }
+        for system_type in [
+            SystemType::LangKobayashi,
+            SystemType::Lorenz,
+            SystemType::MackeyGlass,
+            SystemType::HindmarshRose,
+            SystemType::StuartLandau,
+            SystemType::FitzHughNagumo,
+            SystemType::Roessler,
+        ] {
+            assert!(
+                systems.contains(&system_type),
+                "no example for {:?}",
+                system_type
+            );
+        }
+    }
+
+    #[test]
+    fn lattices_take_a_delay_or_a_speed() {
+        let lattice = |delay: &str| {
+            let json = format!(
+                r#"{{"put_square_lattice": {{"size": [3, 3], "boundary": "Periodic",
+                    "strength": 0.1, "turn": 0.0{}}}}}"#,
+                delay
+            );
+            let step: NetworkStep = serde_json::from_str(&json).unwrap();
+            let mut network = Network::new(9, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+            step.apply(&mut network).map(|_| network.edges[0].delay)
+        };
+        assert_eq!(lattice(r#", "delay": 3.0"#), Ok(3.0));
+        assert_eq!(lattice(r#", "speed": 4.0"#), Ok(0.25));
+        assert!(lattice("").is_err());
+        assert!(lattice(r#", "delay": 3.0, "speed": 4.0"#).is_err());
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_with_lines() {
+        let json = r#"{
+    "system": {"MackeyGlass": {"beta_0": 0.2, "gama": 0.1}},
+    "dt": 0.015625,
+    "segment_length": 64,
+    "network": {
+        "nodes": 3,
+        "steps": [
+            {"put_ring": {"strength": 0.1, "turn": 0.0, "delay": 2.0}},
+            {"put_edge": {"into": 0, "from": 1, "strength": 0.1,
+                          "turn": 0.0, "delay": 2.0, "strenght": 0.2}}
+        ]
+    },
+    "tasks": [{"IntegrateUntilTimeNoSave": {"time": 1.0, "seed": 3}}],
+    "comment": "not read"
+}"#;
+        let Err(ConfigError::UnknownKeys(keys)) = RunConfig::from_json(json) else {
+            panic!("unknown keys are not reported");
+        };
+        let reported: Vec<(Option<usize>, &str)> =
+            keys.iter().map(|k| (k.line, k.path.as_str())).collect();
+        assert_eq!(
+            reported,
+            vec![
+                (Some(2), "system.gama"),
+                (Some(10), "network.steps.1.strenght"),
+                (Some(13), "tasks.0.seed"),
+                (Some(14), "comment"),
+            ]
+        );
+
+        let valid = json
+            .replace(", \"gama\": 0.1", "")
+            .replace(", \"strenght\": 0.2", "")
+            .replace(", \"seed\": 3", "")
+            .replace(",\n    \"comment\": \"not read\"", "");
+        let config = RunConfig::from_json(&valid).unwrap();
+        let network = config.build_network().unwrap();
+        assert_eq!(network.edges.len(), 4);
+        assert_eq!(config.system.parameters()["beta_0"], 0.2);
+    }
+
+    #[test]
+    fn model_parameters_reach_the_system() {
+        let directory = std::env::temp_dir().join("run_config_model_parameters");
+        let states = |model: &str| {
+            let json = format!(
+                r#"{{
+                    "system": {{"MackeyGlass": {}}},
+                    "dt": 0.015625,
+                    "segment_length": 64,
+                    "data_directory": "{}",
+                    "network": {{"nodes": 2, "steps": [
+                        {{"put_bi_ring": {{"strength": 0.1, "turn": 0.0, "delay": 2.0}}}}
+                    ]}},
+                    "tasks": []
+                }}"#,
+                model,
+                directory.to_string_lossy()
+            );
+            let config = RunConfig::from_json(&json).unwrap();
+            let network = config.build_network().unwrap();
+            let mut calculation = config.calculation(&network).unwrap();
+            calculation.n_steps_rk4(256);
+            calculation.system.keep_state()
+        };
+        assert_eq!(
+            states("{}"),
+            states(r#"{"beta_0": 0.2, "n": 10, "gamma": 0.1}"#)
+        );
+        assert_ne!(states("{}"), states(r#"{"beta_0": 0.3}"#));
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
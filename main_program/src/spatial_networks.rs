@@ -0,0 +1,500 @@
+use crate::network::{on_ring, Edge, Network};
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum Boundary {
+    Periodic, // opposite sides are connected like `put_ring` does in 1d
+    Open,
+}
+
+// delay of the lattice edges: the same for all of them, or the distance of the nodes divided by
+// a propagation speed
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum LatticeDelay {
+    Constant(f64),
+    Speed(f64),
+}
+
+// spatial topologies. lattice builders need `nodes` to match the lattice size, place every node
+// at its lattice site (spacing 1.0) in `positions` and connect nearest neighbours in both
+// directions, each neighbour pair once per direction. periodic lattices keep their cell in
+// `periodic_cell`, distances are measured to the nearest periodic image.
+#[allow(dead_code)]
+impl Network {
+    pub fn distance(&self, a: usize, b: usize) -> Result<f64, String> {
+        if a.max(b) >= self.positions.len() {
+            return Err(format!(
+                "no positions for nodes {} and {}, the network has {} positions",
+                a,
+                b,
+                self.positions.len()
+            ));
+        }
+        Ok(self.separation(a, b))
+    }
+
+    fn check_speed(speed: f64) -> Result<(), String> {
+        if speed > 0.0 && speed.is_finite() {
+            Ok(())
+        } else {
+            Err(format!(
+                "delays from distances need a positive speed, got {}",
+                speed
+            ))
+        }
+    }
+
+    // `distance` of nodes that have positions
+    fn separation(&self, a: usize, b: usize) -> f64 {
+        let (p, q) = (self.positions[a], self.positions[b]);
+        let difference = [p[0] - q[0], p[1] - q[1], p[2] - q[2]];
+        let length = |d: [f64; 3]| (d[0].powi(2) + d[1].powi(2) + d[2].powi(2)).sqrt();
+        let Some(cell) = self.periodic_cell else {
+            return length(difference);
+        };
+        // the nearest image is at most one cell away for the (reduced) cells of the lattices
+        let mut shortest = f64::INFINITY;
+        for k in 0..27 {
+            let shifts = [k % 3, k / 3 % 3, k / 9].map(|s| s as f64 - 1.0);
+            let mut image = difference;
+            for (shift, vector) in shifts.iter().zip(&cell) {
+                for d in 0..3 {
+                    image[d] += shift * vector[d];
+                }
+            }
+            shortest = shortest.min(length(image));
+        }
+        shortest
+    }
+
+    // ++++ ------+------+
+    //      |     |      |
+    //      +--(x,y)--(x+1,y)
+    //      |     |      |
+    pub fn put_square_lattice(
+        &mut self,
+        [width, height]: [usize; 2],
+        boundary: Boundary,
+        strength: f64,
+        turn: f64,
+        delay: LatticeDelay,
+    ) -> Result<(), String> {
+        self.put_lattice(
+            [width, height, 1],
+            &[[1, 0, 0], [0, 1, 0]],
+            boundary,
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            [strength, turn],
+            delay,
+        )?;
+        self.edge_groups.push(format!(
+            "square lattice {}x{} ({:?} boundary)",
+            width, height, boundary
+        ));
+        Ok(())
+    }
+
+    // six neighbours per node. rows are shifted by half a spacing, so the lattice has the shape
+    // of a rhombus, which makes periodic boundaries consistent in both directions.
+    pub fn put_hexagonal_lattice(
+        &mut self,
+        [width, height]: [usize; 2],
+        boundary: Boundary,
+        strength: f64,
+        turn: f64,
+        delay: LatticeDelay,
+    ) -> Result<(), String> {
+        self.put_lattice(
+            [width, height, 1],
+            &[[1, 0, 0], [0, 1, 0], [-1, 1, 0]],
+            boundary,
+            [[1.0, 0.0, 0.0], [0.5, 0.75f64.sqrt(), 0.0], [0.0, 0.0, 1.0]],
+            [strength, turn],
+            delay,
+        )?;
+        self.edge_groups.push(format!(
+            "hexagonal lattice {}x{} ({:?} boundary)",
+            width, height, boundary
+        ));
+        Ok(())
+    }
+
+    pub fn put_cubic_lattice(
+        &mut self,
+        [width, height, depth]: [usize; 3],
+        boundary: Boundary,
+        strength: f64,
+        turn: f64,
+        delay: LatticeDelay,
+    ) -> Result<(), String> {
+        self.put_lattice(
+            [width, height, depth],
+            &[[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+            boundary,
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            [strength, turn],
+            delay,
+        )?;
+        self.edge_groups.push(format!(
+            "cubic lattice {}x{}x{} ({:?} boundary)",
+            width, height, depth, boundary
+        ));
+        Ok(())
+    }
+
+    // site (x, y, z) is at x * `basis[0]` + y * `basis[1]` + z * `basis[2]`
+    fn put_lattice(
+        &mut self,
+        size: [usize; 3],
+        neighbour_offsets: &[[isize; 3]],
+        boundary: Boundary,
+        basis: [[f64; 3]; 3],
+        [strength, turn]: [f64; 2],
+        delay: LatticeDelay,
+    ) -> Result<(), String> {
+        if size.iter().product::<usize>() != self.nodes {
+            return Err(format!(
+                "a {}x{}x{} lattice needs {} nodes, the network has {}",
+                size[0],
+                size[1],
+                size[2],
+                size.iter().product::<usize>(),
+                self.nodes
+            ));
+        }
+        if let LatticeDelay::Speed(speed) = delay {
+            Self::check_speed(speed)?;
+        }
+        let index = |[x, y, z]: [usize; 3]| x + size[0] * (y + size[1] * z);
+
+        let site_position = |site: [f64; 3]| -> [f64; 3] {
+            let mut position = [0.0; 3];
+            for (coordinate, vector) in site.iter().zip(&basis) {
+                for d in 0..3 {
+                    position[d] += coordinate * vector[d];
+                }
+            }
+            position
+        };
+        self.positions = vec![[0.0; 3]; self.nodes];
+        for z in 0..size[2] {
+            for y in 0..size[1] {
+                for x in 0..size[0] {
+                    self.positions[index([x, y, z])] =
+                        site_position([x as f64, y as f64, z as f64]);
+                }
+            }
+        }
+        self.periodic_cell = match boundary {
+            Boundary::Periodic => Some([0, 1, 2].map(|d| {
+                let mut extent = [0.0; 3];
+                extent[d] = size[d] as f64;
+                site_position(extent)
+            })),
+            Boundary::Open => None,
+        };
+
+        let mut pairs: Vec<(usize, usize)> = Vec::new();
+        let mut connected = HashSet::<(usize, usize)>::new();
+        for z in 0..size[2] {
+            for y in 0..size[1] {
+                for x in 0..size[0] {
+                    for offset in neighbour_offsets {
+                        let site = [x, y, z];
+                        let mut neighbour = [0; 3];
+                        let mut inside = true;
+                        for d in 0..3 {
+                            let shifted = site[d] as isize + offset[d];
+                            neighbour[d] = on_ring(shifted, size[d]);
+                            inside &= shifted >= 0 && shifted < size[d] as isize;
+                        }
+                        let (a, b) = (index(site), index(neighbour));
+                        // lattices with one or two sites along a direction would otherwise
+                        // produce self loops and doubled connections
+                        if a != b
+                            && (inside || boundary == Boundary::Periodic)
+                            && connected.insert((a.min(b), a.max(b)))
+                        {
+                            pairs.push((a, b));
+                        }
+                    }
+                }
+            }
+        }
+
+        let group = self.edge_groups.len();
+        for (a, b) in pairs {
+            let delay = match delay {
+                LatticeDelay::Constant(delay) => delay,
+                LatticeDelay::Speed(speed) => self.separation(a, b) / speed,
+            };
+            for (into, from) in [(a, b), (b, a)] {
+                self.edges.push(Edge {
+                    group,
+                    layer: 0,
+                    into,
+                    from,
+                    strength,
+                    turn,
+                    delay,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // random geometric graph: nodes without `positions` are placed uniformly in the unit
+    // square (`dimensions` = 2) or unit cube (3). all pairs closer than `radius` are connected
+    // in both directions with delay = distance / `speed`.
+    pub fn put_random_geometric(
+        &mut self,
+        radius: f64,
+        speed: f64,
+        dimensions: usize,
+        strength: f64,
+        turn: f64,
+    ) -> Result<(), String> {
+        if !(1..=3).contains(&dimensions) {
+            return Err(format!(
+                "random geometric graphs are placed in 1 to 3 dimensions, got {}",
+                dimensions
+            ));
+        }
+        Self::check_speed(speed)?;
+        if self.positions.len() != self.nodes {
+            self.positions = (0..self.nodes)
+                .map(|_| {
+                    let mut p = [0.0; 3];
+                    for coordinate in p.iter_mut().take(dimensions) {
+                        *coordinate = self.rng.gen_range(0.0..1.0);
+                    }
+                    p
+                })
+                .collect();
+            self.periodic_cell = None;
+        }
+        let group = self.edge_groups.len();
+        for a in 0..self.nodes {
+            for b in a + 1..self.nodes {
+                let distance = self.separation(a, b);
+                if distance < radius {
+                    for (into, from) in [(a, b), (b, a)] {
+                        self.edges.push(Edge {
+                            group,
+                            layer: 0,
+                            into,
+                            from,
+                            strength,
+                            turn,
+                            delay: distance / speed,
+                        });
+                    }
+                }
+            }
+        }
+        self.edge_groups.push(format!(
+            "random geometric (radius: {}, speed: {}, {}d)",
+            radius, speed, dimensions
+        ));
+        Ok(())
+    }
+
+    // recompute the delays of a group's edges from the distance of their nodes
+    pub fn delays_from_distance(&mut self, group: usize, speed: f64) -> Result<(), String> {
+        Self::check_speed(speed)?;
+        if self.positions.len() != self.nodes {
+            return Err(format!(
+                "delays from distances need a position for each of the {} nodes, there are {}",
+                self.nodes,
+                self.positions.len()
+            ));
+        }
+        for i in 0..self.edges.len() {
+            let e = self.edges[i];
+            if e.group == group {
+                self.edges[i].delay = self.separation(e.into, e.from) / speed;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::delay_steps;
+
+    fn degrees(network: &Network) -> Vec<usize> {
+        (0..network.nodes)
+            .map(|n| network.get_edges_into_node(n).len())
+            .collect()
+    }
+
+    #[test]
+    fn square_lattice_degrees() {
+        let mut periodic = Network::new(12, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        periodic
+            .put_square_lattice(
+                [4, 3],
+                Boundary::Periodic,
+                0.1,
+                0.0,
+                LatticeDelay::Constant(1.0),
+            )
+            .unwrap();
+        assert!(degrees(&periodic).iter().all(|&d| d == 4));
+
+        let mut open = Network::new(12, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        open.put_square_lattice(
+            [4, 3],
+            Boundary::Open,
+            0.1,
+            0.0,
+            LatticeDelay::Constant(1.0),
+        )
+        .unwrap();
+        let open_degrees = degrees(&open);
+        assert_eq!(open_degrees[0], 2); // corner
+        assert_eq!(open_degrees[1], 3); // edge
+        assert_eq!(open_degrees[5], 4); // bulk
+
+        let mut wrong_size = Network::new(10, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        assert!(wrong_size
+            .put_square_lattice(
+                [4, 3],
+                Boundary::Open,
+                0.1,
+                0.0,
+                LatticeDelay::Constant(1.0)
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn hexagonal_and_cubic_lattice_degrees() {
+        let mut hexagonal = Network::new(16, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        hexagonal
+            .put_hexagonal_lattice(
+                [4, 4],
+                Boundary::Periodic,
+                0.1,
+                0.0,
+                LatticeDelay::Constant(1.0),
+            )
+            .unwrap();
+        assert!(degrees(&hexagonal).iter().all(|&d| d == 6));
+        // all neighbours are one spacing apart, across the periodic boundary too
+        for e in &hexagonal.edges {
+            assert!((hexagonal.distance(e.into, e.from).unwrap() - 1.0).abs() < 1e-12);
+        }
+
+        let mut cubic = Network::new(27, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        cubic
+            .put_cubic_lattice(
+                [3, 3, 3],
+                Boundary::Periodic,
+                0.1,
+                0.0,
+                LatticeDelay::Constant(1.0),
+            )
+            .unwrap();
+        assert!(degrees(&cubic).iter().all(|&d| d == 6));
+    }
+
+    #[test]
+    fn geometric_delays_follow_distance() {
+        let mut network = Network::new(40, 0.1, 0.0, 1.0, 11, 1.0 / 64.0);
+        network
+            .put_random_geometric(0.3, 0.01, 2, 0.1, 0.0)
+            .unwrap();
+        assert!(!network.edges.is_empty());
+        for e in &network.edges {
+            let distance = network.distance(e.into, e.from).unwrap();
+            assert!(distance < 0.3);
+            assert_eq!(e.delay, distance / 0.01);
+            assert_eq!(network.positions[e.into][2], 0.0);
+        }
+    }
+
+    #[test]
+    fn lattice_delays_from_the_nearest_image() {
+        let speed = LatticeDelay::Speed(4.0);
+        let mut square = Network::new(12, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        square
+            .put_square_lattice([4, 3], Boundary::Periodic, 0.1, 0.0, speed)
+            .unwrap();
+        // the wrap-around edges, e.g. from x = 3 to x = 0, are one spacing long as well
+        assert!(square.edges.iter().all(|e| e.delay == 0.25));
+        assert_eq!(square.distance(0, 3), Ok(1.0));
+        assert_eq!(square.distance(0, 2), Ok(2.0));
+
+        let mut hexagonal = Network::new(20, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        hexagonal
+            .put_hexagonal_lattice([5, 4], Boundary::Periodic, 0.1, 0.0, speed)
+            .unwrap();
+        assert!(hexagonal
+            .edges
+            .iter()
+            .all(|e| (e.delay - 0.25).abs() < 1e-12));
+
+        // without periodic boundaries the far side is far away
+        let mut open = Network::new(12, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        open.put_square_lattice([4, 3], Boundary::Open, 0.1, 0.0, speed)
+            .unwrap();
+        assert_eq!(open.distance(0, 3), Ok(3.0));
+        open.delays_from_distance(0, 1.0).unwrap();
+        assert!(open.edges.iter().all(|e| e.delay == 1.0));
+    }
+
+    #[test]
+    fn distances_need_positions() {
+        let mut network = Network::new(3, 0.1, 0.0, 1.0, 0, 1.0 / 64.0);
+        network.put_ring(0.1, 0.0, 1.0);
+        assert!(network.distance(0, 1).is_err());
+        assert!(network.delays_from_distance(0, 1.0).is_err());
+    }
+
+    #[test]
+    fn uniform_lattices_have_one_step_count() {
+        // 1 / (1/3) is a few ulps short of 3 for some of the edges, 192 steps of 1/64
+        let dt = 1.0 / 64.0;
+        let mut hexagonal = Network::new(20, 0.1, 0.0, 1.0, 0, dt);
+        hexagonal
+            .put_hexagonal_lattice(
+                [5, 4],
+                Boundary::Periodic,
+                0.1,
+                0.0,
+                LatticeDelay::Speed(1.0 / 3.0),
+            )
+            .unwrap();
+        assert!(hexagonal.edges.iter().any(|e| e.delay / dt < 192.0));
+        assert!(hexagonal
+            .edges
+            .iter()
+            .all(|e| delay_steps(e.delay, dt) == 192));
+        assert!(hexagonal.validate().is_ok());
+    }
+
+    #[test]
+    fn geometric_graphs_need_dimensions_and_speed() {
+        let mut network = Network::new(10, 0.1, 0.0, 1.0, 3, 1.0 / 64.0);
+        assert!(network.put_random_geometric(0.5, 1.0, 0, 0.1, 0.0).is_err());
+        assert!(network.put_random_geometric(0.5, 1.0, 4, 0.1, 0.0).is_err());
+        for speed in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(network
+                .put_random_geometric(0.5, speed, 2, 0.1, 0.0)
+                .is_err());
+            assert!(network.delays_from_distance(0, speed).is_err());
+        }
+        let speed = LatticeDelay::Speed(0.0);
+        assert!(network
+            .put_square_lattice([5, 2], Boundary::Open, 0.1, 0.0, speed)
+            .is_err());
+        assert!(network.edges.is_empty() && network.positions.is_empty());
+    }
+}
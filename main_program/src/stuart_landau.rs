@@ -1,6 +1,7 @@
-use crate::dynamical_system::{DynamicalSystem, Feedback, WeightComplex};
+use crate::dynamical_system::{Components, DynamicalSystem, Feedback, WeightComplex};
 use derive_more::{Add, AddAssign, Div, Mul, MulAssign};
 use num_complex::Complex;
+use serde::{Deserialize, Serialize};
 
 pub struct System {}
 impl DynamicalSystem for System {
@@ -9,6 +10,9 @@ impl DynamicalSystem for System {
     fn keep_state(state: &Self::StateT) -> Vec<f64> {
         vec![state.z.norm_sqr()]
     }
+    fn keep_state_into(state: &Self::StateT, values: &mut [f64]) {
+        values.copy_from_slice(&[state.z.norm_sqr()])
+    }
     fn keep_state_names() -> &'static [&'static str] {
         &["z"]
     }
@@ -37,11 +41,23 @@ impl Feedback for System {
 
 pub type FeedbackState = Complex<f64>;
 
-#[derive(Copy, Clone, Add, AddAssign, Mul, MulAssign, Div, Debug)]
+#[derive(Copy, Clone, Add, AddAssign, Mul, MulAssign, Div, Debug, Serialize, Deserialize)]
 pub struct State {
     pub z: Complex<f64>,
 }
 
+impl Components for State {
+    const LEN: usize = 2;
+    fn write_components(&self, into: &mut [f64]) {
+        into.copy_from_slice(&[self.z.re, self.z.im]);
+    }
+    fn from_components(components: &[f64]) -> Self {
+        State {
+            z: Complex::new(components[0], components[1]),
+        }
+    }
+}
+
 impl Default for State {
     fn default() -> Self {
         State {
@@ -50,7 +66,8 @@ impl Default for State {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Model {
     pub lambda: f64,
     pub omega: f64,
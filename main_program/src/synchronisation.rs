@@ -0,0 +1,512 @@
+use num_complex::Complex;
+use serde::Deserialize;
+use std::f64::consts::TAU;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const SYNCHRONY_FILE: &str = "synchrony.txt";
+const CORRELATION_FILE: &str = "cross_correlation.txt";
+const LAG_FILE: &str = "cross_correlation_lag.txt";
+const CLUSTER_FILE: &str = "clusters.txt";
+
+// saves `segments` segments like `IntegrateSegmentsAndSave` and measures how synchronous the
+// nodes are in every one of them:
+// - the kuramoto order parameter of the phases of `variable` (`Phases`)
+// - the synchronisation error, the distance of the nodes from their mean state
+// - the cross-correlation of `variable` for every pair of nodes, the largest one for lags up to
+//   `max_lag` (at most half a segment) and the lag where it is reached
+// - clusters of nodes that move together: the distance of two nodes, every variable relative to
+//   its standard deviation in the segment, is below `cluster_tolerance`
+// the results are written to the data directory, a line per segment.
+#[allow(dead_code)]
+#[derive(Clone, Deserialize)]
+pub struct SynchronisationAnalysis {
+    pub segments: usize,
+    pub epsilon: f64,
+    pub variable: usize, // index into the saved variables (`timeseries_curve_names`)
+    #[serde(default)]
+    pub phases: Phases,
+    #[serde(default = "default_max_lag")]
+    pub max_lag: f64,
+    #[serde(default = "default_cluster_tolerance")]
+    pub cluster_tolerance: f64,
+}
+
+fn default_max_lag() -> f64 {
+    10.0
+}
+
+fn default_cluster_tolerance() -> f64 {
+    1e-3
+}
+
+// how the phase of a node is taken from its curve
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub enum Phases {
+    // grows by 2 pi from one maximum of `variable` to the next, linear in between. only known
+    // between the first and the last maximum of the segment.
+    #[default]
+    Maxima,
+    // the angle of (`variable`, `second`) around their mean in the segment, e.g. the real and
+    // imaginary part of an oscillator
+    Angle {
+        second: usize,
+    },
+}
+
+// the measures of one segment, the matrices are nodes x nodes in rows
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct Synchrony {
+    pub order: f64, // kuramoto order parameter, the mean over the segment, nan without phases
+    pub min_order: f64, // its smallest value
+    pub error: f64, // synchronisation error, the mean over the segment
+    pub max_error: f64,
+    pub correlations: Vec<f64>, // the largest cross-correlation, nan for constant curves
+    pub lags: Vec<f64>,         // node j lags behind node i by `lags[i * nodes + j]`
+    pub clusters: Vec<usize>,   // every node's cluster, named by its first node
+}
+
+#[allow(dead_code)]
+impl Synchrony {
+    pub fn number_of_clusters(&self) -> usize {
+        self.clusters
+            .iter()
+            .enumerate()
+            .filter(|(node, cluster)| node == *cluster)
+            .count()
+    }
+}
+
+#[allow(dead_code)]
+impl SynchronisationAnalysis {
+    pub fn check(&self, nodes: usize, variables: &[&str]) -> Result<(), String> {
+        if nodes < 2 {
+            return Err("the synchronisation of a single node can not be measured".to_string());
+        }
+        let second = match self.phases {
+            Phases::Angle { second } => second,
+            Phases::Maxima => self.variable,
+        };
+        if self.variable.max(second) >= variables.len() {
+            return Err(format!(
+                "the synchronisation analysis uses variable {} of only {} ({:?})",
+                self.variable.max(second),
+                variables.len(),
+                variables
+            ));
+        }
+        Ok(())
+    }
+
+    // `segment` as kept by `Timeseries`: a row per step with the saved variables of all nodes
+    pub fn analyse(&self, segment: &[Vec<f64>], nodes: usize, dt: f64) -> Synchrony {
+        let row_len = segment[0].len() / nodes;
+        let curve = |node: usize, variable: usize| -> Vec<f64> {
+            segment
+                .iter()
+                .map(|row| row[node * row_len + variable])
+                .collect()
+        };
+        let curves: Vec<Vec<f64>> = (0..nodes).map(|node| curve(node, self.variable)).collect();
+
+        let phases: Vec<Vec<f64>> = match self.phases {
+            Phases::Maxima => curves.iter().map(|c| phases_from_maxima(c)).collect(),
+            Phases::Angle { second } => (0..nodes)
+                .map(|node| phases_from_angle(&curves[node], &curve(node, second)))
+                .collect(),
+        };
+        let (order, min_order) = kuramoto_order(&phases);
+        let (error, max_error) = synchronisation_error(segment, nodes);
+        let max_lag = ((self.max_lag / dt) as usize).min(segment.len() / 2);
+        let (correlations, lags) = cross_correlations(&curves, max_lag);
+        Synchrony {
+            order,
+            min_order,
+            error,
+            max_error,
+            correlations,
+            lags: lags.into_iter().map(|lag| lag as f64 * dt).collect(),
+            clusters: clusters(segment, nodes, self.cluster_tolerance),
+        }
+    }
+}
+
+// phase of a curve from its maxima, nan before the first and after the last one
+fn phases_from_maxima(curve: &[f64]) -> Vec<f64> {
+    let maxima: Vec<usize> = (1..curve.len().saturating_sub(1))
+        .filter(|&i| curve[i - 1] < curve[i] && curve[i] >= curve[i + 1])
+        .collect();
+    let mut phases = vec![f64::NAN; curve.len()];
+    for (k, pair) in maxima.windows(2).enumerate() {
+        let (from, to) = (pair[0], pair[1]);
+        for (i, phase) in phases.iter_mut().enumerate().take(to + 1).skip(from) {
+            *phase = TAU * (k as f64 + (i - from) as f64 / (to - from) as f64);
+        }
+    }
+    phases
+}
+
+fn phases_from_angle(first: &[f64], second: &[f64]) -> Vec<f64> {
+    let (first_mean, second_mean) = (mean(first), mean(second));
+    first
+        .iter()
+        .zip(second)
+        .map(|(x, y)| (y - second_mean).atan2(x - first_mean))
+        .collect()
+}
+
+// mean and smallest |sum of e^(i phase)| / nodes over the steps where every node has a phase
+fn kuramoto_order(phases: &[Vec<f64>]) -> (f64, f64) {
+    let orders: Vec<f64> = (0..phases[0].len())
+        .filter(|&t| phases.iter().all(|p| p[t].is_finite()))
+        .map(|t| {
+            let sum: Complex<f64> = phases.iter().map(|p| Complex::from_polar(1.0, p[t])).sum();
+            sum.norm() / phases.len() as f64
+        })
+        .collect();
+    match orders.is_empty() {
+        true => (f64::NAN, f64::NAN),
+        false => (
+            mean(&orders),
+            orders.iter().copied().fold(f64::INFINITY, f64::min),
+        ),
+    }
+}
+
+// mean and largest sqrt(sum over the nodes of |x_j - mean x|^2 / nodes), all saved variables
+fn synchronisation_error(segment: &[Vec<f64>], nodes: usize) -> (f64, f64) {
+    let row_len = segment[0].len() / nodes;
+    let errors: Vec<f64> = segment
+        .iter()
+        .map(|row| {
+            let squares: f64 = (0..row_len)
+                .map(|v| {
+                    let values = row.iter().skip(v).step_by(row_len);
+                    let mean = values.clone().sum::<f64>() / nodes as f64;
+                    values.map(|x| (x - mean) * (x - mean)).sum::<f64>()
+                })
+                .sum();
+            (squares / nodes as f64).sqrt()
+        })
+        .collect();
+    // nan once a node diverged
+    let max_error = errors
+        .iter()
+        .copied()
+        .fold(0.0, |max, e| if e > max || e.is_nan() { e } else { max });
+    (mean(&errors), max_error)
+}
+
+// for every pair the largest correlation of x_i(t) and x_j(t + lag), |lag| <= `max_lag` steps,
+// and its lag. every lag is correlated over the part of the segment both curves cover.
+fn cross_correlations(curves: &[Vec<f64>], max_lag: usize) -> (Vec<f64>, Vec<isize>) {
+    let nodes = curves.len();
+    let mut correlations = vec![f64::NAN; nodes * nodes];
+    let mut lags = vec![0; nodes * nodes];
+    for i in 0..nodes {
+        for j in i..nodes {
+            let (x, y) = (&curves[i], &curves[j]);
+            let mut best = (f64::NEG_INFINITY, 0);
+            for lag in -(max_lag as isize)..=max_lag as isize {
+                let overlap = x.len() - lag.unsigned_abs();
+                let correlation = match lag >= 0 {
+                    true => pearson(&x[..overlap], &y[lag as usize..]),
+                    false => pearson(&x[lag.unsigned_abs()..], &y[..overlap]),
+                };
+                if correlation > best.0 {
+                    best = (correlation, lag);
+                }
+            }
+            // the correlation with a constant curve is nan, never larger than -inf
+            if best.0.is_finite() {
+                correlations[i * nodes + j] = best.0;
+                correlations[j * nodes + i] = best.0;
+                lags[i * nodes + j] = best.1;
+                lags[j * nodes + i] = -best.1;
+            }
+        }
+    }
+    (correlations, lags)
+}
+
+fn pearson(x: &[f64], y: &[f64]) -> f64 {
+    let (x_mean, y_mean) = (mean(x), mean(y));
+    let (mut xy, mut xx, mut yy) = (0.0, 0.0, 0.0);
+    for (a, b) in x.iter().zip(y) {
+        let (a, b) = (a - x_mean, b - y_mean);
+        xy += a * b;
+        xx += a * a;
+        yy += b * b;
+    }
+    xy / (xx * yy).sqrt()
+}
+
+// nodes closer than `tolerance` are in the same cluster, and so are the nodes close to any of
+// its nodes. variables that are the same constant everywhere are left out, diverged nodes are
+// clusters of their own.
+fn clusters(segment: &[Vec<f64>], nodes: usize, tolerance: f64) -> Vec<usize> {
+    let row_len = segment[0].len() / nodes;
+    let scales: Vec<f64> = (0..row_len)
+        .map(|v| {
+            let values: Vec<f64> = segment
+                .iter()
+                .flat_map(|row| row.iter().skip(v).step_by(row_len))
+                .copied()
+                .collect();
+            let mean = mean(&values);
+            (values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / values.len() as f64).sqrt()
+        })
+        .collect();
+    let distance = |i: usize, j: usize| -> f64 {
+        let squares: f64 = segment
+            .iter()
+            .map(|row| {
+                (0..row_len)
+                    .filter(|&v| scales[v] != 0.0)
+                    .map(|v| ((row[i * row_len + v] - row[j * row_len + v]) / scales[v]).powi(2))
+                    .sum::<f64>()
+            })
+            .sum();
+        (squares / (segment.len() * row_len) as f64).sqrt()
+    };
+
+    let mut clusters: Vec<usize> = (0..nodes).collect();
+    for i in 0..nodes {
+        for j in i + 1..nodes {
+            if distance(i, j) < tolerance {
+                let (a, b) = (clusters[i], clusters[j]);
+                let (first, other) = (a.min(b), a.max(b));
+                clusters
+                    .iter_mut()
+                    .filter(|c| **c == other)
+                    .for_each(|c| *c = first);
+            }
+        }
+    }
+    clusters
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+// the result files, a line per segment that starts with the segment and its time
+pub struct SynchronisationFiles {
+    paths: Vec<PathBuf>,
+    files: Vec<BufWriter<File>>,
+}
+
+#[allow(dead_code)]
+impl SynchronisationFiles {
+    // new files, or with `segments` > 0 the files of a resumed run, the lines of later segments
+    // are dropped
+    pub fn open(data_directory: &str, nodes: usize, segments: usize) -> Result<Self, String> {
+        let node_columns = (0..nodes)
+            .map(|node| format!("{:02}", node))
+            .collect::<Vec<String>>()
+            .join("\t");
+        let matrix_columns = format!("{} rows of {} columns", nodes, nodes);
+        let headers = [
+            (
+                SYNCHRONY_FILE,
+                "order\tmin_order\terror\tmax_error\tclusters".to_string(),
+            ),
+            (CORRELATION_FILE, matrix_columns.clone()),
+            (LAG_FILE, matrix_columns),
+            (CLUSTER_FILE, node_columns),
+        ];
+        let mut paths = Vec::new();
+        let mut files = Vec::new();
+        for (name, columns) in headers {
+            let path = Path::new(data_directory).join(name);
+            let open_error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+            let file = match segments {
+                0 => {
+                    let mut file = File::create(&path).map_err(open_error)?;
+                    writeln!(file, "# segment\ttime\t{}", columns).map_err(open_error)?;
+                    file
+                }
+                _ => {
+                    let kept: String = fs::read_to_string(&path)
+                        .map_err(open_error)?
+                        .lines()
+                        .filter(
+                            |line| match line.split('\t').next().map(str::parse::<usize>) {
+                                Some(Ok(segment)) => segment < segments,
+                                _ => line.starts_with('#'),
+                            },
+                        )
+                        .map(|line| format!("{}\n", line))
+                        .collect();
+                    fs::write(&path, kept).map_err(open_error)?;
+                    OpenOptions::new()
+                        .append(true)
+                        .open(&path)
+                        .map_err(open_error)?
+                }
+            };
+            paths.push(path);
+            files.push(BufWriter::new(file));
+        }
+        Ok(SynchronisationFiles { paths, files })
+    }
+
+    // flushed right away, a checkpoint never refers to unwritten lines
+    pub fn write(
+        &mut self,
+        segment: usize,
+        time: f64,
+        synchrony: &Synchrony,
+    ) -> Result<(), String> {
+        let join = |values: &[f64]| {
+            values
+                .iter()
+                .map(f64::to_string)
+                .collect::<Vec<String>>()
+                .join("\t")
+        };
+        let clusters: Vec<String> = synchrony.clusters.iter().map(usize::to_string).collect();
+        let lines = [
+            format!(
+                "{}\t{}\t{}\t{}\t{}",
+                synchrony.order,
+                synchrony.min_order,
+                synchrony.error,
+                synchrony.max_error,
+                synchrony.number_of_clusters()
+            ),
+            join(&synchrony.correlations),
+            join(&synchrony.lags),
+            clusters.join("\t"),
+        ];
+        for ((file, path), line) in self.files.iter_mut().zip(&self.paths).zip(lines) {
+            let write_error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+            writeln!(file, "{}\t{}\t{}", segment, time, line).map_err(write_error)?;
+            file.flush().map_err(write_error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculation::{Calculation, CouplingLayout, NodeSetup, SystemType, Tasks};
+    use crate::network::Network;
+    use timeseries::SaveItems;
+
+    fn analysis(phases: Phases) -> SynchronisationAnalysis {
+        SynchronisationAnalysis {
+            segments: 1,
+            epsilon: 0.001,
+            variable: 0,
+            phases,
+            max_lag: 2.0,
+            cluster_tolerance: 1e-3,
+        }
+    }
+
+    #[test]
+    fn measures_of_shifted_oscillations() {
+        // nodes 2 and 3 lag behind nodes 0 and 1 by one time unit
+        let dt = 0.01;
+        let shifts = [0.0, 0.0, 1.0, 1.0];
+        let segment: Vec<Vec<f64>> = (0..3000)
+            .map(|i| {
+                let t = i as f64 * dt;
+                shifts
+                    .iter()
+                    .flat_map(|shift| [(t - shift).sin(), (t - shift).cos()])
+                    .collect()
+            })
+            .collect();
+        let expected_order = 0.5f64.cos();
+
+        let synchrony = analysis(Phases::Angle { second: 1 }).analyse(&segment, 4, dt);
+        assert!((synchrony.order - expected_order).abs() < 1e-3);
+        assert!((synchrony.min_order - expected_order).abs() < 1e-3);
+        assert!((synchrony.error - 0.5f64.sin()).abs() < 1e-9);
+        assert!((synchrony.max_error - 0.5f64.sin()).abs() < 1e-9);
+        assert_eq!(synchrony.clusters, vec![0, 0, 2, 2]);
+        assert_eq!(synchrony.number_of_clusters(), 2);
+        assert!((synchrony.lags[2] - 1.0).abs() < 1e-9);
+        assert!((synchrony.lags[3 * 4] + 1.0).abs() < 1e-9);
+        assert_eq!(synchrony.lags[1], 0.0);
+        assert!(synchrony.correlations.iter().all(|c| *c > 0.99));
+
+        let synchrony = analysis(Phases::Maxima).analyse(&segment, 4, dt);
+        assert!((synchrony.order - expected_order).abs() < 1e-3);
+    }
+
+    #[test]
+    fn constant_and_diverged_curves() {
+        let segment = vec![vec![1.0, 2.0]; 100];
+        let synchrony = analysis(Phases::Maxima).analyse(&segment, 2, 0.1);
+        assert!(synchrony.order.is_nan());
+        assert!(synchrony.correlations.iter().all(|c| c.is_nan()));
+        assert_eq!(synchrony.clusters, vec![0, 1]);
+
+        // the second node diverged
+        let segment = vec![vec![1.0, f64::NAN, 1.0, 1.0, 1.0, f64::NAN]; 100];
+        let synchrony = analysis(Phases::Maxima).analyse(&segment, 3, 0.1);
+        assert!(synchrony.max_error.is_nan());
+        assert_eq!(synchrony.clusters, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn resumed_analysis_continues_the_files() {
+        let dt = 1.0 / 64.0;
+        let mut network = Network::new(4, 0.1, 0.0, 1.0, 0, dt);
+        network.put_bi_ring(0.1, 0.0, 2.0);
+        let tasks = vec![
+            Tasks::PerturbStates {
+                amount: 0.5,
+                seed: 2,
+            },
+            Tasks::IntegrateSegmentsAndAnalyse(SynchronisationAnalysis {
+                segments: 5,
+                ..analysis(Phases::Angle { second: 1 })
+            }),
+        ];
+        let base = std::env::temp_dir().join("synchronisation_resumed_analysis");
+        let directory = |name: &str| base.join(name).to_string_lossy().to_string();
+        let run = |data_directory: &str| {
+            Calculation::examples_in_directory(
+                data_directory,
+                dt,
+                &network,
+                256,
+                NodeSetup::Identical,
+                SystemType::Roessler,
+                CouplingLayout::RingBuffers,
+                &tasks,
+                SaveItems::Timeseries,
+            )
+            .unwrap()
+        };
+        let read = |name: &str, file: &str| fs::read_to_string(base.join(name).join(file)).unwrap();
+
+        run(&directory("uninterrupted")).perform_tasks().unwrap();
+
+        // the last checkpoint is saved after 4 segments, the last line is written again
+        let checkpoint = base.join("run.checkpoint");
+        let mut interrupted = run(&directory("interrupted"));
+        interrupted.checkpoint_every(&checkpoint, 2);
+        interrupted.perform_tasks().unwrap();
+        drop(interrupted);
+        Calculation::resume_in_directory(&checkpoint, &directory("interrupted"), &network, &tasks)
+            .unwrap()
+            .perform_tasks()
+            .unwrap();
+
+        for file in [SYNCHRONY_FILE, CORRELATION_FILE, LAG_FILE, CLUSTER_FILE] {
+            let lines = read("uninterrupted", file);
+            assert_eq!(lines.lines().count(), 6);
+            assert_eq!(read("interrupted", file), lines);
+        }
+        fs::remove_dir_all(base).unwrap();
+    }
+}
@@ -34,73 +34,76 @@ impl<'a> Var<'a> {
                     Var::UFloat(0.0)
                 }
             }
-            Var::Phase(value) => Var::Phase((((value % 1.0) + 1.0) % 1.0).to_owned()),
-            Var::Turn(value) => Var::Turn((((value % TAU) + TAU) % TAU).to_owned()),
+            Var::Phase(value) => Var::Phase(value.rem_euclid(TAU)),
+            Var::Turn(value) => Var::Turn(value.rem_euclid(1.0)),
         }
     }
 
-    pub fn try_set(&mut self, string: &'a str) -> Self {
-        match self {
-            Var::Switch(_) => Var::Switch(string.parse::<bool>().expect("could not parse `bool`")),
-            Var::Word(_) => Var::Word(string),
-            Var::UInt(_) => Var::UInt(string.parse::<u32>().expect("could not parse `u32`")),
-            Var::Int(_) => Var::Int(string.parse::<i32>().expect("could not parse `i32`")),
-            Var::Float(_) => Var::Float(string.parse::<f64>().expect("could not parse `f64`")),
-            Var::UFloat(_) => Var::UFloat(string.parse::<f64>().expect("could not parse `+f64`")),
-            Var::Phase(_) => {
-                let mut phase_value: f64 = string.parse::<f64>().expect("could not parse `f64`");
-                if phase_value < 0.0 || phase_value >= TAU {
-                    println!("phase value outside range 0..2Pi, so it was wrapped around.");
-                    phase_value = ((phase_value % TAU) + TAU) % TAU;
-                };
-                Var::Phase(phase_value)
-            }
-            Var::Turn(_) => {
-                let mut turn_value: f64 = string.parse::<f64>().expect("could not parse `i32`");
-                if turn_value < 0.0 || turn_value >= 1.0 {
-                    println!("turn value outside range 0..1, so it was wrapped around.");
-                    turn_value = ((turn_value % TAU) + TAU) % TAU;
-                };
-                Var::Phase(turn_value)
-            }
-        }
+    // the value parsed from `string`, with the type of `self`. on failure: what was expected
+    pub fn try_set(&self, string: &'a str) -> Result<Self, &'static str> {
+        let mut var = *self;
+        var.try_parse(string)?;
+        Ok(var)
     }
 
-    pub fn try_parse(&mut self, string: &'a str) {
+    // on failure `self` is unchanged and the expected kind of value is returned
+    pub fn try_parse(&mut self, string: &'a str) -> Result<(), &'static str> {
+        let expected = self.kind();
+        let float = || string.parse::<f64>().map_err(|_| expected);
         match self {
-            Var::Switch(val) => *val = string.parse::<bool>().expect("could not parse `bool`"),
+            Var::Switch(val) => *val = string.parse::<bool>().map_err(|_| expected)?,
             Var::Word(val) => *val = string,
-            Var::UInt(val) => *val = string.parse::<u32>().expect("could not parse `u32`"),
-            Var::Int(val) => *val = string.parse::<i32>().expect("could not parse `i32`"),
-            Var::Float(val) => *val = string.parse::<f64>().expect("could not parse `f64`"),
+            Var::UInt(val) => *val = string.parse::<u32>().map_err(|_| expected)?,
+            Var::Int(val) => *val = string.parse::<i32>().map_err(|_| expected)?,
+            Var::Float(val) => *val = float()?,
             Var::UFloat(val) => {
-                let value = string.parse::<f64>().expect("could not parse `+f64`");
-                if value <= 0.0 {
-                    *val = -value
-                } else {
-                    *val = value
-                };
+                let value = float()?;
+                if value < 0.0 {
+                    return Err(expected);
+                }
+                *val = value;
             }
             Var::Phase(val) => {
-                let mut phase_value: f64 = string
-                    .parse::<f64>()
-                    .expect("could not parse `0.0 <= f64 < 2Pi`");
-                if phase_value < 0.0 || phase_value >= TAU {
+                let mut phase_value = float()?;
+                if !(0.0..TAU).contains(&phase_value) {
                     println!("phase value outside range 0..2Pi, so it was wrapped around.");
-                    phase_value = ((phase_value % TAU) + TAU) % TAU;
+                    phase_value = phase_value.rem_euclid(TAU);
                 };
                 *val = phase_value;
             }
             Var::Turn(val) => {
-                let mut turn_value: f64 = string.parse::<f64>().expect("could not parse `i32`");
-                if turn_value < 0.0 || turn_value >= 1.0 {
+                let mut turn_value = float()?;
+                if !(0.0..1.0).contains(&turn_value) {
                     println!("turn value outside range 0..1, so it was wrapped around.");
-                    turn_value = ((turn_value % TAU) + TAU) % TAU;
+                    turn_value = turn_value.rem_euclid(1.0);
                 };
                 *val = turn_value;
             }
         }
+        Ok(())
     }
+
+    // for error messages and the help text
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Var::Switch(_) => "true/false",
+            Var::Word(_) => "word",
+            Var::UInt(_) => "unsigned int",
+            Var::Int(_) => "int",
+            Var::UFloat(_) => "float >= 0",
+            Var::Float(_) => "float",
+            Var::Phase(_) => "phase [0..2Pi)",
+            Var::Turn(_) => "turn [0..1)",
+        }
+    }
+
+    pub fn word(&self) -> Option<&'a str> {
+        match *self {
+            Var::Word(word) => Some(word),
+            _ => None,
+        }
+    }
+
     pub fn get<T>(&self) -> Option<T>
     where
         T: VarTypes + Default,
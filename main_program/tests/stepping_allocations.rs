@@ -0,0 +1,224 @@
+// counting allocations needs its own global allocator, which would replace the allocator of
+// every other test in the same binary. the program is a binary crate, so its modules are
+// compiled into this test once more. `harness = false`: `main` runs the check by itself.
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/calculation.rs"]
+mod calculation;
+#[path = "../src/checkpoint.rs"]
+mod checkpoint;
+#[path = "../src/command_line.rs"]
+mod command_line;
+#[path = "../src/composite_system.rs"]
+mod composite_system;
+#[path = "../src/coupling_schedule.rs"]
+mod coupling_schedule;
+#[path = "../src/csr_history.rs"]
+mod csr_history;
+#[path = "../src/delay_buffer.rs"]
+mod delay_buffer;
+#[path = "../src/delay_coupling.rs"]
+mod delay_coupling;
+#[path = "../src/dynamical_system.rs"]
+mod dynamical_system;
+#[path = "../src/edge_selection.rs"]
+mod edge_selection;
+#[path = "../src/ensemble.rs"]
+mod ensemble;
+#[path = "../src/fitzhugh_nagumo.rs"]
+mod fitzhugh_nagumo;
+#[path = "../src/global_parameter_map.rs"]
+mod global_parameter_map;
+#[path = "../src/hindmarsh_rose.rs"]
+mod hindmarsh_rose;
+#[path = "../src/history.rs"]
+mod history;
+#[path = "../src/integration_methods.rs"]
+mod integration_methods;
+#[path = "../src/lang_kobayashi.rs"]
+mod lang_kobayashi;
+#[path = "../src/layered_history.rs"]
+mod layered_history;
+#[path = "../src/lorenz.rs"]
+mod lorenz;
+#[path = "../src/lyapunov.rs"]
+mod lyapunov;
+#[path = "../src/mackey_glass.rs"]
+mod mackey_glass;
+#[path = "../src/matrix.rs"]
+mod matrix;
+#[path = "../src/network.rs"]
+mod network;
+#[path = "../src/network_analysis.rs"]
+mod network_analysis;
+#[path = "../src/network_export.rs"]
+mod network_export;
+#[path = "../src/network_io.rs"]
+mod network_io;
+#[path = "../src/network_layers.rs"]
+mod network_layers;
+#[path = "../src/network_validation.rs"]
+mod network_validation;
+#[path = "../src/network_weights.rs"]
+mod network_weights;
+#[path = "../src/parameter.rs"]
+mod parameter;
+#[path = "../src/parameter_sweep.rs"]
+mod parameter_sweep;
+#[path = "../src/plasticity.rs"]
+mod plasticity;
+#[path = "../src/random_networks.rs"]
+mod random_networks;
+#[path = "../src/regime_map.rs"]
+mod regime_map;
+#[path = "../src/roessler.rs"]
+mod roessler;
+#[path = "../src/run_config.rs"]
+mod run_config;
+#[path = "../src/spatial_networks.rs"]
+mod spatial_networks;
+#[path = "../src/stuart_landau.rs"]
+mod stuart_landau;
+#[path = "../src/synchronisation.rs"]
+mod synchronisation;
+#[path = "../src/timer.rs"]
+mod timer;
+#[path = "../src/var.rs"]
+mod var;
+
+use composite_system::{
+    MultipleDistinctFeedbackSystems, MultipleIdenticalFeedbackSystems, SingleFeedbackSystem,
+};
+use coupling_schedule::{CouplingSchedule, EdgeParameter, Modulation};
+use csr_history::CsrHistory;
+use edge_selection::EdgeSelection;
+use integration_methods::{IntegrationMethods, RungeKuttaDelay};
+use network::{Network, SelectGroup};
+use plasticity::{Plasticity, PlasticityRule};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+// counts the allocations of the current thread, the `parallel` feature adds worker threads
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn ring(nodes: usize, dt: f64) -> Network {
+    let mut network = Network::new(nodes, 0.1, 0.0, 1.0, 0, dt);
+    network.put_bi_ring(0.1, 0.0, 10.0);
+    network
+}
+
+fn hebbian() -> Vec<Plasticity> {
+    vec![Plasticity {
+        rule: PlasticityRule::Hebbian {
+            rate: 0.01,
+            decay: 1.0,
+        },
+        selection: EdgeSelection::All,
+        min_strength: 0.0,
+        max_strength: 1.0,
+    }]
+}
+
+fn sinusoid() -> CouplingSchedule {
+    CouplingSchedule::new(1).add(
+        SelectGroup::AllGroups,
+        EdgeParameter::Strength,
+        Modulation::Sinusoid {
+            amplitude: 0.05,
+            period: 20.0,
+            phase: 0.0,
+        },
+    )
+}
+
+fn main() {
+    let dt = 1.0 / 64.0;
+    type Csr = CsrHistory<mackey_glass::System, RungeKuttaDelay<f64>>;
+    let mut systems: Vec<(&str, Box<dyn IntegrationMethods>)> = vec![
+        (
+            "identical",
+            Box::new(
+                MultipleIdenticalFeedbackSystems::<mackey_glass::System>::new(&ring(50, dt), dt),
+            ),
+        ),
+        (
+            "distinct",
+            Box::new(
+                MultipleDistinctFeedbackSystems::<mackey_glass::System>::new(&ring(50, dt), dt),
+            ),
+        ),
+        (
+            "single",
+            Box::new(SingleFeedbackSystem::<mackey_glass::System>::new(
+                &ring(1, dt),
+                dt,
+            )),
+        ),
+        (
+            "csr",
+            Box::new(
+                MultipleDistinctFeedbackSystems::<mackey_glass::System, Csr>::with_coupling(
+                    &ring(50, dt),
+                    dt,
+                ),
+            ),
+        ),
+    ];
+    for (name, coupling) in [
+        ("plastic", 0),
+        ("plastic csr", 1),
+        ("scheduled", 0),
+        ("scheduled csr", 1),
+    ] {
+        let mut system: Box<dyn IntegrationMethods> = if coupling == 0 {
+            Box::new(
+                MultipleDistinctFeedbackSystems::<mackey_glass::System>::new(&ring(50, dt), dt),
+            )
+        } else {
+            Box::new(
+                MultipleDistinctFeedbackSystems::<mackey_glass::System, Csr>::with_coupling(
+                    &ring(50, dt),
+                    dt,
+                ),
+            )
+        };
+        if name.starts_with("plastic") {
+            system.set_plasticity(hebbian()).unwrap();
+        } else {
+            system.set_coupling_schedule(sinusoid()).unwrap();
+        }
+        systems.push((name, system));
+    }
+
+    // the first steps may start the thread pool of the `parallel` feature and fill the scratch
+    // buffers of the schedule
+    for (_, system) in &mut systems {
+        system.n_steps_rk4(2);
+    }
+    let mut failed = false;
+    for (name, system) in &mut systems {
+        let before = ALLOCATIONS.with(|a| a.get());
+        system.n_steps_rk4(100);
+        let allocations = ALLOCATIONS.with(|a| a.get()) - before;
+        println!("{}: {} allocations in 100 steps", name, allocations);
+        failed |= allocations > 0;
+    }
+    assert!(!failed, "stepping allocates");
+}
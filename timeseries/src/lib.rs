@@ -1,13 +1,14 @@
 mod simplify_timeseries;
 
+use serde::{Deserialize, Serialize};
 use std::{
-    fs::{DirBuilder, File},
-    io::BufWriter,
+    fs::{DirBuilder, File, OpenOptions},
+    io::{self, BufWriter, Write},
 };
 
 use std::fmt::Display;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum SaveItems {
     Timeseries,
     ParametricCurve2d { variable_pairs: Vec<[usize; 2]> },
@@ -28,19 +29,26 @@ struct SaveFiles {
 }
 
 impl SaveFiles {
+    // `lengths`: the files exist and are continued after this many bytes (timeseries files
+    // first), empty for new files
     pub fn new(
         save_items: &SaveItems,
         directory_name: &str,
         nodes: usize,
         dyn_variables: &[&str],
-    ) -> Self {
-        match save_items {
+        lengths: &[u64],
+    ) -> io::Result<Self> {
+        let (timeseries_files, _) = Self::file_counts(save_items, nodes, dyn_variables);
+        let (timeseries_lengths, parametric_lengths) =
+            lengths.split_at(timeseries_files.min(lengths.len()));
+        Ok(match save_items {
             SaveItems::Timeseries => SaveFiles {
                 timeseries_files: Self::setup_timeseries_files(
                     directory_name,
                     nodes,
                     dyn_variables,
-                ),
+                    timeseries_lengths,
+                )?,
                 timeseries_written_lines: vec![0; nodes * dyn_variables.len()],
                 parametric_files: Vec::new(),
                 parametric_written_lines: Vec::new(),
@@ -53,7 +61,8 @@ impl SaveFiles {
                     nodes,
                     dyn_variables,
                     variable_pairs,
-                ),
+                    parametric_lengths,
+                )?,
                 parametric_written_lines: vec![0; nodes * variable_pairs.len()],
             },
             SaveItems::TimeseriesAndParametricCurve2d { variable_pairs } => SaveFiles {
@@ -61,16 +70,29 @@ impl SaveFiles {
                     directory_name,
                     nodes,
                     dyn_variables,
-                ),
+                    timeseries_lengths,
+                )?,
                 timeseries_written_lines: vec![0; nodes * dyn_variables.len()],
                 parametric_files: Self::setup_parametric_curve_files(
                     directory_name,
                     nodes,
                     dyn_variables,
                     variable_pairs,
-                ),
+                    parametric_lengths,
+                )?,
                 parametric_written_lines: vec![0; nodes * variable_pairs.len()],
             },
+        })
+    }
+
+    // the number of timeseries and of parametric curve files
+    fn file_counts(save_items: &SaveItems, nodes: usize, dyn_variables: &[&str]) -> (usize, usize) {
+        match save_items {
+            SaveItems::Timeseries => (nodes * dyn_variables.len(), 0),
+            SaveItems::ParametricCurve2d { variable_pairs } => (0, nodes * variable_pairs.len()),
+            SaveItems::TimeseriesAndParametricCurve2d { variable_pairs } => {
+                (nodes * dyn_variables.len(), nodes * variable_pairs.len())
+            }
         }
     }
 
@@ -78,19 +100,20 @@ impl SaveFiles {
         directory_name: &str,
         nodes: usize,
         dyn_variables: &[&str],
-    ) -> Vec<BufWriter<File>> {
+        lengths: &[u64],
+    ) -> io::Result<Vec<BufWriter<File>>> {
         (0..nodes)
             .flat_map(|node| {
                 dyn_variables.iter().map(move |var_name| {
-                    let filename = format!(
+                    format!(
                         "{}/trajectory_{:02}_{}.txt",
                         directory_name, node, &var_name
-                    );
-                    let file = File::create(filename).unwrap();
-                    BufWriter::new(file)
+                    )
                 })
             })
-            .collect::<Vec<BufWriter<File>>>()
+            .enumerate()
+            .map(|(i, filename)| open_output_file(&filename, lengths.get(i).copied()))
+            .collect()
     }
 
     fn setup_parametric_curve_files(
@@ -98,22 +121,52 @@ impl SaveFiles {
         nodes: usize,
         dyn_variables: &[&str],
         variable_pairs: &[[usize; 2]],
-    ) -> Vec<BufWriter<File>> {
+        lengths: &[u64],
+    ) -> io::Result<Vec<BufWriter<File>>> {
         (0..nodes)
             .flat_map(|node| {
                 variable_pairs.iter().map(move |[first, second]| {
-                    let filename = format!(
+                    format!(
                         "{}/parametric_curve_{:02}_{}_{}.txt",
                         directory_name, node, dyn_variables[*first], dyn_variables[*second]
-                    );
-                    let file = File::create(filename).unwrap();
-                    BufWriter::new(file)
+                    )
                 })
             })
-            .collect::<Vec<BufWriter<File>>>()
+            .enumerate()
+            .map(|(i, filename)| open_output_file(&filename, lengths.get(i).copied()))
+            .collect()
     }
 }
 
+// a new file, or with `length` an existing one that is cut back to `length` bytes and
+// continued, e.g. after a restart from a checkpoint
+fn open_output_file(filename: &str, length: Option<u64>) -> io::Result<BufWriter<File>> {
+    let file = match length {
+        None => File::create(filename)?,
+        Some(length) => {
+            let file = OpenOptions::new().append(true).open(filename)?;
+            if file.metadata()?.len() < length {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is shorter than when it was saved", filename),
+                ));
+            }
+            file.set_len(length)?;
+            file
+        }
+    };
+    Ok(BufWriter::new(file))
+}
+
+// how far the output files are written, enough to continue them after a restart
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TimeseriesProgress {
+    pub written_segments: u64,
+    pub timeseries_written_lines: Vec<u64>,
+    pub parametric_written_lines: Vec<u64>,
+    pub file_lengths: Vec<u64>, // bytes, timeseries files first
+}
+
 pub struct Timeseries {
     dt: f64,
     num_nodes: usize,
@@ -134,27 +187,136 @@ impl Timeseries {
         dyn_var_names: &[&str],
         save_items: &SaveItems,
     ) -> Self {
+        Self::new_in_directory(
+            "./data",
+            dt,
+            num_nodes,
+            dim_save_state,
+            segment_size,
+            dyn_var_names,
+            save_items,
+        )
+    }
+
+    // the output files are written to `data_directory`, it is created if needed
+    pub fn new_in_directory(
+        data_directory: &str,
+        dt: f64,
+        num_nodes: usize,
+        dim_save_state: usize,
+        segment_size: usize,
+        dyn_var_names: &[&str],
+        save_items: &SaveItems,
+    ) -> Self {
+        Self::open_in_directory(
+            data_directory,
+            dt,
+            num_nodes,
+            dim_save_state,
+            segment_size,
+            dyn_var_names,
+            save_items,
+            &TimeseriesProgress::default(),
+        )
+        .unwrap()
+    }
+
+    // continues the files in `data_directory` where `progress` was taken, later lines are
+    // dropped. the other arguments have to be the same as for the first run.
+    #[allow(clippy::too_many_arguments)]
+    pub fn resume_in_directory(
+        data_directory: &str,
+        dt: f64,
+        num_nodes: usize,
+        dim_save_state: usize,
+        segment_size: usize,
+        dyn_var_names: &[&str],
+        save_items: &SaveItems,
+        progress: &TimeseriesProgress,
+    ) -> io::Result<Self> {
+        // checked before any file is opened, a mismatched progress must not cut them back
+        let (timeseries_files, parametric_files) =
+            SaveFiles::file_counts(save_items, num_nodes, dyn_var_names);
+        if progress.timeseries_written_lines.len() != timeseries_files
+            || progress.parametric_written_lines.len() != parametric_files
+            || progress.file_lengths.len() != timeseries_files + parametric_files
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the saved progress is of other output files",
+            ));
+        }
+        Self::open_in_directory(
+            data_directory,
+            dt,
+            num_nodes,
+            dim_save_state,
+            segment_size,
+            dyn_var_names,
+            save_items,
+            progress,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn open_in_directory(
+        data_directory: &str,
+        dt: f64,
+        num_nodes: usize,
+        dim_save_state: usize,
+        segment_size: usize,
+        dyn_var_names: &[&str],
+        save_items: &SaveItems,
+        progress: &TimeseriesProgress,
+    ) -> io::Result<Self> {
         let full_dimension = num_nodes * dim_save_state;
 
         assert_eq!(dim_save_state, dyn_var_names.len());
 
-        let curve_directory_name = "data";
-        let data_directory = format!("./{}", &curve_directory_name);
-        DirBuilder::new()
-            .recursive(true)
-            .create(&data_directory)
-            .unwrap();
+        DirBuilder::new().recursive(true).create(data_directory)?;
+
+        let mut output_files = SaveFiles::new(
+            save_items,
+            data_directory,
+            num_nodes,
+            dyn_var_names,
+            &progress.file_lengths,
+        )?;
+        if !progress.file_lengths.is_empty() {
+            output_files.timeseries_written_lines = progress.timeseries_written_lines.clone();
+            output_files.parametric_written_lines = progress.parametric_written_lines.clone();
+        }
 
-        Timeseries {
+        Ok(Timeseries {
             dt,
             num_nodes,
             num_variables: dyn_var_names.len(),
             segment_start_time: 0.0,
             segment: vec![vec![0.0; full_dimension]; segment_size],
-            output_files: SaveFiles::new(&save_items, &data_directory, num_nodes, dyn_var_names),
-            written_segments: 0,
+            output_files,
+            written_segments: progress.written_segments,
             // written_lines: vec![0; full_dimension],
+        })
+    }
+
+    // flushes the output files and returns how far they are written
+    pub fn progress(&mut self) -> io::Result<TimeseriesProgress> {
+        let mut file_lengths = Vec::new();
+        for file in self
+            .output_files
+            .timeseries_files
+            .iter_mut()
+            .chain(self.output_files.parametric_files.iter_mut())
+        {
+            file.flush()?;
+            file_lengths.push(file.get_ref().metadata()?.len());
         }
+        Ok(TimeseriesProgress {
+            written_segments: self.written_segments,
+            timeseries_written_lines: self.output_files.timeseries_written_lines.clone(),
+            parametric_written_lines: self.output_files.parametric_written_lines.clone(),
+            file_lengths,
+        })
     }
 
     pub fn segment(&mut self) -> &mut Vec<Vec<f64>> {
@@ -165,6 +327,11 @@ impl Timeseries {
         self.segment_start_time = *time;
     }
 
+    // time before the first row of the segment
+    pub fn segment_start_time(&self) -> f64 {
+        self.segment_start_time
+    }
+
     // save simplified timeseries in individual files
     pub fn save_simplified_timeseries(&mut self, epsilon: &f64) {
         simplify_timeseries::simplify_curves_individually(
@@ -258,4 +425,37 @@ mod tests {
             distance_point_to_line_squared_2d(a_2, b_2, p_2) as f32
         );
     }
+
+    #[test]
+    fn mismatched_progress_keeps_the_files() {
+        let directory = std::env::temp_dir().join(format!("resume_{}", std::process::id()));
+        let directory = directory.to_str().unwrap();
+        DirBuilder::new().recursive(true).create(directory).unwrap();
+        let filename = format!("{}/trajectory_00_x.txt", directory);
+        std::fs::write(&filename, "0 1\n").unwrap();
+        // one file for two variables
+        let progress = TimeseriesProgress {
+            written_segments: 1,
+            timeseries_written_lines: vec![1, 0],
+            parametric_written_lines: Vec::new(),
+            file_lengths: vec![4],
+        };
+        let resumed = Timeseries::resume_in_directory(
+            directory,
+            0.1,
+            1,
+            2,
+            10,
+            &["x", "y"],
+            &SaveItems::Timeseries,
+            &progress,
+        );
+        assert_eq!(
+            resumed.err().map(|e| e.kind()),
+            Some(io::ErrorKind::InvalidData)
+        );
+        assert_eq!(std::fs::read_to_string(&filename).unwrap(), "0 1\n");
+        assert!(!std::path::Path::new(&format!("{}/trajectory_00_y.txt", directory)).exists());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
 }